@@ -0,0 +1,319 @@
+// Copyright 2021, Collabora Ltd.
+// SPDX-License-Identifier: MIT
+
+//! Safe views over the data planes of a `spa_buffer`.
+
+use bitflags::bitflags;
+use std::slice;
+
+/// The kind of memory a [`Data`] plane is stored in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataType {
+    Invalid,
+    /// Memory that is directly accessible through a pointer.
+    MemPtr,
+    /// Memory in a file descriptor, which can be mapped.
+    MemFd,
+    /// A DMA-BUF file descriptor.
+    DmaBuf,
+    /// Memory identified by an id.
+    MemId,
+}
+
+impl DataType {
+    /// The raw `spa_data_type` of this type.
+    pub fn as_raw(self) -> u32 {
+        match self {
+            DataType::Invalid => spa_sys::spa_data_type_SPA_DATA_Invalid,
+            DataType::MemPtr => spa_sys::spa_data_type_SPA_DATA_MemPtr,
+            DataType::MemFd => spa_sys::spa_data_type_SPA_DATA_MemFd,
+            DataType::DmaBuf => spa_sys::spa_data_type_SPA_DATA_DmaBuf,
+            DataType::MemId => spa_sys::spa_data_type_SPA_DATA_MemId,
+        }
+    }
+
+    /// Get the type matching a raw `spa_data_type`, if any.
+    pub fn from_raw(raw: u32) -> Option<Self> {
+        match raw {
+            spa_sys::spa_data_type_SPA_DATA_Invalid => Some(DataType::Invalid),
+            spa_sys::spa_data_type_SPA_DATA_MemPtr => Some(DataType::MemPtr),
+            spa_sys::spa_data_type_SPA_DATA_MemFd => Some(DataType::MemFd),
+            spa_sys::spa_data_type_SPA_DATA_DmaBuf => Some(DataType::DmaBuf),
+            spa_sys::spa_data_type_SPA_DATA_MemId => Some(DataType::MemId),
+            _ => None,
+        }
+    }
+}
+
+bitflags! {
+    pub struct DataFlags: u32 {
+        /// The data can be read.
+        const READABLE = spa_sys::SPA_DATA_FLAG_READABLE;
+        /// The data can be written.
+        const WRITABLE = spa_sys::SPA_DATA_FLAG_WRITABLE;
+        /// The data pointer can change at each cycle.
+        const DYNAMIC = spa_sys::SPA_DATA_FLAG_DYNAMIC;
+    }
+}
+
+/// The valid region of a [`Data`] plane, equivalent to `spa_chunk`.
+#[repr(transparent)]
+pub struct Chunk(spa_sys::spa_chunk);
+
+impl Chunk {
+    /// The offset of the valid data in the plane.
+    pub fn offset(&self) -> u32 {
+        self.0.offset
+    }
+
+    pub fn offset_mut(&mut self) -> &mut u32 {
+        &mut self.0.offset
+    }
+
+    /// The size of the valid data, in bytes.
+    pub fn size(&self) -> u32 {
+        self.0.size
+    }
+
+    pub fn size_mut(&mut self) -> &mut u32 {
+        &mut self.0.size
+    }
+
+    /// The stride of the valid data, such as the size of an audio frame or of a video line.
+    pub fn stride(&self) -> i32 {
+        self.0.stride
+    }
+
+    pub fn stride_mut(&mut self) -> &mut i32 {
+        &mut self.0.stride
+    }
+
+    /// The raw `SPA_CHUNK_FLAG_*` flags of the chunk.
+    pub fn flags(&self) -> i32 {
+        self.0.flags
+    }
+
+    pub fn flags_mut(&mut self) -> &mut i32 {
+        &mut self.0.flags
+    }
+}
+
+/// A sample type that any properly aligned bytes can be viewed as.
+///
+/// # Safety
+/// Every bit pattern must be a valid value of the type, and it must not contain padding.
+pub unsafe trait Sample: Copy {}
+
+unsafe impl Sample for u8 {}
+unsafe impl Sample for i8 {}
+unsafe impl Sample for u16 {}
+unsafe impl Sample for i16 {}
+unsafe impl Sample for u32 {}
+unsafe impl Sample for i32 {}
+unsafe impl Sample for f32 {}
+unsafe impl Sample for f64 {}
+
+/// A data plane of a buffer, equivalent to `spa_data`.
+#[repr(transparent)]
+pub struct Data(spa_sys::spa_data);
+
+impl Data {
+    /// Get a slice of `Data` from the raw `datas` of a `spa_buffer`.
+    ///
+    /// # Safety
+    /// `datas` must point to `n_datas` valid `spa_data`, which must stay valid and not be accessed
+    /// by anything else for `'a`. Their `chunk` and `data` pointers must either be null or point to
+    /// a valid chunk and to `maxsize` bytes of memory.
+    pub unsafe fn from_raw_mut<'a>(datas: *mut spa_sys::spa_data, n_datas: u32) -> &'a mut [Self] {
+        if datas.is_null() || n_datas == 0 {
+            &mut []
+        } else {
+            slice::from_raw_parts_mut(datas.cast(), n_datas as usize)
+        }
+    }
+
+    pub fn as_raw(&self) -> &spa_sys::spa_data {
+        &self.0
+    }
+
+    /// The type of the memory, `None` if it is not known.
+    pub fn type_(&self) -> Option<DataType> {
+        DataType::from_raw(self.0.type_)
+    }
+
+    pub fn flags(&self) -> DataFlags {
+        DataFlags::from_bits_truncate(self.0.flags)
+    }
+
+    /// The file descriptor of the memory, for `MemFd` and `DmaBuf` planes.
+    pub fn fd(&self) -> Option<i64> {
+        match self.type_() {
+            Some(DataType::MemFd) | Some(DataType::DmaBuf) => Some(self.0.fd),
+            _ => None,
+        }
+    }
+
+    /// The offset of the plane in the memory of `fd`.
+    pub fn map_offset(&self) -> u32 {
+        self.0.mapoffset
+    }
+
+    /// The size of the plane, in bytes.
+    pub fn max_size(&self) -> u32 {
+        self.0.maxsize
+    }
+
+    /// The memory of the plane.
+    ///
+    /// Returns `None` if the plane is not mapped, which is the case of `MemFd` and `DmaBuf` planes
+    /// unless the stream was connected with `MAP_BUFFERS`.
+    pub fn data(&self) -> Option<&[u8]> {
+        if self.0.data.is_null() {
+            None
+        } else {
+            unsafe {
+                Some(slice::from_raw_parts(
+                    self.0.data.cast(),
+                    self.0.maxsize as usize,
+                ))
+            }
+        }
+    }
+
+    /// The memory of the plane, to be written to.
+    ///
+    /// See [`data`](Self::data) for when this returns `None`.
+    pub fn data_mut(&mut self) -> Option<&mut [u8]> {
+        if self.0.data.is_null() {
+            None
+        } else {
+            unsafe {
+                Some(slice::from_raw_parts_mut(
+                    self.0.data.cast(),
+                    self.0.maxsize as usize,
+                ))
+            }
+        }
+    }
+
+    /// The memory of the plane as samples of type `T`, such as interleaved `f32` audio.
+    ///
+    /// Returns `None` if the plane is not mapped or is not aligned for `T`.
+    /// Trailing bytes that are too short to hold a sample are left out.
+    pub fn data_as<T: Sample>(&self) -> Option<&[T]> {
+        let data = self.data()?;
+        // Safety: `Sample` types can be built from any bytes.
+        let (prefix, samples, _) = unsafe { data.align_to::<T>() };
+        if prefix.is_empty() {
+            Some(samples)
+        } else {
+            None
+        }
+    }
+
+    /// The memory of the plane as samples of type `T`, to be written to.
+    ///
+    /// See [`data_as`](Self::data_as) for when this returns `None`.
+    pub fn data_as_mut<T: Sample>(&mut self) -> Option<&mut [T]> {
+        let data = self.data_mut()?;
+        // Safety: `Sample` types can be built from any bytes and turned back into bytes.
+        let (prefix, samples, _) = unsafe { data.align_to_mut::<T>() };
+        if prefix.is_empty() {
+            Some(samples)
+        } else {
+            None
+        }
+    }
+
+    /// The part of the plane holding valid data, as described by its chunk.
+    ///
+    /// The chunk is clamped to the plane, as the C examples do, so this never fails on a bogus
+    /// chunk sent by the peer.
+    pub fn chunk_data(&self) -> Option<&[u8]> {
+        let chunk = self.chunk()?;
+        let data = self.data()?;
+        let offset = (chunk.offset() as usize).min(data.len());
+        let size = (chunk.size() as usize).min(data.len() - offset);
+        Some(&data[offset..offset + size])
+    }
+
+    pub fn chunk(&self) -> Option<&Chunk> {
+        unsafe { self.0.chunk.cast::<Chunk>().as_ref() }
+    }
+
+    pub fn chunk_mut(&mut self) -> Option<&mut Chunk> {
+        unsafe { self.0.chunk.cast::<Chunk>().as_mut() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{mem, ptr};
+
+    fn raw_data(memory: &mut [u64], chunk: &mut spa_sys::spa_chunk) -> spa_sys::spa_data {
+        spa_sys::spa_data {
+            type_: spa_sys::spa_data_type_SPA_DATA_MemPtr,
+            flags: spa_sys::SPA_DATA_FLAG_READABLE | spa_sys::SPA_DATA_FLAG_WRITABLE,
+            fd: -1,
+            mapoffset: 0,
+            maxsize: mem::size_of_val(memory) as u32,
+            data: memory.as_mut_ptr().cast(),
+            chunk,
+        }
+    }
+
+    #[test]
+    fn samples() {
+        let mut memory = [0u64; 4];
+        let mut chunk: spa_sys::spa_chunk = unsafe { mem::zeroed() };
+        let mut raw = [raw_data(&mut memory, &mut chunk)];
+        let datas = unsafe { Data::from_raw_mut(raw.as_mut_ptr(), 1) };
+        let data = &mut datas[0];
+
+        assert_eq!(data.type_(), Some(DataType::MemPtr));
+        assert_eq!(data.flags(), DataFlags::READABLE | DataFlags::WRITABLE);
+        assert_eq!(data.fd(), None);
+
+        let samples = data.data_as_mut::<f32>().unwrap();
+        assert_eq!(samples.len(), 8);
+        samples[1] = 0.5;
+        let chunk = data.chunk_mut().unwrap();
+        *chunk.size_mut() = 8;
+        *chunk.stride_mut() = 4;
+
+        assert_eq!(data.data_as::<i16>().unwrap().len(), 16);
+        assert_eq!(data.chunk_data().unwrap(), &[0, 0, 0, 0, 0, 0, 0, 0x3f][..]);
+        assert_eq!(data.chunk().unwrap().stride(), 4);
+    }
+
+    #[test]
+    fn misaligned() {
+        let mut memory = [0u64; 2];
+        let mut chunk: spa_sys::spa_chunk = unsafe { mem::zeroed() };
+        let mut raw = raw_data(&mut memory, &mut chunk);
+        raw.data = unsafe { raw.data.cast::<u8>().add(1).cast() };
+        raw.maxsize -= 1;
+        let datas = unsafe { Data::from_raw_mut(&mut raw, 1) };
+
+        assert_eq!(datas[0].data().unwrap().len(), 15);
+        assert!(datas[0].data_as::<f32>().is_none());
+        assert_eq!(datas[0].data_as::<u8>().unwrap().len(), 15);
+    }
+
+    #[test]
+    fn unmapped() {
+        let mut chunk: spa_sys::spa_chunk = unsafe { mem::zeroed() };
+        chunk.offset = 64;
+        chunk.size = 64;
+        let mut raw = raw_data(&mut [], &mut chunk);
+        raw.type_ = spa_sys::spa_data_type_SPA_DATA_MemFd;
+        raw.fd = 42;
+        raw.data = ptr::null_mut();
+        let datas = unsafe { Data::from_raw_mut(&mut raw, 1) };
+
+        assert_eq!(datas[0].fd(), Some(42));
+        assert!(datas[0].data().is_none());
+        assert!(datas[0].chunk_data().is_none());
+    }
+}
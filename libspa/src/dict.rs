@@ -110,6 +110,12 @@ impl ReadableDict for ForeignDict {
     }
 }
 
+impl<D: ReadableDict + ?Sized> ReadableDict for &D {
+    fn get_dict_ptr(&self) -> *const spa_sys::spa_dict {
+        (**self).get_dict_ptr()
+    }
+}
+
 impl fmt::Debug for ForeignDict {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         // FIXME: Find a way to display flags too.
@@ -37,6 +37,13 @@
 /// ```
 #[macro_export]
 macro_rules! spa_interface_call_method {
+    ($interface_ptr:expr, $methods_struct:ty, $method:ident) => {{
+        let iface: *mut spa_sys::spa_interface = $interface_ptr.cast();
+        let funcs: *const $methods_struct = (*iface).cb.funcs.cast();
+        let f = (*funcs).$method.unwrap();
+
+        f((*iface).cb.data)
+    }};
     ($interface_ptr:expr, $methods_struct:ty, $method:ident, $( $arg:expr ),*) => {{
         let iface: *mut spa_sys::spa_interface = $interface_ptr.cast();
         let funcs: *const $methods_struct = (*iface).cb.funcs.cast();
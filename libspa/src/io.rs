@@ -0,0 +1,87 @@
+// Copyright 2021, Collabora Ltd.
+// SPDX-License-Identifier: MIT
+
+//! IO areas, shared between the graph and its nodes to exchange timing information.
+
+use crate::pod::Fraction;
+
+/// The clock driving the graph, equivalent to `spa_io_clock`.
+#[repr(transparent)]
+pub struct Clock(spa_sys::spa_io_clock);
+
+impl Clock {
+    pub fn as_raw(&self) -> &spa_sys::spa_io_clock {
+        &self.0
+    }
+
+    /// The id of the clock.
+    pub fn id(&self) -> u32 {
+        self.0.id
+    }
+
+    /// The time of the current cycle, in nanoseconds.
+    pub fn nsec(&self) -> u64 {
+        self.0.nsec
+    }
+
+    /// The rate of `position` and `duration`.
+    pub fn rate(&self) -> Fraction {
+        Fraction {
+            num: self.0.rate.num,
+            denom: self.0.rate.denom,
+        }
+    }
+
+    /// The position of the current cycle, in samples.
+    pub fn position(&self) -> u64 {
+        self.0.position
+    }
+
+    /// The duration of the current cycle, in samples.
+    pub fn duration(&self) -> u64 {
+        self.0.duration
+    }
+
+    /// The delay between the position and the hardware, in samples.
+    pub fn delay(&self) -> i64 {
+        self.0.delay
+    }
+
+    /// The rate difference between the clock and the monotonic time.
+    pub fn rate_diff(&self) -> f64 {
+        self.0.rate_diff
+    }
+
+    /// The estimated time of the next cycle, in nanoseconds.
+    pub fn next_nsec(&self) -> u64 {
+        self.0.next_nsec
+    }
+}
+
+/// The position of the graph, equivalent to `spa_io_position`.
+#[repr(transparent)]
+pub struct Position(spa_sys::spa_io_position);
+
+impl Position {
+    /// Borrow a raw `spa_io_position`.
+    ///
+    /// # Safety
+    /// `position` must be null or point to a valid position that stays valid for `'a`.
+    pub unsafe fn from_raw<'a>(position: *const spa_sys::spa_io_position) -> Option<&'a Self> {
+        position.cast::<Self>().as_ref()
+    }
+
+    pub fn as_raw(&self) -> &spa_sys::spa_io_position {
+        &self.0
+    }
+
+    pub fn clock(&self) -> &Clock {
+        // Safety: `Clock` is a transparent wrapper around `spa_io_clock`.
+        unsafe { &*(&self.0.clock as *const spa_sys::spa_io_clock).cast::<Clock>() }
+    }
+
+    /// The offset subtracted from the clock position to get the running time of the graph.
+    pub fn offset(&self) -> i64 {
+        self.0.offset
+    }
+}
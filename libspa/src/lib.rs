@@ -20,11 +20,15 @@ use std::{
     rc::Rc,
 };
 
+pub mod buffer;
 pub mod dict;
 pub mod hook;
 pub mod interface;
+pub mod io;
 pub mod list;
 pub mod names;
+pub mod param;
+pub mod pod;
 pub mod support;
 
 pub type Result<T = (), E = Error> = std::result::Result<T, E>;
@@ -410,6 +414,40 @@ pub struct Interface<'a, T> {
 }
 */
 
+/// The direction of a port, or of the data flowing through a stream.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Direction {
+    Input,
+    Output,
+}
+
+impl Direction {
+    /// The raw `spa_direction` of this direction.
+    pub fn as_raw(self) -> spa_sys::spa_direction {
+        match self {
+            Direction::Input => spa_sys::spa_direction_SPA_DIRECTION_INPUT,
+            Direction::Output => spa_sys::spa_direction_SPA_DIRECTION_OUTPUT,
+        }
+    }
+
+    /// Get the direction matching a raw `spa_direction`.
+    pub fn from_raw(raw: spa_sys::spa_direction) -> Option<Self> {
+        match raw {
+            spa_sys::spa_direction_SPA_DIRECTION_INPUT => Some(Direction::Input),
+            spa_sys::spa_direction_SPA_DIRECTION_OUTPUT => Some(Direction::Output),
+            _ => None,
+        }
+    }
+
+    /// The opposite direction.
+    pub fn reverse(self) -> Self {
+        match self {
+            Direction::Input => Direction::Output,
+            Direction::Output => Direction::Input,
+        }
+    }
+}
+
 #[derive(Debug, Copy, Clone)]
 pub struct SpaResult(i32);
 /*
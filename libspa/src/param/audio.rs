@@ -0,0 +1,829 @@
+// Copyright 2021, Collabora Ltd.
+// SPDX-License-Identifier: MIT
+
+//! Raw audio formats.
+
+use bitflags::bitflags;
+use std::{convert::TryFrom, io};
+
+use super::{
+    id, int, optional, require, ChoiceValue, FormatError, MediaSubtype, MediaType, ParamType,
+};
+use crate::pod::{Builder, Object, Pod, Value};
+
+/// The maximum number of channels of a raw audio format.
+pub const MAX_CHANNELS: usize = spa_sys::SPA_AUDIO_MAX_CHANNELS as usize;
+
+raw_enum! {
+    /// A raw audio sample format.
+    ///
+    /// Planar formats store each channel in its own buffer, other formats interleave channels.
+    pub enum AudioFormat {
+        Unknown = spa_sys::spa_audio_format_SPA_AUDIO_FORMAT_UNKNOWN,
+        Encoded = spa_sys::spa_audio_format_SPA_AUDIO_FORMAT_ENCODED,
+        S8 = spa_sys::spa_audio_format_SPA_AUDIO_FORMAT_S8,
+        U8 = spa_sys::spa_audio_format_SPA_AUDIO_FORMAT_U8,
+        S16LE = spa_sys::spa_audio_format_SPA_AUDIO_FORMAT_S16_LE,
+        S16BE = spa_sys::spa_audio_format_SPA_AUDIO_FORMAT_S16_BE,
+        U16LE = spa_sys::spa_audio_format_SPA_AUDIO_FORMAT_U16_LE,
+        U16BE = spa_sys::spa_audio_format_SPA_AUDIO_FORMAT_U16_BE,
+        S24_32LE = spa_sys::spa_audio_format_SPA_AUDIO_FORMAT_S24_32_LE,
+        S24_32BE = spa_sys::spa_audio_format_SPA_AUDIO_FORMAT_S24_32_BE,
+        U24_32LE = spa_sys::spa_audio_format_SPA_AUDIO_FORMAT_U24_32_LE,
+        U24_32BE = spa_sys::spa_audio_format_SPA_AUDIO_FORMAT_U24_32_BE,
+        S32LE = spa_sys::spa_audio_format_SPA_AUDIO_FORMAT_S32_LE,
+        S32BE = spa_sys::spa_audio_format_SPA_AUDIO_FORMAT_S32_BE,
+        U32LE = spa_sys::spa_audio_format_SPA_AUDIO_FORMAT_U32_LE,
+        U32BE = spa_sys::spa_audio_format_SPA_AUDIO_FORMAT_U32_BE,
+        S24LE = spa_sys::spa_audio_format_SPA_AUDIO_FORMAT_S24_LE,
+        S24BE = spa_sys::spa_audio_format_SPA_AUDIO_FORMAT_S24_BE,
+        U24LE = spa_sys::spa_audio_format_SPA_AUDIO_FORMAT_U24_LE,
+        U24BE = spa_sys::spa_audio_format_SPA_AUDIO_FORMAT_U24_BE,
+        S20LE = spa_sys::spa_audio_format_SPA_AUDIO_FORMAT_S20_LE,
+        S20BE = spa_sys::spa_audio_format_SPA_AUDIO_FORMAT_S20_BE,
+        U20LE = spa_sys::spa_audio_format_SPA_AUDIO_FORMAT_U20_LE,
+        U20BE = spa_sys::spa_audio_format_SPA_AUDIO_FORMAT_U20_BE,
+        S18LE = spa_sys::spa_audio_format_SPA_AUDIO_FORMAT_S18_LE,
+        S18BE = spa_sys::spa_audio_format_SPA_AUDIO_FORMAT_S18_BE,
+        U18LE = spa_sys::spa_audio_format_SPA_AUDIO_FORMAT_U18_LE,
+        U18BE = spa_sys::spa_audio_format_SPA_AUDIO_FORMAT_U18_BE,
+        F32LE = spa_sys::spa_audio_format_SPA_AUDIO_FORMAT_F32_LE,
+        F32BE = spa_sys::spa_audio_format_SPA_AUDIO_FORMAT_F32_BE,
+        F64LE = spa_sys::spa_audio_format_SPA_AUDIO_FORMAT_F64_LE,
+        F64BE = spa_sys::spa_audio_format_SPA_AUDIO_FORMAT_F64_BE,
+        U8P = spa_sys::spa_audio_format_SPA_AUDIO_FORMAT_U8P,
+        S16P = spa_sys::spa_audio_format_SPA_AUDIO_FORMAT_S16P,
+        S24_32P = spa_sys::spa_audio_format_SPA_AUDIO_FORMAT_S24_32P,
+        S32P = spa_sys::spa_audio_format_SPA_AUDIO_FORMAT_S32P,
+        S24P = spa_sys::spa_audio_format_SPA_AUDIO_FORMAT_S24P,
+        F32P = spa_sys::spa_audio_format_SPA_AUDIO_FORMAT_F32P,
+        F64P = spa_sys::spa_audio_format_SPA_AUDIO_FORMAT_F64P,
+        S8P = spa_sys::spa_audio_format_SPA_AUDIO_FORMAT_S8P,
+    }
+}
+
+#[cfg(target_endian = "little")]
+impl AudioFormat {
+    /// Native endian signed 16 bit samples.
+    pub const S16: Self = AudioFormat::S16LE;
+    /// Native endian signed 24 bit samples, stored in 32 bits.
+    pub const S24_32: Self = AudioFormat::S24_32LE;
+    /// Native endian signed 32 bit samples.
+    pub const S32: Self = AudioFormat::S32LE;
+    /// Native endian signed 24 bit samples.
+    pub const S24: Self = AudioFormat::S24LE;
+    /// Native endian 32 bit float samples.
+    pub const F32: Self = AudioFormat::F32LE;
+    /// Native endian 64 bit float samples.
+    pub const F64: Self = AudioFormat::F64LE;
+}
+
+#[cfg(target_endian = "big")]
+impl AudioFormat {
+    /// Native endian signed 16 bit samples.
+    pub const S16: Self = AudioFormat::S16BE;
+    /// Native endian signed 24 bit samples, stored in 32 bits.
+    pub const S24_32: Self = AudioFormat::S24_32BE;
+    /// Native endian signed 32 bit samples.
+    pub const S32: Self = AudioFormat::S32BE;
+    /// Native endian signed 24 bit samples.
+    pub const S24: Self = AudioFormat::S24BE;
+    /// Native endian 32 bit float samples.
+    pub const F32: Self = AudioFormat::F32BE;
+    /// Native endian 64 bit float samples.
+    pub const F64: Self = AudioFormat::F64BE;
+}
+
+/// First raw value of the auxiliary channels.
+const CHANNEL_AUX_START: u32 = 0x1000;
+/// Last raw value of the auxiliary channels.
+const CHANNEL_AUX_LAST: u32 = 0x1fff;
+
+/// The position of an audio channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AudioChannel {
+    Unknown,
+    /// Not applicable.
+    NA,
+    Mono,
+    FL,
+    FR,
+    FC,
+    LFE,
+    SL,
+    SR,
+    FLC,
+    FRC,
+    RC,
+    RL,
+    RR,
+    TC,
+    TFL,
+    TFC,
+    TFR,
+    TRL,
+    TRC,
+    TRR,
+    RLC,
+    RRC,
+    FLW,
+    FRW,
+    LFE2,
+    FLH,
+    FCH,
+    FRH,
+    TFLC,
+    TFRC,
+    TSL,
+    TSR,
+    LLFE,
+    RLFE,
+    BC,
+    BLC,
+    BRC,
+    /// An auxiliary channel without a defined position.
+    Aux(AuxChannel),
+}
+
+/// The number of an auxiliary audio channel, from 0 to [`AuxChannel::MAX`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct AuxChannel(u16);
+
+impl AuxChannel {
+    /// The highest number of an auxiliary channel.
+    pub const MAX: u16 = (CHANNEL_AUX_LAST - CHANNEL_AUX_START) as u16;
+
+    /// The auxiliary channel numbered `n`, if `n` is not above [`MAX`](Self::MAX).
+    pub fn new(n: u16) -> Option<Self> {
+        if n <= Self::MAX {
+            Some(Self(n))
+        } else {
+            None
+        }
+    }
+
+    /// The number of the channel.
+    pub fn get(self) -> u16 {
+        self.0
+    }
+}
+
+impl AudioChannel {
+    /// The raw `spa_audio_channel` of this position.
+    pub fn as_raw(self) -> u32 {
+        match self {
+            AudioChannel::Unknown => spa_sys::spa_audio_channel_SPA_AUDIO_CHANNEL_UNKNOWN,
+            AudioChannel::NA => spa_sys::spa_audio_channel_SPA_AUDIO_CHANNEL_NA,
+            AudioChannel::Mono => spa_sys::spa_audio_channel_SPA_AUDIO_CHANNEL_MONO,
+            AudioChannel::FL => spa_sys::spa_audio_channel_SPA_AUDIO_CHANNEL_FL,
+            AudioChannel::FR => spa_sys::spa_audio_channel_SPA_AUDIO_CHANNEL_FR,
+            AudioChannel::FC => spa_sys::spa_audio_channel_SPA_AUDIO_CHANNEL_FC,
+            AudioChannel::LFE => spa_sys::spa_audio_channel_SPA_AUDIO_CHANNEL_LFE,
+            AudioChannel::SL => spa_sys::spa_audio_channel_SPA_AUDIO_CHANNEL_SL,
+            AudioChannel::SR => spa_sys::spa_audio_channel_SPA_AUDIO_CHANNEL_SR,
+            AudioChannel::FLC => spa_sys::spa_audio_channel_SPA_AUDIO_CHANNEL_FLC,
+            AudioChannel::FRC => spa_sys::spa_audio_channel_SPA_AUDIO_CHANNEL_FRC,
+            AudioChannel::RC => spa_sys::spa_audio_channel_SPA_AUDIO_CHANNEL_RC,
+            AudioChannel::RL => spa_sys::spa_audio_channel_SPA_AUDIO_CHANNEL_RL,
+            AudioChannel::RR => spa_sys::spa_audio_channel_SPA_AUDIO_CHANNEL_RR,
+            AudioChannel::TC => spa_sys::spa_audio_channel_SPA_AUDIO_CHANNEL_TC,
+            AudioChannel::TFL => spa_sys::spa_audio_channel_SPA_AUDIO_CHANNEL_TFL,
+            AudioChannel::TFC => spa_sys::spa_audio_channel_SPA_AUDIO_CHANNEL_TFC,
+            AudioChannel::TFR => spa_sys::spa_audio_channel_SPA_AUDIO_CHANNEL_TFR,
+            AudioChannel::TRL => spa_sys::spa_audio_channel_SPA_AUDIO_CHANNEL_TRL,
+            AudioChannel::TRC => spa_sys::spa_audio_channel_SPA_AUDIO_CHANNEL_TRC,
+            AudioChannel::TRR => spa_sys::spa_audio_channel_SPA_AUDIO_CHANNEL_TRR,
+            AudioChannel::RLC => spa_sys::spa_audio_channel_SPA_AUDIO_CHANNEL_RLC,
+            AudioChannel::RRC => spa_sys::spa_audio_channel_SPA_AUDIO_CHANNEL_RRC,
+            AudioChannel::FLW => spa_sys::spa_audio_channel_SPA_AUDIO_CHANNEL_FLW,
+            AudioChannel::FRW => spa_sys::spa_audio_channel_SPA_AUDIO_CHANNEL_FRW,
+            AudioChannel::LFE2 => spa_sys::spa_audio_channel_SPA_AUDIO_CHANNEL_LFE2,
+            AudioChannel::FLH => spa_sys::spa_audio_channel_SPA_AUDIO_CHANNEL_FLH,
+            AudioChannel::FCH => spa_sys::spa_audio_channel_SPA_AUDIO_CHANNEL_FCH,
+            AudioChannel::FRH => spa_sys::spa_audio_channel_SPA_AUDIO_CHANNEL_FRH,
+            AudioChannel::TFLC => spa_sys::spa_audio_channel_SPA_AUDIO_CHANNEL_TFLC,
+            AudioChannel::TFRC => spa_sys::spa_audio_channel_SPA_AUDIO_CHANNEL_TFRC,
+            AudioChannel::TSL => spa_sys::spa_audio_channel_SPA_AUDIO_CHANNEL_TSL,
+            AudioChannel::TSR => spa_sys::spa_audio_channel_SPA_AUDIO_CHANNEL_TSR,
+            AudioChannel::LLFE => spa_sys::spa_audio_channel_SPA_AUDIO_CHANNEL_LLFE,
+            AudioChannel::RLFE => spa_sys::spa_audio_channel_SPA_AUDIO_CHANNEL_RLFE,
+            AudioChannel::BC => spa_sys::spa_audio_channel_SPA_AUDIO_CHANNEL_BC,
+            AudioChannel::BLC => spa_sys::spa_audio_channel_SPA_AUDIO_CHANNEL_BLC,
+            AudioChannel::BRC => spa_sys::spa_audio_channel_SPA_AUDIO_CHANNEL_BRC,
+            AudioChannel::Aux(n) => CHANNEL_AUX_START + u32::from(n.get()),
+        }
+    }
+
+    /// Get the position matching a raw `spa_audio_channel`, if any.
+    pub fn from_raw(raw: u32) -> Option<Self> {
+        let channel = match raw {
+            spa_sys::spa_audio_channel_SPA_AUDIO_CHANNEL_UNKNOWN => AudioChannel::Unknown,
+            spa_sys::spa_audio_channel_SPA_AUDIO_CHANNEL_NA => AudioChannel::NA,
+            spa_sys::spa_audio_channel_SPA_AUDIO_CHANNEL_MONO => AudioChannel::Mono,
+            spa_sys::spa_audio_channel_SPA_AUDIO_CHANNEL_FL => AudioChannel::FL,
+            spa_sys::spa_audio_channel_SPA_AUDIO_CHANNEL_FR => AudioChannel::FR,
+            spa_sys::spa_audio_channel_SPA_AUDIO_CHANNEL_FC => AudioChannel::FC,
+            spa_sys::spa_audio_channel_SPA_AUDIO_CHANNEL_LFE => AudioChannel::LFE,
+            spa_sys::spa_audio_channel_SPA_AUDIO_CHANNEL_SL => AudioChannel::SL,
+            spa_sys::spa_audio_channel_SPA_AUDIO_CHANNEL_SR => AudioChannel::SR,
+            spa_sys::spa_audio_channel_SPA_AUDIO_CHANNEL_FLC => AudioChannel::FLC,
+            spa_sys::spa_audio_channel_SPA_AUDIO_CHANNEL_FRC => AudioChannel::FRC,
+            spa_sys::spa_audio_channel_SPA_AUDIO_CHANNEL_RC => AudioChannel::RC,
+            spa_sys::spa_audio_channel_SPA_AUDIO_CHANNEL_RL => AudioChannel::RL,
+            spa_sys::spa_audio_channel_SPA_AUDIO_CHANNEL_RR => AudioChannel::RR,
+            spa_sys::spa_audio_channel_SPA_AUDIO_CHANNEL_TC => AudioChannel::TC,
+            spa_sys::spa_audio_channel_SPA_AUDIO_CHANNEL_TFL => AudioChannel::TFL,
+            spa_sys::spa_audio_channel_SPA_AUDIO_CHANNEL_TFC => AudioChannel::TFC,
+            spa_sys::spa_audio_channel_SPA_AUDIO_CHANNEL_TFR => AudioChannel::TFR,
+            spa_sys::spa_audio_channel_SPA_AUDIO_CHANNEL_TRL => AudioChannel::TRL,
+            spa_sys::spa_audio_channel_SPA_AUDIO_CHANNEL_TRC => AudioChannel::TRC,
+            spa_sys::spa_audio_channel_SPA_AUDIO_CHANNEL_TRR => AudioChannel::TRR,
+            spa_sys::spa_audio_channel_SPA_AUDIO_CHANNEL_RLC => AudioChannel::RLC,
+            spa_sys::spa_audio_channel_SPA_AUDIO_CHANNEL_RRC => AudioChannel::RRC,
+            spa_sys::spa_audio_channel_SPA_AUDIO_CHANNEL_FLW => AudioChannel::FLW,
+            spa_sys::spa_audio_channel_SPA_AUDIO_CHANNEL_FRW => AudioChannel::FRW,
+            spa_sys::spa_audio_channel_SPA_AUDIO_CHANNEL_LFE2 => AudioChannel::LFE2,
+            spa_sys::spa_audio_channel_SPA_AUDIO_CHANNEL_FLH => AudioChannel::FLH,
+            spa_sys::spa_audio_channel_SPA_AUDIO_CHANNEL_FCH => AudioChannel::FCH,
+            spa_sys::spa_audio_channel_SPA_AUDIO_CHANNEL_FRH => AudioChannel::FRH,
+            spa_sys::spa_audio_channel_SPA_AUDIO_CHANNEL_TFLC => AudioChannel::TFLC,
+            spa_sys::spa_audio_channel_SPA_AUDIO_CHANNEL_TFRC => AudioChannel::TFRC,
+            spa_sys::spa_audio_channel_SPA_AUDIO_CHANNEL_TSL => AudioChannel::TSL,
+            spa_sys::spa_audio_channel_SPA_AUDIO_CHANNEL_TSR => AudioChannel::TSR,
+            spa_sys::spa_audio_channel_SPA_AUDIO_CHANNEL_LLFE => AudioChannel::LLFE,
+            spa_sys::spa_audio_channel_SPA_AUDIO_CHANNEL_RLFE => AudioChannel::RLFE,
+            spa_sys::spa_audio_channel_SPA_AUDIO_CHANNEL_BC => AudioChannel::BC,
+            spa_sys::spa_audio_channel_SPA_AUDIO_CHANNEL_BLC => AudioChannel::BLC,
+            spa_sys::spa_audio_channel_SPA_AUDIO_CHANNEL_BRC => AudioChannel::BRC,
+            CHANNEL_AUX_START..=CHANNEL_AUX_LAST => {
+                AudioChannel::Aux(AuxChannel((raw - CHANNEL_AUX_START) as u16))
+            }
+            _ => return None,
+        };
+        Some(channel)
+    }
+}
+
+bitflags! {
+    /// Flags of a raw audio format.
+    pub struct AudioFlags: u32 {
+        /// The channels have no position, `AudioInfoRaw::position` is ignored.
+        const UNPOSITIONED = spa_sys::SPA_AUDIO_FLAG_UNPOSITIONED;
+    }
+}
+
+/// A raw audio format, equivalent to `spa_audio_info_raw`.
+///
+/// Use [`build`](Self::build) to turn it into an `EnumFormat` or `Format` param, and
+/// [`parse`](Self::parse) to read it back from the params a node or port reports.
+///
+/// Fields left to their default value are omitted from the built format, leaving them for the
+/// other side of the negotiation to pick.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AudioInfoRaw {
+    pub format: AudioFormat,
+    pub flags: AudioFlags,
+    pub rate: u32,
+    pub channels: u32,
+    /// The position of each channel, should have `channels` entries unless the format is
+    /// [`UNPOSITIONED`](AudioFlags::UNPOSITIONED).
+    pub position: Vec<AudioChannel>,
+}
+
+impl Default for AudioInfoRaw {
+    fn default() -> Self {
+        AudioInfoRaw {
+            format: AudioFormat::Unknown,
+            flags: AudioFlags::empty(),
+            rate: 0,
+            channels: 0,
+            position: Vec::new(),
+        }
+    }
+}
+
+impl AudioInfoRaw {
+    /// Create an empty format, with every field left to be negotiated.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add this format to `builder` as a `Format` object with the given `id`,
+    /// equivalent to `spa_format_audio_raw_build`.
+    ///
+    /// # Examples
+    /// ```
+    /// use libspa::param::{audio::{AudioChannel, AudioFormat, AudioInfoRaw}, ParamType};
+    /// use libspa::pod::Builder;
+    ///
+    /// let info = AudioInfoRaw {
+    ///     format: AudioFormat::F32LE,
+    ///     rate: 48000,
+    ///     channels: 2,
+    ///     position: vec![AudioChannel::FL, AudioChannel::FR],
+    ///     ..AudioInfoRaw::new()
+    /// };
+    ///
+    /// let mut data = Vec::new();
+    /// info.build(&mut Builder::with_vec(&mut data), ParamType::EnumFormat).unwrap();
+    /// ```
+    ///
+    /// Fails with [`InvalidInput`](io::ErrorKind::InvalidInput) if `position` does not have
+    /// `channels` entries, or has more than [`MAX_CHANNELS`].
+    pub fn build(&self, builder: &mut Builder, id: ParamType) -> io::Result<()> {
+        let positioned = self.channels != 0 && !self.flags.contains(AudioFlags::UNPOSITIONED);
+        if positioned {
+            check_position(&self.position, self.channels)?;
+        }
+        let rate = to_int(self.rate)?;
+        let channels = to_int(self.channels)?;
+
+        push_audio_format(builder, id)?;
+
+        if self.format != AudioFormat::Unknown {
+            builder.prop(spa_sys::spa_format_SPA_FORMAT_AUDIO_format, 0)?;
+            builder.id(self.format.as_raw())?;
+        }
+        if rate != 0 {
+            builder.prop(spa_sys::spa_format_SPA_FORMAT_AUDIO_rate, 0)?;
+            builder.int(rate)?;
+        }
+        if channels != 0 {
+            builder.prop(spa_sys::spa_format_SPA_FORMAT_AUDIO_channels, 0)?;
+            builder.int(channels)?;
+        }
+        if positioned {
+            build_position(builder, &self.position)?;
+        }
+
+        builder.pop()
+    }
+
+    /// Parse a raw audio format, equivalent to `spa_format_audio_raw_parse`.
+    ///
+    /// The format, rate and channels must be fixed. If the format has no channel positions,
+    /// the [`UNPOSITIONED`](AudioFlags::UNPOSITIONED) flag is set.
+    pub fn parse(format: &Pod) -> Result<Self, FormatError> {
+        Self::from_object(&super::format_object(format)?)
+    }
+
+    /// Parse a raw audio format from an already parsed `Format` object.
+    pub fn from_object(format: &Object) -> Result<Self, FormatError> {
+        if format.type_ != spa_sys::SPA_TYPE_OBJECT_Format {
+            return Err(FormatError::NotAFormat);
+        }
+
+        let audio_format = require(format, spa_sys::spa_format_SPA_FORMAT_AUDIO_format, |v| {
+            id(v).and_then(AudioFormat::from_raw)
+        })?;
+        let rate = require(format, spa_sys::spa_format_SPA_FORMAT_AUDIO_rate, uint)?;
+        let channels = require(format, spa_sys::spa_format_SPA_FORMAT_AUDIO_channels, uint)?;
+        let position = optional(
+            format,
+            spa_sys::spa_format_SPA_FORMAT_AUDIO_position,
+            |v| match v {
+                // Positions this version doesn't know about are reported as unknown, so the
+                // format can still be used.
+                Value::Array(values) => values
+                    .iter()
+                    .take(MAX_CHANNELS)
+                    .map(|v| {
+                        id(v)
+                            .map(|raw| AudioChannel::from_raw(raw).unwrap_or(AudioChannel::Unknown))
+                    })
+                    .collect::<Option<Vec<_>>>(),
+                _ => None,
+            },
+        )?
+        .unwrap_or_default();
+
+        let mut flags = AudioFlags::empty();
+        if position.is_empty() {
+            flags.insert(AudioFlags::UNPOSITIONED);
+        }
+
+        Ok(AudioInfoRaw {
+            format: audio_format,
+            flags,
+            rate,
+            channels,
+            position,
+        })
+    }
+}
+
+/// The raw audio formats a port can handle, to be built as an `EnumFormat` param.
+///
+/// Unlike [`AudioInfoRaw`], the format, rate and channels can offer a range or a list of values
+/// for the other side of the negotiation to pick from. Properties set to `None` are omitted.
+///
+/// # Examples
+/// ```
+/// use libspa::param::{audio::{AudioEnumFormat, AudioFormat}, ChoiceValue, ParamType};
+/// use libspa::pod::Builder;
+///
+/// let mut formats = AudioEnumFormat::new(ChoiceValue::Enum {
+///     default: AudioFormat::F32LE,
+///     alternatives: vec![AudioFormat::F32LE, AudioFormat::S16LE],
+/// });
+/// formats.rate = Some(ChoiceValue::Range {
+///     default: 48000,
+///     min: 1,
+///     max: 384000,
+/// });
+/// formats.channels = Some(2.into());
+///
+/// let mut data = Vec::new();
+/// formats.build(&mut Builder::with_vec(&mut data), ParamType::EnumFormat).unwrap();
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct AudioEnumFormat {
+    pub format: ChoiceValue<AudioFormat>,
+    pub rate: Option<ChoiceValue<u32>>,
+    pub channels: Option<ChoiceValue<u32>>,
+    /// The position of each channel, which must have as many entries as the channels when they
+    /// are fixed.
+    pub position: Option<Vec<AudioChannel>>,
+}
+
+impl AudioEnumFormat {
+    /// Offer `format`, leaving every other property to be negotiated.
+    pub fn new(format: impl Into<ChoiceValue<AudioFormat>>) -> Self {
+        AudioEnumFormat {
+            format: format.into(),
+            rate: None,
+            channels: None,
+            position: None,
+        }
+    }
+
+    /// Add the formats to `builder` as a `Format` object with the given `id`.
+    ///
+    /// Fails with [`InvalidInput`](io::ErrorKind::InvalidInput) if `position` does not have as
+    /// many entries as the fixed channels, or has more than [`MAX_CHANNELS`].
+    pub fn build(&self, builder: &mut Builder, id: ParamType) -> io::Result<()> {
+        if let Some(position) = &self.position {
+            match self.channels {
+                Some(ChoiceValue::Fixed(channels)) => check_position(position, channels)?,
+                _ => check_position(position, position.len() as u32)?,
+            }
+        }
+
+        push_audio_format(builder, id)?;
+
+        builder.prop(spa_sys::spa_format_SPA_FORMAT_AUDIO_format, 0)?;
+        self.format.build(builder, |b, f| b.id(f.as_raw()))?;
+
+        if let Some(rate) = &self.rate {
+            builder.prop(spa_sys::spa_format_SPA_FORMAT_AUDIO_rate, 0)?;
+            rate.build(builder, |b, r| b.int(to_int(r)?))?;
+        }
+        if let Some(channels) = &self.channels {
+            builder.prop(spa_sys::spa_format_SPA_FORMAT_AUDIO_channels, 0)?;
+            channels.build(builder, |b, c| b.int(to_int(c)?))?;
+        }
+        if let Some(position) = &self.position {
+            build_position(builder, position)?;
+        }
+
+        builder.pop()
+    }
+}
+
+/// Start an audio `Format` object, with the media type and subtype properties.
+fn push_audio_format(builder: &mut Builder, id: ParamType) -> io::Result<()> {
+    builder.push_object(spa_sys::SPA_TYPE_OBJECT_Format, id.as_raw())?;
+    builder.prop(spa_sys::spa_format_SPA_FORMAT_mediaType, 0)?;
+    builder.id(MediaType::Audio.as_raw())?;
+    builder.prop(spa_sys::spa_format_SPA_FORMAT_mediaSubtype, 0)?;
+    builder.id(MediaSubtype::Raw.as_raw())
+}
+
+/// Add the `position` property of a format.
+fn build_position(builder: &mut Builder, position: &[AudioChannel]) -> io::Result<()> {
+    builder.prop(spa_sys::spa_format_SPA_FORMAT_AUDIO_position, 0)?;
+    builder.push_array()?;
+    for channel in position {
+        builder.id(channel.as_raw())?;
+    }
+    builder.pop()
+}
+
+/// Check that `position` has a position for each of the `channels`.
+fn check_position(position: &[AudioChannel], channels: u32) -> io::Result<()> {
+    if position.len() > MAX_CHANNELS {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("more than {} channel positions", MAX_CHANNELS),
+        ))
+    } else if position.len() != channels as usize {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "{} channel positions for {} channels",
+                position.len(),
+                channels
+            ),
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// Convert `value` to the `Int` used for the rate and channels.
+fn to_int(value: u32) -> io::Result<i32> {
+    i32::try_from(value).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} is too large for an Int", value),
+        )
+    })
+}
+
+/// Convert an `Int` property that can't be negative.
+fn uint(value: &Value) -> Option<u32> {
+    int(value).and_then(|int| u32::try_from(int).ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pod::ChoiceType;
+
+    fn stereo() -> AudioInfoRaw {
+        AudioInfoRaw {
+            format: AudioFormat::S16LE,
+            flags: AudioFlags::empty(),
+            rate: 44100,
+            channels: 2,
+            position: vec![AudioChannel::FL, AudioChannel::FR],
+        }
+    }
+
+    fn build(info: &AudioInfoRaw) -> Vec<u8> {
+        let mut data = Vec::new();
+        info.build(&mut Builder::with_vec(&mut data), ParamType::Format)
+            .unwrap();
+        data
+    }
+
+    #[test]
+    fn roundtrip() {
+        let info = stereo();
+        let data = build(&info);
+        let pod = Pod::from_bytes(&data).unwrap();
+
+        assert_eq!(AudioInfoRaw::parse(&pod).unwrap(), info);
+        assert_eq!(
+            super::super::parse_format(&pod).unwrap(),
+            (MediaType::Audio, MediaSubtype::Raw)
+        );
+
+        match pod.value().unwrap() {
+            Value::Object(object) => {
+                assert_eq!(object.id, ParamType::Format.as_raw());
+                assert_eq!(object.properties.len(), 6);
+            }
+            _ => panic!("not an object"),
+        }
+    }
+
+    #[test]
+    fn unpositioned() {
+        let info = AudioInfoRaw {
+            flags: AudioFlags::UNPOSITIONED,
+            position: Vec::new(),
+            ..stereo()
+        };
+        let data = build(&info);
+        let pod = Pod::from_bytes(&data).unwrap();
+
+        assert_eq!(AudioInfoRaw::parse(&pod).unwrap(), info);
+    }
+
+    #[test]
+    fn aux_channels() {
+        let aux = AuxChannel::new(3).unwrap();
+        assert_eq!(AudioChannel::from_raw(0x1003), Some(AudioChannel::Aux(aux)));
+        assert_eq!(AudioChannel::Aux(aux).as_raw(), 0x1003);
+
+        let last = AuxChannel::new(AuxChannel::MAX).unwrap();
+        assert_eq!(
+            AudioChannel::from_raw(0x1fff),
+            Some(AudioChannel::Aux(last))
+        );
+        assert_eq!(AudioChannel::Aux(last).as_raw(), 0x1fff);
+        assert_eq!(AuxChannel::new(AuxChannel::MAX + 1), None);
+        assert_eq!(AudioChannel::from_raw(0x2000), None);
+        assert_eq!(
+            AudioChannel::from_raw(AudioChannel::FL.as_raw()),
+            Some(AudioChannel::FL)
+        );
+    }
+
+    #[test]
+    fn unknown_channels() {
+        let mut data = Vec::new();
+        let mut builder = Builder::with_vec(&mut data);
+        push_audio_format(&mut builder, ParamType::Format).unwrap();
+        builder
+            .prop(spa_sys::spa_format_SPA_FORMAT_AUDIO_format, 0)
+            .unwrap();
+        builder.id(AudioFormat::S16LE.as_raw()).unwrap();
+        builder
+            .prop(spa_sys::spa_format_SPA_FORMAT_AUDIO_rate, 0)
+            .unwrap();
+        builder.int(44100).unwrap();
+        builder
+            .prop(spa_sys::spa_format_SPA_FORMAT_AUDIO_channels, 0)
+            .unwrap();
+        builder.int(2).unwrap();
+        builder
+            .prop(spa_sys::spa_format_SPA_FORMAT_AUDIO_position, 0)
+            .unwrap();
+        builder.push_array().unwrap();
+        builder.id(AudioChannel::FL.as_raw()).unwrap();
+        builder.id(0x8000).unwrap();
+        builder.pop().unwrap();
+        builder.pop().unwrap();
+
+        let info = AudioInfoRaw::parse(&Pod::from_bytes(&data).unwrap()).unwrap();
+        assert_eq!(info.position, [AudioChannel::FL, AudioChannel::Unknown]);
+    }
+
+    #[test]
+    fn negative_rate() {
+        let mut data = Vec::new();
+        let mut builder = Builder::with_vec(&mut data);
+        push_audio_format(&mut builder, ParamType::Format).unwrap();
+        builder
+            .prop(spa_sys::spa_format_SPA_FORMAT_AUDIO_format, 0)
+            .unwrap();
+        builder.id(AudioFormat::S16LE.as_raw()).unwrap();
+        builder
+            .prop(spa_sys::spa_format_SPA_FORMAT_AUDIO_rate, 0)
+            .unwrap();
+        builder.int(-1).unwrap();
+        builder
+            .prop(spa_sys::spa_format_SPA_FORMAT_AUDIO_channels, 0)
+            .unwrap();
+        builder.int(2).unwrap();
+        builder.pop().unwrap();
+
+        assert_eq!(
+            AudioInfoRaw::parse(&Pod::from_bytes(&data).unwrap()),
+            Err(FormatError::InvalidProperty(
+                spa_sys::spa_format_SPA_FORMAT_AUDIO_rate
+            ))
+        );
+    }
+
+    #[test]
+    fn invalid_position() {
+        let mut data = Vec::new();
+        let mono = AudioInfoRaw {
+            channels: 1,
+            ..stereo()
+        };
+        let error = mono
+            .build(&mut Builder::with_vec(&mut data), ParamType::Format)
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+
+        let many = AudioInfoRaw {
+            channels: MAX_CHANNELS as u32 + 1,
+            position: vec![AudioChannel::Mono; MAX_CHANNELS + 1],
+            ..stereo()
+        };
+        let error = many
+            .build(&mut Builder::with_vec(&mut data), ParamType::Format)
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        assert!(data.is_empty());
+    }
+
+    #[test]
+    fn enum_format() {
+        let mut formats = AudioEnumFormat::new(AudioFormat::F32P);
+        formats.rate = Some(ChoiceValue::Enum {
+            default: 48000,
+            alternatives: vec![48000, 44100],
+        });
+        formats.channels = Some(ChoiceValue::Range {
+            default: 2,
+            min: 1,
+            max: 8,
+        });
+        let mut data = Vec::new();
+        formats
+            .build(&mut Builder::with_vec(&mut data), ParamType::EnumFormat)
+            .unwrap();
+        let pod = Pod::from_bytes(&data).unwrap();
+
+        let object = match pod.value().unwrap() {
+            Value::Object(object) => object,
+            _ => panic!("not an object"),
+        };
+        let rate = object
+            .property(spa_sys::spa_format_SPA_FORMAT_AUDIO_rate)
+            .unwrap();
+        match &rate.value {
+            Value::Choice(choice) => {
+                assert_eq!(choice.type_, ChoiceType::Enum);
+                assert_eq!(
+                    choice.values,
+                    vec![Value::Int(48000), Value::Int(48000), Value::Int(44100)]
+                );
+            }
+            value => panic!("unexpected rate {:?}", value),
+        }
+        let channels = object
+            .property(spa_sys::spa_format_SPA_FORMAT_AUDIO_channels)
+            .unwrap();
+        match &channels.value {
+            Value::Choice(choice) => assert_eq!(choice.type_, ChoiceType::Range),
+            value => panic!("unexpected channels {:?}", value),
+        }
+
+        formats.channels = Some(2.into());
+        formats.position = Some(vec![AudioChannel::Mono]);
+        let error = formats
+            .build(
+                &mut Builder::with_vec(&mut Vec::new()),
+                ParamType::EnumFormat,
+            )
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn missing_properties() {
+        // Only the media type and subtype are written.
+        let data = build(&AudioInfoRaw::new());
+        let pod = Pod::from_bytes(&data).unwrap();
+
+        assert_eq!(
+            AudioInfoRaw::parse(&pod),
+            Err(FormatError::MissingProperty(
+                spa_sys::spa_format_SPA_FORMAT_AUDIO_format
+            ))
+        );
+    }
+
+    #[test]
+    fn choices() {
+        let build_rate = |choice: ChoiceType| {
+            let mut data = Vec::new();
+            let mut builder = Builder::with_vec(&mut data);
+            builder
+                .push_object(
+                    spa_sys::SPA_TYPE_OBJECT_Format,
+                    ParamType::EnumFormat.as_raw(),
+                )
+                .unwrap();
+            builder
+                .prop(spa_sys::spa_format_SPA_FORMAT_AUDIO_format, 0)
+                .unwrap();
+            builder.id(AudioFormat::F32P.as_raw()).unwrap();
+            builder
+                .prop(spa_sys::spa_format_SPA_FORMAT_AUDIO_rate, 0)
+                .unwrap();
+            builder.push_choice(choice, 0).unwrap();
+            builder.int(48000).unwrap();
+            builder.int(44100).unwrap();
+            builder.pop().unwrap();
+            builder
+                .prop(spa_sys::spa_format_SPA_FORMAT_AUDIO_channels, 0)
+                .unwrap();
+            builder.int(1).unwrap();
+            builder.pop().unwrap();
+            data
+        };
+
+        // A `None` choice is as good as a fixed value.
+        let data = build_rate(ChoiceType::None);
+        let info = AudioInfoRaw::parse(&Pod::from_bytes(&data).unwrap()).unwrap();
+        assert_eq!(info.format, AudioFormat::F32P);
+        assert_eq!(info.rate, 48000);
+        assert_eq!(info.flags, AudioFlags::UNPOSITIONED);
+
+        let data = build_rate(ChoiceType::Enum);
+        assert_eq!(
+            AudioInfoRaw::parse(&Pod::from_bytes(&data).unwrap()),
+            Err(FormatError::InvalidProperty(
+                spa_sys::spa_format_SPA_FORMAT_AUDIO_rate
+            ))
+        );
+    }
+
+    #[test]
+    fn not_a_format() {
+        let mut data = Vec::new();
+        Builder::with_vec(&mut data).int(1).unwrap();
+
+        assert_eq!(
+            AudioInfoRaw::parse(&Pod::from_bytes(&data).unwrap()),
+            Err(FormatError::NotAFormat)
+        );
+    }
+}
@@ -0,0 +1,380 @@
+// Copyright 2021, Collabora Ltd.
+// SPDX-License-Identifier: MIT
+
+//! Types and functions to build and parse the params exchanged by nodes and ports.
+
+use std::{fmt, io};
+
+use crate::pod::{Builder, ChoiceType, Fraction, Object, ParseError, Pod, Rectangle, Value};
+
+/// Generate an enum whose variants map to raw SPA values, with conversion methods.
+macro_rules! raw_enum {
+    (
+        $(#[$meta:meta])*
+        pub enum $name:ident {
+            $(
+                $(#[$variant_meta:meta])*
+                $variant:ident = $raw:path,
+            )*
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum $name {
+            $(
+                $(#[$variant_meta])*
+                $variant,
+            )*
+        }
+
+        impl $name {
+            /// The raw SPA value of this variant.
+            pub fn as_raw(self) -> u32 {
+                match self {
+                    $($name::$variant => $raw,)*
+                }
+            }
+
+            /// Get the variant matching a raw SPA value, if any.
+            pub fn from_raw(raw: u32) -> Option<Self> {
+                match raw {
+                    $($raw => Some($name::$variant),)*
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+pub mod audio;
+pub mod video;
+
+raw_enum! {
+    /// The type of a param, used as the id of the param objects.
+    pub enum ParamType {
+        Invalid = spa_sys::spa_param_type_SPA_PARAM_Invalid,
+        PropInfo = spa_sys::spa_param_type_SPA_PARAM_PropInfo,
+        Props = spa_sys::spa_param_type_SPA_PARAM_Props,
+        EnumFormat = spa_sys::spa_param_type_SPA_PARAM_EnumFormat,
+        Format = spa_sys::spa_param_type_SPA_PARAM_Format,
+        Buffers = spa_sys::spa_param_type_SPA_PARAM_Buffers,
+        Meta = spa_sys::spa_param_type_SPA_PARAM_Meta,
+        IO = spa_sys::spa_param_type_SPA_PARAM_IO,
+        EnumProfile = spa_sys::spa_param_type_SPA_PARAM_EnumProfile,
+        Profile = spa_sys::spa_param_type_SPA_PARAM_Profile,
+        EnumPortConfig = spa_sys::spa_param_type_SPA_PARAM_EnumPortConfig,
+        PortConfig = spa_sys::spa_param_type_SPA_PARAM_PortConfig,
+        EnumRoute = spa_sys::spa_param_type_SPA_PARAM_EnumRoute,
+        Route = spa_sys::spa_param_type_SPA_PARAM_Route,
+    }
+}
+
+raw_enum! {
+    /// The media type of a format.
+    pub enum MediaType {
+        Unknown = spa_sys::spa_media_type_SPA_MEDIA_TYPE_unknown,
+        Audio = spa_sys::spa_media_type_SPA_MEDIA_TYPE_audio,
+        Video = spa_sys::spa_media_type_SPA_MEDIA_TYPE_video,
+        Image = spa_sys::spa_media_type_SPA_MEDIA_TYPE_image,
+        Binary = spa_sys::spa_media_type_SPA_MEDIA_TYPE_binary,
+        Stream = spa_sys::spa_media_type_SPA_MEDIA_TYPE_stream,
+        Application = spa_sys::spa_media_type_SPA_MEDIA_TYPE_application,
+    }
+}
+
+raw_enum! {
+    /// The media subtype of a format.
+    pub enum MediaSubtype {
+        Unknown = spa_sys::spa_media_subtype_SPA_MEDIA_SUBTYPE_unknown,
+        Raw = spa_sys::spa_media_subtype_SPA_MEDIA_SUBTYPE_raw,
+        Dsp = spa_sys::spa_media_subtype_SPA_MEDIA_SUBTYPE_dsp,
+        Iec958 = spa_sys::spa_media_subtype_SPA_MEDIA_SUBTYPE_iec958,
+        Dsd = spa_sys::spa_media_subtype_SPA_MEDIA_SUBTYPE_dsd,
+        Mp3 = spa_sys::spa_media_subtype_SPA_MEDIA_SUBTYPE_mp3,
+        Aac = spa_sys::spa_media_subtype_SPA_MEDIA_SUBTYPE_aac,
+        Vorbis = spa_sys::spa_media_subtype_SPA_MEDIA_SUBTYPE_vorbis,
+        Wma = spa_sys::spa_media_subtype_SPA_MEDIA_SUBTYPE_wma,
+        Ra = spa_sys::spa_media_subtype_SPA_MEDIA_SUBTYPE_ra,
+        Sbc = spa_sys::spa_media_subtype_SPA_MEDIA_SUBTYPE_sbc,
+        Adpcm = spa_sys::spa_media_subtype_SPA_MEDIA_SUBTYPE_adpcm,
+        G723 = spa_sys::spa_media_subtype_SPA_MEDIA_SUBTYPE_g723,
+        G726 = spa_sys::spa_media_subtype_SPA_MEDIA_SUBTYPE_g726,
+        G729 = spa_sys::spa_media_subtype_SPA_MEDIA_SUBTYPE_g729,
+        Amr = spa_sys::spa_media_subtype_SPA_MEDIA_SUBTYPE_amr,
+        Gsm = spa_sys::spa_media_subtype_SPA_MEDIA_SUBTYPE_gsm,
+        H264 = spa_sys::spa_media_subtype_SPA_MEDIA_SUBTYPE_h264,
+        Mjpg = spa_sys::spa_media_subtype_SPA_MEDIA_SUBTYPE_mjpg,
+        Dv = spa_sys::spa_media_subtype_SPA_MEDIA_SUBTYPE_dv,
+        Mpegts = spa_sys::spa_media_subtype_SPA_MEDIA_SUBTYPE_mpegts,
+        H263 = spa_sys::spa_media_subtype_SPA_MEDIA_SUBTYPE_h263,
+        Mpeg1 = spa_sys::spa_media_subtype_SPA_MEDIA_SUBTYPE_mpeg1,
+        Mpeg2 = spa_sys::spa_media_subtype_SPA_MEDIA_SUBTYPE_mpeg2,
+        Mpeg4 = spa_sys::spa_media_subtype_SPA_MEDIA_SUBTYPE_mpeg4,
+        Xvid = spa_sys::spa_media_subtype_SPA_MEDIA_SUBTYPE_xvid,
+        Vc1 = spa_sys::spa_media_subtype_SPA_MEDIA_SUBTYPE_vc1,
+        Vp8 = spa_sys::spa_media_subtype_SPA_MEDIA_SUBTYPE_vp8,
+        Vp9 = spa_sys::spa_media_subtype_SPA_MEDIA_SUBTYPE_vp9,
+        Bayer = spa_sys::spa_media_subtype_SPA_MEDIA_SUBTYPE_bayer,
+        Jpeg = spa_sys::spa_media_subtype_SPA_MEDIA_SUBTYPE_jpeg,
+        Midi = spa_sys::spa_media_subtype_SPA_MEDIA_SUBTYPE_midi,
+        Control = spa_sys::spa_media_subtype_SPA_MEDIA_SUBTYPE_control,
+    }
+}
+
+/// A property value offered during format negotiation, either fixed or as a choice.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChoiceValue<T> {
+    /// A single value.
+    Fixed(T),
+    /// Any value between `min` and `max`, preferably `default`.
+    Range { default: T, min: T, max: T },
+    /// One of `alternatives`, preferably `default`.
+    ///
+    /// Like in C, `alternatives` usually starts with `default`.
+    Enum { default: T, alternatives: Vec<T> },
+}
+
+impl<T: Copy> ChoiceValue<T> {
+    /// Add the value to `builder`, writing each item with `write`.
+    pub(crate) fn build<'d, F>(&self, builder: &mut Builder<'d>, write: F) -> io::Result<()>
+    where
+        F: Fn(&mut Builder<'d>, T) -> io::Result<()>,
+    {
+        match self {
+            ChoiceValue::Fixed(value) => write(builder, *value),
+            ChoiceValue::Range { default, min, max } => {
+                builder.push_choice(ChoiceType::Range, 0)?;
+                for value in &[*default, *min, *max] {
+                    write(builder, *value)?;
+                }
+                builder.pop()
+            }
+            ChoiceValue::Enum {
+                default,
+                alternatives,
+            } => {
+                builder.push_choice(ChoiceType::Enum, 0)?;
+                write(builder, *default)?;
+                for value in alternatives {
+                    write(builder, *value)?;
+                }
+                builder.pop()
+            }
+        }
+    }
+}
+
+impl<T> From<T> for ChoiceValue<T> {
+    fn from(value: T) -> Self {
+        ChoiceValue::Fixed(value)
+    }
+}
+
+/// A decoded format, for the media types that can be decoded by this module.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FormatInfo {
+    AudioRaw(audio::AudioInfoRaw),
+    VideoRaw(video::VideoInfoRaw),
+    /// A format whose properties cannot be decoded.
+    Other(MediaType, MediaSubtype),
+}
+
+impl FormatInfo {
+    /// Parse a `Format` object, decoding its properties if its media type is known.
+    pub fn parse(format: &Pod) -> Result<Self, FormatError> {
+        let object = format_object(format)?;
+
+        match parse_format_object(&object)? {
+            (MediaType::Audio, MediaSubtype::Raw) => {
+                audio::AudioInfoRaw::from_object(&object).map(FormatInfo::AudioRaw)
+            }
+            (MediaType::Video, MediaSubtype::Raw) => {
+                video::VideoInfoRaw::from_object(&object).map(FormatInfo::VideoRaw)
+            }
+            (media_type, media_subtype) => Ok(FormatInfo::Other(media_type, media_subtype)),
+        }
+    }
+}
+
+/// Errors that can happen when parsing a format param.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FormatError {
+    /// The POD is malformed.
+    Parse(ParseError),
+    /// The POD is not a `Format` object.
+    NotAFormat,
+    /// A mandatory property is missing.
+    MissingProperty(u32),
+    /// A property does not have the expected type, has an unknown value or is not fixed.
+    InvalidProperty(u32),
+}
+
+impl fmt::Display for FormatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FormatError::Parse(e) => write!(f, "failed to parse format: {}", e),
+            FormatError::NotAFormat => write!(f, "POD is not a format object"),
+            FormatError::MissingProperty(key) => write!(f, "missing property {}", key),
+            FormatError::InvalidProperty(key) => write!(f, "invalid property {}", key),
+        }
+    }
+}
+
+impl std::error::Error for FormatError {}
+
+impl From<ParseError> for FormatError {
+    fn from(e: ParseError) -> Self {
+        FormatError::Parse(e)
+    }
+}
+
+/// Parse `format` as a `Format` object.
+pub(crate) fn format_object<'a>(format: &Pod<'a>) -> Result<Object<'a>, FormatError> {
+    match format.value()? {
+        Value::Object(object) if object.type_ == spa_sys::SPA_TYPE_OBJECT_Format => Ok(object),
+        _ => Err(FormatError::NotAFormat),
+    }
+}
+
+/// Get the media type and subtype of a format, equivalent to `spa_format_parse`.
+pub fn parse_format(format: &Pod) -> Result<(MediaType, MediaSubtype), FormatError> {
+    let object = format_object(format)?;
+    parse_format_object(&object)
+}
+
+/// Get the media type and subtype of an already parsed format object.
+pub fn parse_format_object(format: &Object) -> Result<(MediaType, MediaSubtype), FormatError> {
+    if format.type_ != spa_sys::SPA_TYPE_OBJECT_Format {
+        return Err(FormatError::NotAFormat);
+    }
+
+    let media_type = require(format, spa_sys::spa_format_SPA_FORMAT_mediaType, |v| {
+        id(v).and_then(MediaType::from_raw)
+    })?;
+    let media_subtype = require(format, spa_sys::spa_format_SPA_FORMAT_mediaSubtype, |v| {
+        id(v).and_then(MediaSubtype::from_raw)
+    })?;
+
+    Ok((media_type, media_subtype))
+}
+
+/// Get the fixed value of the `key` property of `object`, if it has one.
+///
+/// Like the C parser, a `None` choice is accepted in place of its default value.
+pub(crate) fn fixed_property<'o, 'a>(
+    object: &'o Object<'a>,
+    key: u32,
+) -> Result<Option<&'o Value<'a>>, FormatError> {
+    match object.property(key).map(|p| &p.value) {
+        Some(Value::Choice(choice)) if choice.type_ == ChoiceType::None => choice
+            .default()
+            .map(Some)
+            .ok_or(FormatError::InvalidProperty(key)),
+        Some(Value::Choice(_)) => Err(FormatError::InvalidProperty(key)),
+        value => Ok(value),
+    }
+}
+
+/// Convert the `key` property of `object` with `convert`, if the object has it.
+pub(crate) fn optional<T, F>(
+    object: &Object,
+    key: u32,
+    convert: F,
+) -> Result<Option<T>, FormatError>
+where
+    F: FnOnce(&Value) -> Option<T>,
+{
+    match fixed_property(object, key)? {
+        Some(value) => convert(value)
+            .map(Some)
+            .ok_or(FormatError::InvalidProperty(key)),
+        None => Ok(None),
+    }
+}
+
+/// Convert the `key` property of `object` with `convert`, failing if the object does not have it.
+pub(crate) fn require<T, F>(object: &Object, key: u32, convert: F) -> Result<T, FormatError>
+where
+    F: FnOnce(&Value) -> Option<T>,
+{
+    optional(object, key, convert)?.ok_or(FormatError::MissingProperty(key))
+}
+
+pub(crate) fn id(value: &Value) -> Option<u32> {
+    match value {
+        Value::Id(id) => Some(*id),
+        _ => None,
+    }
+}
+
+pub(crate) fn int(value: &Value) -> Option<i32> {
+    match value {
+        Value::Int(int) => Some(*int),
+        _ => None,
+    }
+}
+
+pub(crate) fn long(value: &Value) -> Option<i64> {
+    match value {
+        Value::Long(long) => Some(*long),
+        _ => None,
+    }
+}
+
+pub(crate) fn rectangle(value: &Value) -> Option<Rectangle> {
+    match value {
+        Value::Rectangle(rectangle) => Some(*rectangle),
+        _ => None,
+    }
+}
+
+pub(crate) fn fraction(value: &Value) -> Option<Fraction> {
+    match value {
+        Value::Fraction(fraction) => Some(*fraction),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_info() {
+        let info = audio::AudioInfoRaw {
+            format: audio::AudioFormat::F32P,
+            rate: 48000,
+            channels: 1,
+            position: vec![audio::AudioChannel::Mono],
+            ..audio::AudioInfoRaw::new()
+        };
+        let mut data = Vec::new();
+        info.build(&mut Builder::with_vec(&mut data), ParamType::Format)
+            .unwrap();
+        let pod = Pod::from_bytes(&data).unwrap();
+        assert_eq!(FormatInfo::parse(&pod), Ok(FormatInfo::AudioRaw(info)));
+
+        let mut data = Vec::new();
+        let mut builder = Builder::with_vec(&mut data);
+        builder
+            .push_object(spa_sys::SPA_TYPE_OBJECT_Format, ParamType::Format.as_raw())
+            .unwrap();
+        builder
+            .prop(spa_sys::spa_format_SPA_FORMAT_mediaType, 0)
+            .unwrap();
+        builder.id(MediaType::Application.as_raw()).unwrap();
+        builder
+            .prop(spa_sys::spa_format_SPA_FORMAT_mediaSubtype, 0)
+            .unwrap();
+        builder.id(MediaSubtype::Control.as_raw()).unwrap();
+        builder.pop().unwrap();
+        let pod = Pod::from_bytes(&data).unwrap();
+        assert_eq!(
+            FormatInfo::parse(&pod),
+            Ok(FormatInfo::Other(
+                MediaType::Application,
+                MediaSubtype::Control
+            ))
+        );
+    }
+}
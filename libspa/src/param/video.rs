@@ -0,0 +1,650 @@
+// Copyright 2021, Collabora Ltd.
+// SPDX-License-Identifier: MIT
+
+//! Raw video formats.
+
+use bitflags::bitflags;
+use std::io;
+
+use super::{
+    fraction, id, int, long, optional, rectangle, require, ChoiceValue, FormatError, MediaSubtype,
+    MediaType, ParamType,
+};
+use crate::pod::{Builder, Fraction, Object, Pod, PropertyFlags, Rectangle};
+
+raw_enum! {
+    /// A raw video pixel format.
+    ///
+    /// Formats with a lowercase `x` in C, such as `xRGB`, are spelled with an uppercase `X`.
+    pub enum VideoFormat {
+        Unknown = spa_sys::spa_video_format_SPA_VIDEO_FORMAT_UNKNOWN,
+        Encoded = spa_sys::spa_video_format_SPA_VIDEO_FORMAT_ENCODED,
+        I420 = spa_sys::spa_video_format_SPA_VIDEO_FORMAT_I420,
+        YV12 = spa_sys::spa_video_format_SPA_VIDEO_FORMAT_YV12,
+        YUY2 = spa_sys::spa_video_format_SPA_VIDEO_FORMAT_YUY2,
+        UYVY = spa_sys::spa_video_format_SPA_VIDEO_FORMAT_UYVY,
+        AYUV = spa_sys::spa_video_format_SPA_VIDEO_FORMAT_AYUV,
+        RGBx = spa_sys::spa_video_format_SPA_VIDEO_FORMAT_RGBx,
+        BGRx = spa_sys::spa_video_format_SPA_VIDEO_FORMAT_BGRx,
+        XRGB = spa_sys::spa_video_format_SPA_VIDEO_FORMAT_xRGB,
+        XBGR = spa_sys::spa_video_format_SPA_VIDEO_FORMAT_xBGR,
+        RGBA = spa_sys::spa_video_format_SPA_VIDEO_FORMAT_RGBA,
+        BGRA = spa_sys::spa_video_format_SPA_VIDEO_FORMAT_BGRA,
+        ARGB = spa_sys::spa_video_format_SPA_VIDEO_FORMAT_ARGB,
+        ABGR = spa_sys::spa_video_format_SPA_VIDEO_FORMAT_ABGR,
+        RGB = spa_sys::spa_video_format_SPA_VIDEO_FORMAT_RGB,
+        BGR = spa_sys::spa_video_format_SPA_VIDEO_FORMAT_BGR,
+        Y41B = spa_sys::spa_video_format_SPA_VIDEO_FORMAT_Y41B,
+        Y42B = spa_sys::spa_video_format_SPA_VIDEO_FORMAT_Y42B,
+        YVYU = spa_sys::spa_video_format_SPA_VIDEO_FORMAT_YVYU,
+        Y444 = spa_sys::spa_video_format_SPA_VIDEO_FORMAT_Y444,
+        V210 = spa_sys::spa_video_format_SPA_VIDEO_FORMAT_v210,
+        V216 = spa_sys::spa_video_format_SPA_VIDEO_FORMAT_v216,
+        NV12 = spa_sys::spa_video_format_SPA_VIDEO_FORMAT_NV12,
+        NV21 = spa_sys::spa_video_format_SPA_VIDEO_FORMAT_NV21,
+        Gray8 = spa_sys::spa_video_format_SPA_VIDEO_FORMAT_GRAY8,
+        Gray16BE = spa_sys::spa_video_format_SPA_VIDEO_FORMAT_GRAY16_BE,
+        Gray16LE = spa_sys::spa_video_format_SPA_VIDEO_FORMAT_GRAY16_LE,
+        V308 = spa_sys::spa_video_format_SPA_VIDEO_FORMAT_v308,
+        RGB16 = spa_sys::spa_video_format_SPA_VIDEO_FORMAT_RGB16,
+        BGR16 = spa_sys::spa_video_format_SPA_VIDEO_FORMAT_BGR16,
+        RGB15 = spa_sys::spa_video_format_SPA_VIDEO_FORMAT_RGB15,
+        BGR15 = spa_sys::spa_video_format_SPA_VIDEO_FORMAT_BGR15,
+        UYVP = spa_sys::spa_video_format_SPA_VIDEO_FORMAT_UYVP,
+        A420 = spa_sys::spa_video_format_SPA_VIDEO_FORMAT_A420,
+        RGB8P = spa_sys::spa_video_format_SPA_VIDEO_FORMAT_RGB8P,
+        YUV9 = spa_sys::spa_video_format_SPA_VIDEO_FORMAT_YUV9,
+        YVU9 = spa_sys::spa_video_format_SPA_VIDEO_FORMAT_YVU9,
+        IYU1 = spa_sys::spa_video_format_SPA_VIDEO_FORMAT_IYU1,
+        ARGB64 = spa_sys::spa_video_format_SPA_VIDEO_FORMAT_ARGB64,
+        AYUV64 = spa_sys::spa_video_format_SPA_VIDEO_FORMAT_AYUV64,
+        R210 = spa_sys::spa_video_format_SPA_VIDEO_FORMAT_r210,
+        I420_10BE = spa_sys::spa_video_format_SPA_VIDEO_FORMAT_I420_10BE,
+        I420_10LE = spa_sys::spa_video_format_SPA_VIDEO_FORMAT_I420_10LE,
+        I422_10BE = spa_sys::spa_video_format_SPA_VIDEO_FORMAT_I422_10BE,
+        I422_10LE = spa_sys::spa_video_format_SPA_VIDEO_FORMAT_I422_10LE,
+        Y444_10BE = spa_sys::spa_video_format_SPA_VIDEO_FORMAT_Y444_10BE,
+        Y444_10LE = spa_sys::spa_video_format_SPA_VIDEO_FORMAT_Y444_10LE,
+        GBR = spa_sys::spa_video_format_SPA_VIDEO_FORMAT_GBR,
+        GBR10BE = spa_sys::spa_video_format_SPA_VIDEO_FORMAT_GBR_10BE,
+        GBR10LE = spa_sys::spa_video_format_SPA_VIDEO_FORMAT_GBR_10LE,
+        NV16 = spa_sys::spa_video_format_SPA_VIDEO_FORMAT_NV16,
+        NV24 = spa_sys::spa_video_format_SPA_VIDEO_FORMAT_NV24,
+        NV12_64Z32 = spa_sys::spa_video_format_SPA_VIDEO_FORMAT_NV12_64Z32,
+        A420_10BE = spa_sys::spa_video_format_SPA_VIDEO_FORMAT_A420_10BE,
+        A420_10LE = spa_sys::spa_video_format_SPA_VIDEO_FORMAT_A420_10LE,
+        A422_10BE = spa_sys::spa_video_format_SPA_VIDEO_FORMAT_A422_10BE,
+        A422_10LE = spa_sys::spa_video_format_SPA_VIDEO_FORMAT_A422_10LE,
+        A444_10BE = spa_sys::spa_video_format_SPA_VIDEO_FORMAT_A444_10BE,
+        A444_10LE = spa_sys::spa_video_format_SPA_VIDEO_FORMAT_A444_10LE,
+        NV61 = spa_sys::spa_video_format_SPA_VIDEO_FORMAT_NV61,
+        P010_10BE = spa_sys::spa_video_format_SPA_VIDEO_FORMAT_P010_10BE,
+        P010_10LE = spa_sys::spa_video_format_SPA_VIDEO_FORMAT_P010_10LE,
+        IYU2 = spa_sys::spa_video_format_SPA_VIDEO_FORMAT_IYU2,
+        VYUY = spa_sys::spa_video_format_SPA_VIDEO_FORMAT_VYUY,
+        GBRA = spa_sys::spa_video_format_SPA_VIDEO_FORMAT_GBRA,
+        GBRA10BE = spa_sys::spa_video_format_SPA_VIDEO_FORMAT_GBRA_10BE,
+        GBRA10LE = spa_sys::spa_video_format_SPA_VIDEO_FORMAT_GBRA_10LE,
+        GBR12BE = spa_sys::spa_video_format_SPA_VIDEO_FORMAT_GBR_12BE,
+        GBR12LE = spa_sys::spa_video_format_SPA_VIDEO_FORMAT_GBR_12LE,
+        GBRA12BE = spa_sys::spa_video_format_SPA_VIDEO_FORMAT_GBRA_12BE,
+        GBRA12LE = spa_sys::spa_video_format_SPA_VIDEO_FORMAT_GBRA_12LE,
+        I420_12BE = spa_sys::spa_video_format_SPA_VIDEO_FORMAT_I420_12BE,
+        I420_12LE = spa_sys::spa_video_format_SPA_VIDEO_FORMAT_I420_12LE,
+        I422_12BE = spa_sys::spa_video_format_SPA_VIDEO_FORMAT_I422_12BE,
+        I422_12LE = spa_sys::spa_video_format_SPA_VIDEO_FORMAT_I422_12LE,
+        Y444_12BE = spa_sys::spa_video_format_SPA_VIDEO_FORMAT_Y444_12BE,
+        Y444_12LE = spa_sys::spa_video_format_SPA_VIDEO_FORMAT_Y444_12LE,
+        RGBAF16 = spa_sys::spa_video_format_SPA_VIDEO_FORMAT_RGBA_F16,
+        RGBAF32 = spa_sys::spa_video_format_SPA_VIDEO_FORMAT_RGBA_F32,
+    }
+}
+
+raw_enum! {
+    /// How the frames of a video are interlaced.
+    pub enum VideoInterlaceMode {
+        /// All frames are progressive.
+        Progressive = spa_sys::spa_video_interlace_mode_SPA_VIDEO_INTERLACE_MODE_PROGRESSIVE,
+        /// Both fields are interleaved in one frame.
+        Interleaved = spa_sys::spa_video_interlace_mode_SPA_VIDEO_INTERLACE_MODE_INTERLEAVED,
+        /// Frames contain both progressive and interleaved content.
+        Mixed = spa_sys::spa_video_interlace_mode_SPA_VIDEO_INTERLACE_MODE_MIXED,
+        /// Each field is stored in its own plane.
+        Fields = spa_sys::spa_video_interlace_mode_SPA_VIDEO_INTERLACE_MODE_FIELDS,
+    }
+}
+
+raw_enum! {
+    /// The range of the colour values.
+    pub enum VideoColorRange {
+        Unknown = spa_sys::spa_video_color_range_SPA_VIDEO_COLOR_RANGE_UNKNOWN,
+        /// Values use the full 0 to 255 range.
+        Full = spa_sys::spa_video_color_range_SPA_VIDEO_COLOR_RANGE_0_255,
+        /// Values use the 16 to 235 range.
+        Limited = spa_sys::spa_video_color_range_SPA_VIDEO_COLOR_RANGE_16_235,
+    }
+}
+
+raw_enum! {
+    /// The matrix used to convert between YUV and RGB.
+    pub enum VideoColorMatrix {
+        Unknown = spa_sys::spa_video_color_matrix_SPA_VIDEO_COLOR_MATRIX_UNKNOWN,
+        RGB = spa_sys::spa_video_color_matrix_SPA_VIDEO_COLOR_MATRIX_RGB,
+        FCC = spa_sys::spa_video_color_matrix_SPA_VIDEO_COLOR_MATRIX_FCC,
+        BT709 = spa_sys::spa_video_color_matrix_SPA_VIDEO_COLOR_MATRIX_BT709,
+        BT601 = spa_sys::spa_video_color_matrix_SPA_VIDEO_COLOR_MATRIX_BT601,
+        SMPTE240M = spa_sys::spa_video_color_matrix_SPA_VIDEO_COLOR_MATRIX_SMPTE240M,
+        BT2020 = spa_sys::spa_video_color_matrix_SPA_VIDEO_COLOR_MATRIX_BT2020,
+    }
+}
+
+raw_enum! {
+    /// The transfer function used to encode the colour values.
+    pub enum VideoTransferFunction {
+        Unknown = spa_sys::spa_video_transfer_function_SPA_VIDEO_TRANSFER_UNKNOWN,
+        Gamma10 = spa_sys::spa_video_transfer_function_SPA_VIDEO_TRANSFER_GAMMA10,
+        Gamma18 = spa_sys::spa_video_transfer_function_SPA_VIDEO_TRANSFER_GAMMA18,
+        Gamma20 = spa_sys::spa_video_transfer_function_SPA_VIDEO_TRANSFER_GAMMA20,
+        Gamma22 = spa_sys::spa_video_transfer_function_SPA_VIDEO_TRANSFER_GAMMA22,
+        BT709 = spa_sys::spa_video_transfer_function_SPA_VIDEO_TRANSFER_BT709,
+        SMPTE240M = spa_sys::spa_video_transfer_function_SPA_VIDEO_TRANSFER_SMPTE240M,
+        SRGB = spa_sys::spa_video_transfer_function_SPA_VIDEO_TRANSFER_SRGB,
+        Gamma28 = spa_sys::spa_video_transfer_function_SPA_VIDEO_TRANSFER_GAMMA28,
+        Log100 = spa_sys::spa_video_transfer_function_SPA_VIDEO_TRANSFER_LOG100,
+        Log316 = spa_sys::spa_video_transfer_function_SPA_VIDEO_TRANSFER_LOG316,
+        BT2020_12 = spa_sys::spa_video_transfer_function_SPA_VIDEO_TRANSFER_BT2020_12,
+        AdobeRGB = spa_sys::spa_video_transfer_function_SPA_VIDEO_TRANSFER_ADOBERGB,
+    }
+}
+
+raw_enum! {
+    /// The colour primaries.
+    pub enum VideoColorPrimaries {
+        Unknown = spa_sys::spa_video_color_primaries_SPA_VIDEO_COLOR_PRIMARIES_UNKNOWN,
+        BT709 = spa_sys::spa_video_color_primaries_SPA_VIDEO_COLOR_PRIMARIES_BT709,
+        BT470M = spa_sys::spa_video_color_primaries_SPA_VIDEO_COLOR_PRIMARIES_BT470M,
+        BT470BG = spa_sys::spa_video_color_primaries_SPA_VIDEO_COLOR_PRIMARIES_BT470BG,
+        SMPTE170M = spa_sys::spa_video_color_primaries_SPA_VIDEO_COLOR_PRIMARIES_SMPTE170M,
+        SMPTE240M = spa_sys::spa_video_color_primaries_SPA_VIDEO_COLOR_PRIMARIES_SMPTE240M,
+        Film = spa_sys::spa_video_color_primaries_SPA_VIDEO_COLOR_PRIMARIES_FILM,
+        BT2020 = spa_sys::spa_video_color_primaries_SPA_VIDEO_COLOR_PRIMARIES_BT2020,
+        AdobeRGB = spa_sys::spa_video_color_primaries_SPA_VIDEO_COLOR_PRIMARIES_ADOBERGB,
+    }
+}
+
+bitflags! {
+    /// Where the chroma samples are located relative to the luma samples.
+    pub struct VideoChromaSite: u32 {
+        /// No cositing.
+        const NONE = spa_sys::spa_video_chroma_site_SPA_VIDEO_CHROMA_SITE_NONE;
+        /// Chroma is horizontally cosited.
+        const H_COSITED = spa_sys::spa_video_chroma_site_SPA_VIDEO_CHROMA_SITE_H_COSITED;
+        /// Chroma is vertically cosited.
+        const V_COSITED = spa_sys::spa_video_chroma_site_SPA_VIDEO_CHROMA_SITE_V_COSITED;
+        /// Chroma samples are sited on alternate lines.
+        const ALT_LINE = spa_sys::spa_video_chroma_site_SPA_VIDEO_CHROMA_SITE_ALT_LINE;
+    }
+}
+
+/// A raw video format, equivalent to `spa_video_info_raw`.
+///
+/// Use [`build`](Self::build) to turn it into a `Format` param, and [`parse`](Self::parse)
+/// to read the format a port or link negotiated.
+/// To offer a choice of formats, sizes or framerates, use [`VideoEnumFormat`] instead.
+///
+/// Fields left to their default value are omitted from the built format.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VideoInfoRaw {
+    pub format: VideoFormat,
+    /// The DRM modifier of the buffers, if they are DMA-BUFs.
+    pub modifier: Option<u64>,
+    pub size: Rectangle,
+    pub framerate: Fraction,
+    /// The maximum framerate, when `framerate` is 0 to signal a variable framerate.
+    pub max_framerate: Fraction,
+    /// The number of views, for multiview video.
+    pub views: u32,
+    pub interlace_mode: VideoInterlaceMode,
+    pub pixel_aspect_ratio: Fraction,
+    /// The raw `spa_video_multiview_mode`.
+    pub multiview_mode: u32,
+    /// The raw `spa_video_multiview_flags`.
+    pub multiview_flags: u32,
+    pub chroma_site: VideoChromaSite,
+    pub color_range: VideoColorRange,
+    pub color_matrix: VideoColorMatrix,
+    pub transfer_function: VideoTransferFunction,
+    pub color_primaries: VideoColorPrimaries,
+}
+
+impl Default for VideoInfoRaw {
+    fn default() -> Self {
+        let zero = Fraction { num: 0, denom: 0 };
+        VideoInfoRaw {
+            format: VideoFormat::Unknown,
+            modifier: None,
+            size: Rectangle {
+                width: 0,
+                height: 0,
+            },
+            framerate: zero,
+            max_framerate: zero,
+            views: 0,
+            interlace_mode: VideoInterlaceMode::Progressive,
+            pixel_aspect_ratio: zero,
+            multiview_mode: 0,
+            multiview_flags: 0,
+            chroma_site: VideoChromaSite::empty(),
+            color_range: VideoColorRange::Unknown,
+            color_matrix: VideoColorMatrix::Unknown,
+            transfer_function: VideoTransferFunction::Unknown,
+            color_primaries: VideoColorPrimaries::Unknown,
+        }
+    }
+}
+
+impl VideoInfoRaw {
+    /// Create an empty format, with every field left to be negotiated.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add this format to `builder` as a `Format` object with the given `id`,
+    /// equivalent to `spa_format_video_raw_build`.
+    pub fn build(&self, builder: &mut Builder, id: ParamType) -> io::Result<()> {
+        push_video_format(builder, id)?;
+
+        if self.format != VideoFormat::Unknown {
+            builder.prop(spa_sys::spa_format_SPA_FORMAT_VIDEO_format, 0)?;
+            builder.id(self.format.as_raw())?;
+        }
+        if self.size.width != 0 && self.size.height != 0 {
+            builder.prop(spa_sys::spa_format_SPA_FORMAT_VIDEO_size, 0)?;
+            builder.rectangle(self.size)?;
+        }
+        if self.framerate.denom != 0 {
+            builder.prop(spa_sys::spa_format_SPA_FORMAT_VIDEO_framerate, 0)?;
+            builder.fraction(self.framerate)?;
+        }
+        if let Some(modifier) = self.modifier {
+            builder.prop(spa_sys::spa_format_SPA_FORMAT_VIDEO_modifier, 0)?;
+            builder.long(modifier as i64)?;
+        }
+        if self.max_framerate.denom != 0 {
+            builder.prop(spa_sys::spa_format_SPA_FORMAT_VIDEO_maxFramerate, 0)?;
+            builder.fraction(self.max_framerate)?;
+        }
+        if self.views != 0 {
+            builder.prop(spa_sys::spa_format_SPA_FORMAT_VIDEO_views, 0)?;
+            builder.int(self.views as i32)?;
+        }
+        if self.interlace_mode != VideoInterlaceMode::Progressive {
+            builder.prop(spa_sys::spa_format_SPA_FORMAT_VIDEO_interlaceMode, 0)?;
+            builder.id(self.interlace_mode.as_raw())?;
+        }
+        if self.pixel_aspect_ratio.denom != 0 {
+            builder.prop(spa_sys::spa_format_SPA_FORMAT_VIDEO_pixelAspectRatio, 0)?;
+            builder.fraction(self.pixel_aspect_ratio)?;
+        }
+        if self.multiview_mode != 0 {
+            builder.prop(spa_sys::spa_format_SPA_FORMAT_VIDEO_multiviewMode, 0)?;
+            builder.id(self.multiview_mode)?;
+        }
+        if self.multiview_flags != 0 {
+            builder.prop(spa_sys::spa_format_SPA_FORMAT_VIDEO_multiviewFlags, 0)?;
+            builder.id(self.multiview_flags)?;
+        }
+        if !self.chroma_site.is_empty() {
+            builder.prop(spa_sys::spa_format_SPA_FORMAT_VIDEO_chromaSite, 0)?;
+            builder.id(self.chroma_site.bits())?;
+        }
+        if self.color_range != VideoColorRange::Unknown {
+            builder.prop(spa_sys::spa_format_SPA_FORMAT_VIDEO_colorRange, 0)?;
+            builder.id(self.color_range.as_raw())?;
+        }
+        if self.color_matrix != VideoColorMatrix::Unknown {
+            builder.prop(spa_sys::spa_format_SPA_FORMAT_VIDEO_colorMatrix, 0)?;
+            builder.id(self.color_matrix.as_raw())?;
+        }
+        if self.transfer_function != VideoTransferFunction::Unknown {
+            builder.prop(spa_sys::spa_format_SPA_FORMAT_VIDEO_transferFunction, 0)?;
+            builder.id(self.transfer_function.as_raw())?;
+        }
+        if self.color_primaries != VideoColorPrimaries::Unknown {
+            builder.prop(spa_sys::spa_format_SPA_FORMAT_VIDEO_colorPrimaries, 0)?;
+            builder.id(self.color_primaries.as_raw())?;
+        }
+
+        builder.pop()
+    }
+
+    /// Parse a raw video format, equivalent to `spa_format_video_raw_parse`.
+    ///
+    /// The format, size and framerate must be fixed, the other properties are optional.
+    pub fn parse(format: &Pod) -> Result<Self, FormatError> {
+        Self::from_object(&super::format_object(format)?)
+    }
+
+    /// Parse a raw video format from an already parsed `Format` object.
+    pub fn from_object(format: &Object) -> Result<Self, FormatError> {
+        if format.type_ != spa_sys::SPA_TYPE_OBJECT_Format {
+            return Err(FormatError::NotAFormat);
+        }
+
+        let mut info = VideoInfoRaw::new();
+        info.format = require(format, spa_sys::spa_format_SPA_FORMAT_VIDEO_format, |v| {
+            id(v).and_then(VideoFormat::from_raw)
+        })?;
+        info.modifier = optional(format, spa_sys::spa_format_SPA_FORMAT_VIDEO_modifier, long)?
+            .map(|modifier| modifier as u64);
+        info.size = require(format, spa_sys::spa_format_SPA_FORMAT_VIDEO_size, rectangle)?;
+        info.framerate = require(
+            format,
+            spa_sys::spa_format_SPA_FORMAT_VIDEO_framerate,
+            fraction,
+        )?;
+
+        if let Some(max_framerate) = optional(
+            format,
+            spa_sys::spa_format_SPA_FORMAT_VIDEO_maxFramerate,
+            fraction,
+        )? {
+            info.max_framerate = max_framerate;
+        }
+        if let Some(views) = optional(format, spa_sys::spa_format_SPA_FORMAT_VIDEO_views, int)? {
+            info.views = views as u32;
+        }
+        if let Some(mode) = optional(
+            format,
+            spa_sys::spa_format_SPA_FORMAT_VIDEO_interlaceMode,
+            |v| id(v).and_then(VideoInterlaceMode::from_raw),
+        )? {
+            info.interlace_mode = mode;
+        }
+        if let Some(ratio) = optional(
+            format,
+            spa_sys::spa_format_SPA_FORMAT_VIDEO_pixelAspectRatio,
+            fraction,
+        )? {
+            info.pixel_aspect_ratio = ratio;
+        }
+        if let Some(mode) = optional(
+            format,
+            spa_sys::spa_format_SPA_FORMAT_VIDEO_multiviewMode,
+            id,
+        )? {
+            info.multiview_mode = mode;
+        }
+        if let Some(flags) = optional(
+            format,
+            spa_sys::spa_format_SPA_FORMAT_VIDEO_multiviewFlags,
+            id,
+        )? {
+            info.multiview_flags = flags;
+        }
+        if let Some(site) = optional(
+            format,
+            spa_sys::spa_format_SPA_FORMAT_VIDEO_chromaSite,
+            |v| id(v).map(VideoChromaSite::from_bits_truncate),
+        )? {
+            info.chroma_site = site;
+        }
+        if let Some(range) = optional(
+            format,
+            spa_sys::spa_format_SPA_FORMAT_VIDEO_colorRange,
+            |v| id(v).and_then(VideoColorRange::from_raw),
+        )? {
+            info.color_range = range;
+        }
+        if let Some(matrix) = optional(
+            format,
+            spa_sys::spa_format_SPA_FORMAT_VIDEO_colorMatrix,
+            |v| id(v).and_then(VideoColorMatrix::from_raw),
+        )? {
+            info.color_matrix = matrix;
+        }
+        if let Some(function) = optional(
+            format,
+            spa_sys::spa_format_SPA_FORMAT_VIDEO_transferFunction,
+            |v| id(v).and_then(VideoTransferFunction::from_raw),
+        )? {
+            info.transfer_function = function;
+        }
+        if let Some(primaries) = optional(
+            format,
+            spa_sys::spa_format_SPA_FORMAT_VIDEO_colorPrimaries,
+            |v| id(v).and_then(VideoColorPrimaries::from_raw),
+        )? {
+            info.color_primaries = primaries;
+        }
+
+        Ok(info)
+    }
+}
+
+/// The raw video formats a port can handle, to be built as an `EnumFormat` param.
+///
+/// Unlike [`VideoInfoRaw`], each property can offer a range or a list of values for the other
+/// side of the negotiation to pick from. Properties set to `None` are omitted.
+///
+/// # Examples
+/// ```
+/// use libspa::param::{video::{VideoEnumFormat, VideoFormat}, ChoiceValue, ParamType};
+/// use libspa::pod::{Builder, Fraction, Rectangle};
+///
+/// let mut formats = VideoEnumFormat::new(ChoiceValue::Enum {
+///     default: VideoFormat::BGRx,
+///     alternatives: vec![VideoFormat::BGRx, VideoFormat::RGBx, VideoFormat::NV12],
+/// });
+/// formats.size = Some(ChoiceValue::Range {
+///     default: Rectangle { width: 1920, height: 1080 },
+///     min: Rectangle { width: 1, height: 1 },
+///     max: Rectangle { width: 4096, height: 4096 },
+/// });
+/// formats.framerate = Some(Fraction { num: 0, denom: 1 }.into());
+///
+/// let mut data = Vec::new();
+/// formats.build(&mut Builder::with_vec(&mut data), ParamType::EnumFormat).unwrap();
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct VideoEnumFormat {
+    pub format: ChoiceValue<VideoFormat>,
+    /// The DRM modifiers supported for DMA-BUFs.
+    ///
+    /// When more than one is offered, the property is marked as mandatory and not to be fixated,
+    /// so the peer has to pick a modifier it really supports.
+    pub modifier: Option<ChoiceValue<u64>>,
+    pub size: Option<ChoiceValue<Rectangle>>,
+    pub framerate: Option<ChoiceValue<Fraction>>,
+    pub max_framerate: Option<ChoiceValue<Fraction>>,
+}
+
+impl VideoEnumFormat {
+    /// Offer `format`, leaving every other property to be negotiated.
+    pub fn new(format: impl Into<ChoiceValue<VideoFormat>>) -> Self {
+        VideoEnumFormat {
+            format: format.into(),
+            modifier: None,
+            size: None,
+            framerate: None,
+            max_framerate: None,
+        }
+    }
+
+    /// Add the formats to `builder` as a `Format` object with the given `id`.
+    pub fn build(&self, builder: &mut Builder, id: ParamType) -> io::Result<()> {
+        push_video_format(builder, id)?;
+
+        builder.prop(spa_sys::spa_format_SPA_FORMAT_VIDEO_format, 0)?;
+        self.format.build(builder, |b, f| b.id(f.as_raw()))?;
+
+        if let Some(modifier) = &self.modifier {
+            let flags = match modifier {
+                ChoiceValue::Fixed(_) => PropertyFlags::empty(),
+                _ => PropertyFlags::MANDATORY | PropertyFlags::DONT_FIXATE,
+            };
+            builder.prop(spa_sys::spa_format_SPA_FORMAT_VIDEO_modifier, flags.bits())?;
+            modifier.build(builder, |b, m| b.long(m as i64))?;
+        }
+        if let Some(size) = &self.size {
+            builder.prop(spa_sys::spa_format_SPA_FORMAT_VIDEO_size, 0)?;
+            size.build(builder, Builder::rectangle)?;
+        }
+        if let Some(framerate) = &self.framerate {
+            builder.prop(spa_sys::spa_format_SPA_FORMAT_VIDEO_framerate, 0)?;
+            framerate.build(builder, Builder::fraction)?;
+        }
+        if let Some(max_framerate) = &self.max_framerate {
+            builder.prop(spa_sys::spa_format_SPA_FORMAT_VIDEO_maxFramerate, 0)?;
+            max_framerate.build(builder, Builder::fraction)?;
+        }
+
+        builder.pop()
+    }
+}
+
+/// Start a video `Format` object, with the media type and subtype properties.
+fn push_video_format(builder: &mut Builder, id: ParamType) -> io::Result<()> {
+    builder.push_object(spa_sys::SPA_TYPE_OBJECT_Format, id.as_raw())?;
+    builder.prop(spa_sys::spa_format_SPA_FORMAT_mediaType, 0)?;
+    builder.id(MediaType::Video.as_raw())?;
+    builder.prop(spa_sys::spa_format_SPA_FORMAT_mediaSubtype, 0)?;
+    builder.id(MediaSubtype::Raw.as_raw())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pod::{ChoiceType, Value};
+
+    fn build<F>(f: F) -> Vec<u8>
+    where
+        F: FnOnce(&mut Builder) -> io::Result<()>,
+    {
+        let mut data = Vec::new();
+        f(&mut Builder::with_vec(&mut data)).unwrap();
+        data
+    }
+
+    #[test]
+    fn roundtrip() {
+        let info = VideoInfoRaw {
+            format: VideoFormat::NV12,
+            modifier: Some(0),
+            size: Rectangle {
+                width: 1280,
+                height: 720,
+            },
+            framerate: Fraction { num: 30, denom: 1 },
+            color_range: VideoColorRange::Limited,
+            color_matrix: VideoColorMatrix::BT709,
+            chroma_site: VideoChromaSite::H_COSITED,
+            ..VideoInfoRaw::new()
+        };
+        let data = build(|b| info.build(b, ParamType::Format));
+        let pod = Pod::from_bytes(&data).unwrap();
+
+        assert_eq!(VideoInfoRaw::parse(&pod).unwrap(), info);
+        assert_eq!(
+            super::super::parse_format(&pod).unwrap(),
+            (MediaType::Video, MediaSubtype::Raw)
+        );
+    }
+
+    #[test]
+    fn missing_size() {
+        let info = VideoInfoRaw {
+            format: VideoFormat::RGBA,
+            framerate: Fraction { num: 60, denom: 1 },
+            ..VideoInfoRaw::new()
+        };
+        let data = build(|b| info.build(b, ParamType::Format));
+
+        assert_eq!(
+            VideoInfoRaw::parse(&Pod::from_bytes(&data).unwrap()),
+            Err(FormatError::MissingProperty(
+                spa_sys::spa_format_SPA_FORMAT_VIDEO_size
+            ))
+        );
+    }
+
+    #[test]
+    fn enum_format() {
+        let mut formats = VideoEnumFormat::new(ChoiceValue::Enum {
+            default: VideoFormat::BGRx,
+            alternatives: vec![VideoFormat::BGRx, VideoFormat::YUY2],
+        });
+        formats.modifier = Some(ChoiceValue::Enum {
+            default: 0,
+            alternatives: vec![0, 0x0100_0000_0000_0001],
+        });
+        formats.size = Some(ChoiceValue::Range {
+            default: Rectangle {
+                width: 640,
+                height: 480,
+            },
+            min: Rectangle {
+                width: 1,
+                height: 1,
+            },
+            max: Rectangle {
+                width: 8192,
+                height: 8192,
+            },
+        });
+        formats.framerate = Some(Fraction { num: 25, denom: 1 }.into());
+        let data = build(|b| formats.build(b, ParamType::EnumFormat));
+        let pod = Pod::from_bytes(&data).unwrap();
+
+        let object = match pod.value().unwrap() {
+            Value::Object(object) => object,
+            _ => panic!("not an object"),
+        };
+        assert_eq!(object.id, ParamType::EnumFormat.as_raw());
+
+        let format = object
+            .property(spa_sys::spa_format_SPA_FORMAT_VIDEO_format)
+            .unwrap();
+        match &format.value {
+            Value::Choice(choice) => {
+                assert_eq!(choice.type_, ChoiceType::Enum);
+                assert_eq!(
+                    choice.values,
+                    vec![
+                        Value::Id(VideoFormat::BGRx.as_raw()),
+                        Value::Id(VideoFormat::BGRx.as_raw()),
+                        Value::Id(VideoFormat::YUY2.as_raw()),
+                    ]
+                );
+            }
+            value => panic!("unexpected format {:?}", value),
+        }
+
+        let modifier = object
+            .property(spa_sys::spa_format_SPA_FORMAT_VIDEO_modifier)
+            .unwrap();
+        assert_eq!(
+            modifier.flags,
+            PropertyFlags::MANDATORY | PropertyFlags::DONT_FIXATE
+        );
+
+        let size = object
+            .property(spa_sys::spa_format_SPA_FORMAT_VIDEO_size)
+            .unwrap();
+        match &size.value {
+            Value::Choice(choice) => {
+                assert_eq!(choice.type_, ChoiceType::Range);
+                assert_eq!(choice.values.len(), 3);
+            }
+            value => panic!("unexpected size {:?}", value),
+        }
+
+        // Choices are not fixed, so this is not a usable format yet.
+        assert_eq!(
+            VideoInfoRaw::parse(&pod),
+            Err(FormatError::InvalidProperty(
+                spa_sys::spa_format_SPA_FORMAT_VIDEO_format
+            ))
+        );
+    }
+}
@@ -0,0 +1,637 @@
+// Copyright 2021, Collabora Ltd.
+// SPDX-License-Identifier: MIT
+
+use bitflags::bitflags;
+use std::{io, mem, os::raw::c_void};
+
+use super::{ChoiceType, Fraction, Rectangle};
+
+bitflags! {
+    struct Flags: u32 {
+        /// Only write the body of the next values (used for array and choice children).
+        const BODY = 1 << 0;
+        /// The next value is the first child of an array or choice and still needs its header.
+        const FIRST = 1 << 1;
+    }
+}
+
+/// Size of the `spa_pod` header preceding every POD.
+const HEADER_SIZE: usize = 8;
+
+enum Data<'d> {
+    Slice(&'d mut [u8]),
+    Vec(&'d mut Vec<u8>),
+}
+
+/// A container being built, equivalent to `struct spa_pod_frame`.
+struct Frame {
+    /// Offset of the container header in the buffer.
+    offset: usize,
+    /// Size of the container body written so far.
+    size: u32,
+    type_: u32,
+    /// Flags of the builder when the container was pushed, restored when it is popped.
+    flags: Flags,
+}
+
+/// A builder writing PODs into a buffer.
+///
+/// This is the equivalent of the C `spa_pod_builder` and produces byte-for-byte identical output.
+///
+/// Containers (`Array`, `Struct`, `Object`, `Sequence` and `Choice`) are started with one of the
+/// `push_*` methods and finished with [`pop`](Self::pop). All values written in between are added
+/// to the innermost container.
+///
+/// When writing into a fixed buffer that is too small, methods return an `ENOSPC` error but the
+/// builder keeps track of the space that would have been needed, which can then be retrieved with
+/// [`offset`](Self::offset).
+///
+/// # Examples
+/// Build a struct containing an int and a string:
+/// ```rust
+/// use libspa::pod::Builder;
+///
+/// let mut buffer = Vec::new();
+/// let mut builder = Builder::with_vec(&mut buffer);
+/// builder.push_struct().unwrap();
+/// builder.int(42).unwrap();
+/// builder.string("foo").unwrap();
+/// builder.pop().unwrap();
+/// let size = builder.offset();
+///
+/// assert_eq!(size, 40);
+/// assert_eq!(buffer.len(), 40);
+/// ```
+pub struct Builder<'d> {
+    data: Data<'d>,
+    offset: usize,
+    frames: Vec<Frame>,
+    flags: Flags,
+}
+
+fn no_space() -> io::Error {
+    io::Error::from_raw_os_error(libc::ENOSPC)
+}
+
+impl<'d> Builder<'d> {
+    /// Create a builder writing into the fixed size `data` buffer.
+    pub fn new(data: &'d mut [u8]) -> Self {
+        Self::from_data(Data::Slice(data))
+    }
+
+    /// Create a builder writing into `vec`, growing it as needed.
+    ///
+    /// Any previous content of `vec` is removed.
+    pub fn with_vec(vec: &'d mut Vec<u8>) -> Self {
+        vec.clear();
+        Self::from_data(Data::Vec(vec))
+    }
+
+    fn from_data(data: Data<'d>) -> Self {
+        Self {
+            data,
+            offset: 0,
+            frames: Vec::new(),
+            flags: Flags::empty(),
+        }
+    }
+
+    /// The number of bytes written so far.
+    ///
+    /// If the buffer was too small, this is the size the buffer would have needed.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Append raw bytes to the buffer, equivalent to `spa_pod_builder_raw`.
+    fn raw(&mut self, bytes: &[u8]) -> io::Result<()> {
+        let end = self.offset + bytes.len();
+
+        let res = match &mut self.data {
+            Data::Slice(slice) => {
+                if end > slice.len() {
+                    Err(no_space())
+                } else {
+                    slice[self.offset..end].copy_from_slice(bytes);
+                    Ok(())
+                }
+            }
+            Data::Vec(vec) => {
+                if end > vec.len() {
+                    vec.resize(end, 0);
+                }
+                vec[self.offset..end].copy_from_slice(bytes);
+                Ok(())
+            }
+        };
+
+        self.offset = end;
+        for frame in self.frames.iter_mut() {
+            frame.size += bytes.len() as u32;
+        }
+
+        res
+    }
+
+    /// Add zeroes so that a value of `size` bytes ends on an 8 bytes boundary.
+    fn pad(&mut self, size: usize) -> io::Result<()> {
+        let padding = round_up_8(size) - size;
+        if padding > 0 {
+            self.raw(&[0; 8][..padding])
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Write a fixed size value, equivalent to `spa_pod_builder_primitive`.
+    ///
+    /// Inside arrays and choices only the first child gets a header,
+    /// the following ones are written without header nor padding.
+    fn primitive(&mut self, type_: u32, body: &[u8]) -> io::Result<()> {
+        if self.flags == Flags::BODY {
+            return self.raw(body);
+        }
+
+        let mut pod = [0; HEADER_SIZE + 16];
+        let size = HEADER_SIZE + body.len();
+        pod[..HEADER_SIZE].copy_from_slice(&header(body.len() as u32, type_));
+        pod[HEADER_SIZE..size].copy_from_slice(body);
+
+        self.flags.remove(Flags::FIRST);
+        let res = self.raw(&pod[..size]);
+        if self.flags != Flags::BODY {
+            res.and(self.pad(size))
+        } else {
+            res
+        }
+    }
+
+    /// Start a new container whose header has just been written at `offset`.
+    fn push(&mut self, offset: usize, size: u32, type_: u32) {
+        self.frames.push(Frame {
+            offset,
+            size,
+            type_,
+            flags: self.flags,
+        });
+
+        if type_ == spa_sys::SPA_TYPE_Array || type_ == spa_sys::SPA_TYPE_Choice {
+            self.flags = Flags::BODY | Flags::FIRST;
+        }
+    }
+
+    /// Finish the innermost container started with one of the `push_*` methods.
+    ///
+    /// # Errors
+    /// Returns an `InvalidInput` error if there is no container to finish.
+    pub fn pop(&mut self) -> io::Result<()> {
+        if self.frames.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "no container to pop",
+            ));
+        }
+
+        let mut res = Ok(());
+        if self.flags.contains(Flags::FIRST) {
+            // Empty array or choice, its children are still described by a None header.
+            res = self.raw(&header(0, spa_sys::SPA_TYPE_None));
+        }
+
+        // Now that the size of the container is known, fill in its header.
+        let frame = self.frames.pop().unwrap();
+        let pod = header(frame.size, frame.type_);
+        let end = frame.offset + HEADER_SIZE + frame.size as usize;
+        match &mut self.data {
+            Data::Slice(slice) if end > slice.len() => res = res.and(Err(no_space())),
+            Data::Slice(slice) => {
+                slice[frame.offset..frame.offset + HEADER_SIZE].copy_from_slice(&pod)
+            }
+            Data::Vec(vec) => vec[frame.offset..frame.offset + HEADER_SIZE].copy_from_slice(&pod),
+        }
+
+        self.flags = frame.flags;
+        res.and(self.pad(self.offset))
+    }
+
+    /// Write a `None` POD.
+    pub fn none(&mut self) -> io::Result<()> {
+        self.primitive(spa_sys::SPA_TYPE_None, &[])
+    }
+
+    /// Write a `Bool` POD.
+    pub fn bool(&mut self, val: bool) -> io::Result<()> {
+        self.primitive(spa_sys::SPA_TYPE_Bool, &(val as i32).to_ne_bytes())
+    }
+
+    /// Write an `Id` POD.
+    pub fn id(&mut self, val: u32) -> io::Result<()> {
+        self.primitive(spa_sys::SPA_TYPE_Id, &val.to_ne_bytes())
+    }
+
+    /// Write an `Int` POD.
+    pub fn int(&mut self, val: i32) -> io::Result<()> {
+        self.primitive(spa_sys::SPA_TYPE_Int, &val.to_ne_bytes())
+    }
+
+    /// Write a `Long` POD.
+    pub fn long(&mut self, val: i64) -> io::Result<()> {
+        self.primitive(spa_sys::SPA_TYPE_Long, &val.to_ne_bytes())
+    }
+
+    /// Write a `Float` POD.
+    pub fn float(&mut self, val: f32) -> io::Result<()> {
+        self.primitive(spa_sys::SPA_TYPE_Float, &val.to_ne_bytes())
+    }
+
+    /// Write a `Double` POD.
+    pub fn double(&mut self, val: f64) -> io::Result<()> {
+        self.primitive(spa_sys::SPA_TYPE_Double, &val.to_ne_bytes())
+    }
+
+    /// Write a `String` POD.
+    ///
+    /// The string is written with a trailing null byte, so it must not contain null bytes itself
+    /// or readers will see it truncated.
+    pub fn string(&mut self, val: &str) -> io::Result<()> {
+        let len = val.len() + 1;
+        let res = self.raw(&header(len as u32, spa_sys::SPA_TYPE_String));
+        let res = res.and(self.raw(val.as_bytes()));
+        let res = res.and(self.raw(&[0]));
+        res.and(self.pad(self.offset))
+    }
+
+    /// Write a `Bytes` POD.
+    pub fn bytes(&mut self, val: &[u8]) -> io::Result<()> {
+        self.unsized_(spa_sys::SPA_TYPE_Bytes, val)
+    }
+
+    /// Write a `Bitmap` POD.
+    pub fn bitmap(&mut self, val: &[u8]) -> io::Result<()> {
+        self.unsized_(spa_sys::SPA_TYPE_Bitmap, val)
+    }
+
+    fn unsized_(&mut self, type_: u32, val: &[u8]) -> io::Result<()> {
+        let res = self.raw(&header(val.len() as u32, type_));
+        let res = res.and(self.raw(val));
+        res.and(self.pad(self.offset))
+    }
+
+    /// Write a `Rectangle` POD.
+    pub fn rectangle(&mut self, val: Rectangle) -> io::Result<()> {
+        let mut body = [0; 8];
+        body[..4].copy_from_slice(&val.width.to_ne_bytes());
+        body[4..].copy_from_slice(&val.height.to_ne_bytes());
+        self.primitive(spa_sys::SPA_TYPE_Rectangle, &body)
+    }
+
+    /// Write a `Fraction` POD.
+    pub fn fraction(&mut self, val: Fraction) -> io::Result<()> {
+        let mut body = [0; 8];
+        body[..4].copy_from_slice(&val.num.to_ne_bytes());
+        body[4..].copy_from_slice(&val.denom.to_ne_bytes());
+        self.primitive(spa_sys::SPA_TYPE_Fraction, &body)
+    }
+
+    /// Write a `Pointer` POD.
+    ///
+    /// `type_` is the SPA type of the data pointed to.
+    pub fn pointer(&mut self, type_: u32, val: *const c_void) -> io::Result<()> {
+        const PTR_SIZE: usize = mem::size_of::<usize>();
+
+        let mut body = [0; 8 + PTR_SIZE];
+        body[..4].copy_from_slice(&type_.to_ne_bytes());
+        body[8..].copy_from_slice(&(val as usize).to_ne_bytes());
+        self.primitive(spa_sys::SPA_TYPE_Pointer, &body)
+    }
+
+    /// Write a `Fd` POD.
+    ///
+    /// The value is usually the index of the file descriptor in the message it is sent with,
+    /// not the file descriptor itself.
+    pub fn fd(&mut self, val: i64) -> io::Result<()> {
+        self.primitive(spa_sys::SPA_TYPE_Fd, &val.to_ne_bytes())
+    }
+
+    /// Start an `Array` POD.
+    ///
+    /// All the values added to the array must have the same type and a fixed size.
+    pub fn push_array(&mut self) -> io::Result<()> {
+        let offset = self.offset;
+        let res = self.raw(&header(0, spa_sys::SPA_TYPE_Array));
+        self.push(offset, 0, spa_sys::SPA_TYPE_Array);
+        res
+    }
+
+    /// Start a `Struct` POD.
+    pub fn push_struct(&mut self) -> io::Result<()> {
+        let offset = self.offset;
+        let res = self.raw(&header(0, spa_sys::SPA_TYPE_Struct));
+        self.push(offset, 0, spa_sys::SPA_TYPE_Struct);
+        res
+    }
+
+    /// Start an `Object` POD.
+    ///
+    /// `type_` is the object type, such as `SPA_TYPE_OBJECT_Format`, and `id` the id of
+    /// the object, such as `SPA_PARAM_EnumFormat`.
+    ///
+    /// Properties are added with [`prop`](Self::prop), each followed by its value.
+    pub fn push_object(&mut self, type_: u32, id: u32) -> io::Result<()> {
+        let offset = self.offset;
+        let mut pod = [0; HEADER_SIZE + 8];
+        pod[..HEADER_SIZE].copy_from_slice(&header(8, spa_sys::SPA_TYPE_Object));
+        pod[8..12].copy_from_slice(&type_.to_ne_bytes());
+        pod[12..].copy_from_slice(&id.to_ne_bytes());
+        let res = self.raw(&pod);
+        self.push(offset, 8, spa_sys::SPA_TYPE_Object);
+        res
+    }
+
+    /// Start a `Sequence` POD whose control offsets are expressed in `unit`.
+    ///
+    /// Controls are added with [`control`](Self::control), each followed by its value.
+    pub fn push_sequence(&mut self, unit: u32) -> io::Result<()> {
+        let offset = self.offset;
+        let mut pod = [0; HEADER_SIZE + 8];
+        pod[..HEADER_SIZE].copy_from_slice(&header(8, spa_sys::SPA_TYPE_Sequence));
+        pod[8..12].copy_from_slice(&unit.to_ne_bytes());
+        let res = self.raw(&pod);
+        self.push(offset, 8, spa_sys::SPA_TYPE_Sequence);
+        res
+    }
+
+    /// Start a `Choice` POD.
+    ///
+    /// The first value added is the default one, followed by the values whose meaning depends on
+    /// `type_`. All the values must have the same type and a fixed size.
+    pub fn push_choice(&mut self, type_: ChoiceType, flags: u32) -> io::Result<()> {
+        let offset = self.offset;
+        let mut pod = [0; HEADER_SIZE + 8];
+        pod[..HEADER_SIZE].copy_from_slice(&header(8, spa_sys::SPA_TYPE_Choice));
+        pod[8..12].copy_from_slice(&type_.as_raw().to_ne_bytes());
+        pod[12..].copy_from_slice(&flags.to_ne_bytes());
+        let res = self.raw(&pod);
+        self.push(offset, 8, spa_sys::SPA_TYPE_Choice);
+        res
+    }
+
+    /// Start a property of the current object.
+    ///
+    /// The property value must be written right after this call.
+    pub fn prop(&mut self, key: u32, flags: u32) -> io::Result<()> {
+        let mut prop = [0; 8];
+        prop[..4].copy_from_slice(&key.to_ne_bytes());
+        prop[4..].copy_from_slice(&flags.to_ne_bytes());
+        self.raw(&prop)
+    }
+
+    /// Start a control of the current sequence.
+    ///
+    /// `type_` is the `spa_control_type` of the control, whose value must be written right
+    /// after this call.
+    pub fn control(&mut self, offset: u32, type_: u32) -> io::Result<()> {
+        let mut control = [0; 8];
+        control[..4].copy_from_slice(&offset.to_ne_bytes());
+        control[4..].copy_from_slice(&type_.to_ne_bytes());
+        self.raw(&control)
+    }
+}
+
+fn header(size: u32, type_: u32) -> [u8; HEADER_SIZE] {
+    let mut header = [0; HEADER_SIZE];
+    header[..4].copy_from_slice(&size.to_ne_bytes());
+    header[4..].copy_from_slice(&type_.to_ne_bytes());
+    header
+}
+
+fn round_up_8(size: usize) -> usize {
+    (size + 7) & !7
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Build the expected bytes of a POD from 32 bit words.
+    fn words(words: &[u32]) -> Vec<u8> {
+        words
+            .iter()
+            .flat_map(|w| w.to_ne_bytes().to_vec())
+            .collect()
+    }
+
+    fn build<F>(f: F) -> Vec<u8>
+    where
+        F: FnOnce(&mut Builder) -> io::Result<()>,
+    {
+        let mut buffer = Vec::new();
+        let mut builder = Builder::with_vec(&mut buffer);
+        f(&mut builder).unwrap();
+        assert!(builder.frames.is_empty());
+        buffer
+    }
+
+    #[test]
+    fn primitives() {
+        assert_eq!(build(|b| b.none()), words(&[0, spa_sys::SPA_TYPE_None]));
+        assert_eq!(
+            build(|b| b.bool(true)),
+            words(&[4, spa_sys::SPA_TYPE_Bool, 1, 0])
+        );
+        assert_eq!(build(|b| b.id(7)), words(&[4, spa_sys::SPA_TYPE_Id, 7, 0]));
+        assert_eq!(
+            build(|b| b.int(-1)),
+            words(&[4, spa_sys::SPA_TYPE_Int, u32::MAX, 0])
+        );
+        assert_eq!(
+            build(|b| b.long(1 << 32)),
+            words(&[8, spa_sys::SPA_TYPE_Long, 0, 1])
+        );
+        assert_eq!(
+            build(|b| b.float(1.5)),
+            words(&[4, spa_sys::SPA_TYPE_Float, 1.5f32.to_bits(), 0])
+        );
+        assert_eq!(
+            build(|b| b.rectangle(Rectangle {
+                width: 320,
+                height: 240
+            })),
+            words(&[8, spa_sys::SPA_TYPE_Rectangle, 320, 240])
+        );
+        assert_eq!(
+            build(|b| b.fraction(Fraction { num: 25, denom: 1 })),
+            words(&[8, spa_sys::SPA_TYPE_Fraction, 25, 1])
+        );
+    }
+
+    #[test]
+    fn string_and_bytes() {
+        let mut expected = words(&[4, spa_sys::SPA_TYPE_String]);
+        expected.extend_from_slice(b"foo\0\0\0\0\0");
+        assert_eq!(build(|b| b.string("foo")), expected);
+
+        let mut expected = words(&[8, spa_sys::SPA_TYPE_String]);
+        expected.extend_from_slice(b"1234567\0");
+        assert_eq!(build(|b| b.string("1234567")), expected);
+
+        let mut expected = words(&[3, spa_sys::SPA_TYPE_Bytes]);
+        expected.extend_from_slice(&[1, 2, 3, 0, 0, 0, 0, 0]);
+        assert_eq!(build(|b| b.bytes(&[1, 2, 3])), expected);
+    }
+
+    #[test]
+    fn array() {
+        let pod = build(|b| {
+            b.push_array()?;
+            b.int(1)?;
+            b.int(2)?;
+            b.int(3)?;
+            b.pop()
+        });
+        assert_eq!(
+            pod,
+            words(&[
+                20,
+                spa_sys::SPA_TYPE_Array,
+                4,
+                spa_sys::SPA_TYPE_Int,
+                1,
+                2,
+                3,
+                0
+            ])
+        );
+
+        let pod = build(|b| {
+            b.push_array()?;
+            b.pop()
+        });
+        assert_eq!(
+            pod,
+            words(&[8, spa_sys::SPA_TYPE_Array, 0, spa_sys::SPA_TYPE_None])
+        );
+    }
+
+    #[test]
+    fn struct_() {
+        let pod = build(|b| {
+            b.push_struct()?;
+            b.int(42)?;
+            b.long(3)?;
+            b.pop()
+        });
+        assert_eq!(
+            pod,
+            words(&[
+                32,
+                spa_sys::SPA_TYPE_Struct,
+                4,
+                spa_sys::SPA_TYPE_Int,
+                42,
+                0,
+                8,
+                spa_sys::SPA_TYPE_Long,
+                3,
+                0
+            ])
+        );
+    }
+
+    #[test]
+    fn object() {
+        let pod = build(|b| {
+            b.push_object(spa_sys::SPA_TYPE_OBJECT_Format, 3)?;
+            b.prop(1, 0)?;
+            b.id(2)?;
+            b.prop(2, 0)?;
+            b.push_choice(ChoiceType::Range, 0)?;
+            b.int(48000)?;
+            b.int(1)?;
+            b.int(96000)?;
+            b.pop()?;
+            b.pop()
+        });
+        assert_eq!(
+            pod,
+            words(&[
+                80,
+                spa_sys::SPA_TYPE_Object,
+                spa_sys::SPA_TYPE_OBJECT_Format,
+                3,
+                // First property
+                1,
+                0,
+                4,
+                spa_sys::SPA_TYPE_Id,
+                2,
+                0,
+                // Second property
+                2,
+                0,
+                28,
+                spa_sys::SPA_TYPE_Choice,
+                spa_sys::spa_choice_type_SPA_CHOICE_Range,
+                0,
+                4,
+                spa_sys::SPA_TYPE_Int,
+                48000,
+                1,
+                96000,
+                0
+            ])
+        );
+    }
+
+    #[test]
+    fn sequence() {
+        let pod = build(|b| {
+            b.push_sequence(0)?;
+            b.control(16, spa_sys::spa_control_type_SPA_CONTROL_Midi)?;
+            b.bytes(&[0x90, 0x40, 0x7f])?;
+            b.pop()
+        });
+        let mut expected = words(&[
+            32,
+            spa_sys::SPA_TYPE_Sequence,
+            0,
+            0,
+            16,
+            spa_sys::spa_control_type_SPA_CONTROL_Midi,
+            3,
+            spa_sys::SPA_TYPE_Bytes,
+        ]);
+        expected.extend_from_slice(&[0x90, 0x40, 0x7f, 0, 0, 0, 0, 0]);
+        assert_eq!(pod, expected);
+    }
+
+    #[test]
+    fn overflow() {
+        let mut buffer = [0; 24];
+        let mut builder = Builder::new(&mut buffer);
+        builder.push_struct().unwrap();
+        builder.int(1).unwrap();
+        let err = builder.long(2).unwrap_err();
+        assert_eq!(err.raw_os_error(), Some(libc::ENOSPC));
+        assert!(builder.pop().is_err());
+        // The builder still tracks the size the POD needs.
+        assert_eq!(builder.offset(), 40);
+
+        let mut buffer = vec![0; builder.offset()];
+        let mut builder = Builder::new(&mut buffer);
+        builder.push_struct().unwrap();
+        builder.int(1).unwrap();
+        builder.long(2).unwrap();
+        builder.pop().unwrap();
+    }
+
+    #[test]
+    fn pop_without_push() {
+        let mut buffer = Vec::new();
+        let mut builder = Builder::with_vec(&mut buffer);
+        assert_eq!(
+            builder.pop().unwrap_err().kind(),
+            io::ErrorKind::InvalidInput
+        );
+    }
+}
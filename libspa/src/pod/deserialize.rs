@@ -0,0 +1,457 @@
+// Copyright 2021, Collabora Ltd.
+// SPDX-License-Identifier: MIT
+
+//! Deserialize Rust types from PODs using [serde](https://serde.rs).
+//!
+//! This is the counterpart of the [`serialize`](super::serialize) module and expects PODs
+//! following the same mapping.
+//!
+//! In addition, `Rectangle` and `Fraction` PODs can be read as a tuple of two `u32`,
+//! `Fd` PODs as an `i64` and `Object` PODs as a map from property keys to their values.
+
+use serde::de::{self, Deserialize, DeserializeSeed, IntoDeserializer, Unexpected, Visitor};
+use std::{fmt, vec};
+
+use super::{ParseError, Pod, Value};
+
+/// Errors that can happen when deserializing a POD.
+#[derive(Debug)]
+pub enum Error {
+    /// The POD is malformed.
+    Parse(ParseError),
+    /// An error reported by the type being deserialized, usually because the POD does not
+    /// have the expected type.
+    Custom(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Parse(e) => write!(f, "failed to parse POD: {}", e),
+            Error::Custom(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl de::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Error::Custom(msg.to_string())
+    }
+}
+
+impl From<ParseError> for Error {
+    fn from(e: ParseError) -> Self {
+        Error::Parse(e)
+    }
+}
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// Deserialize a `T` from the POD at the start of `bytes`.
+pub fn from_bytes<'de, T: Deserialize<'de>>(bytes: &'de [u8]) -> Result<T> {
+    from_pod(&Pod::from_bytes(bytes)?)
+}
+
+/// Deserialize a `T` from `pod`.
+pub fn from_pod<'de, T: Deserialize<'de>>(pod: &Pod<'de>) -> Result<T> {
+    T::deserialize(Deserializer::from_pod(pod)?)
+}
+
+/// A deserializer reading values from a POD.
+pub struct Deserializer<'de> {
+    value: Value<'de>,
+}
+
+impl<'de> Deserializer<'de> {
+    /// Parse `pod` and create a deserializer for its content.
+    pub fn from_pod(pod: &Pod<'de>) -> Result<Self> {
+        Ok(Self::new(pod.value()?))
+    }
+
+    fn new(value: Value<'de>) -> Self {
+        Self { value }
+    }
+}
+
+fn unexpected<'a>(value: &'a Value) -> Unexpected<'a> {
+    match value {
+        Value::None => Unexpected::Unit,
+        Value::Bool(b) => Unexpected::Bool(*b),
+        Value::Id(id) => Unexpected::Unsigned((*id).into()),
+        Value::Int(i) => Unexpected::Signed((*i).into()),
+        Value::Long(l) => Unexpected::Signed(*l),
+        Value::Float(f) => Unexpected::Float((*f).into()),
+        Value::Double(d) => Unexpected::Float(*d),
+        Value::String(s) => Unexpected::Str(s),
+        Value::Bytes(b) | Value::Bitmap(b) => Unexpected::Bytes(b),
+        Value::Array(_) | Value::Struct(_) | Value::Rectangle(_) | Value::Fraction(_) => {
+            Unexpected::Seq
+        }
+        Value::Object(_) => Unexpected::Map,
+        Value::Sequence(_) => Unexpected::Other("sequence"),
+        Value::Pointer(..) => Unexpected::Other("pointer"),
+        Value::Fd(fd) => Unexpected::Signed(*fd),
+        Value::Choice(_) => Unexpected::Other("choice"),
+    }
+}
+
+impl<'de> de::Deserializer<'de> for Deserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.value {
+            Value::None => visitor.visit_unit(),
+            Value::Bool(b) => visitor.visit_bool(b),
+            Value::Id(id) => visitor.visit_u32(id),
+            Value::Int(i) => visitor.visit_i32(i),
+            Value::Long(l) => visitor.visit_i64(l),
+            Value::Float(f) => visitor.visit_f32(f),
+            Value::Double(d) => visitor.visit_f64(d),
+            Value::String(s) => visitor.visit_borrowed_str(s),
+            Value::Bytes(b) | Value::Bitmap(b) => visitor.visit_borrowed_bytes(b),
+            Value::Rectangle(r) => visitor.visit_seq(SeqAccess::new(vec![
+                Value::Id(r.width),
+                Value::Id(r.height),
+            ])),
+            Value::Fraction(f) => {
+                visitor.visit_seq(SeqAccess::new(vec![Value::Id(f.num), Value::Id(f.denom)]))
+            }
+            Value::Array(values) | Value::Struct(values) => {
+                visitor.visit_seq(SeqAccess::new(values))
+            }
+            Value::Object(object) => visitor.visit_map(MapAccess::new(
+                object
+                    .properties
+                    .into_iter()
+                    .flat_map(|p| vec![Value::Id(p.key), p.value])
+                    .collect(),
+            )),
+            Value::Fd(fd) => visitor.visit_i64(fd),
+            ref value => Err(de::Error::invalid_type(unexpected(value), &visitor)),
+        }
+    }
+
+    fn deserialize_u32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.value {
+            // u32 are serialized as Int
+            Value::Int(i) => visitor.visit_u32(i as u32),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_u64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.value {
+            // u64 are serialized as Long
+            Value::Long(l) => visitor.visit_u64(l as u64),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.value {
+            Value::None => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.value {
+            Value::Struct(values) => visitor.visit_map(MapAccess::new(values)),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        match self.value {
+            Value::Id(index) => visitor.visit_enum(EnumAccess {
+                index,
+                values: Vec::new(),
+            }),
+            Value::Struct(mut values) => match values.first() {
+                Some(Value::Id(index)) => {
+                    let index = *index;
+                    values.remove(0);
+                    visitor.visit_enum(EnumAccess { index, values })
+                }
+                _ => Err(de::Error::invalid_type(Unexpected::Seq, &visitor)),
+            },
+            ref value => Err(de::Error::invalid_type(unexpected(value), &visitor)),
+        }
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 u8 u16 f32 f64 char str string bytes byte_buf unit unit_struct seq
+        tuple tuple_struct struct identifier ignored_any
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
+
+struct SeqAccess<'de> {
+    values: vec::IntoIter<Value<'de>>,
+}
+
+impl<'de> SeqAccess<'de> {
+    fn new(values: Vec<Value<'de>>) -> Self {
+        Self {
+            values: values.into_iter(),
+        }
+    }
+}
+
+impl<'de> de::SeqAccess<'de> for SeqAccess<'de> {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>> {
+        self.values
+            .next()
+            .map(|value| seed.deserialize(Deserializer::new(value)))
+            .transpose()
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.values.len())
+    }
+}
+
+/// Access to a map whose keys and values are alternated.
+struct MapAccess<'de> {
+    values: vec::IntoIter<Value<'de>>,
+}
+
+impl<'de> MapAccess<'de> {
+    fn new(values: Vec<Value<'de>>) -> Self {
+        Self {
+            values: values.into_iter(),
+        }
+    }
+}
+
+impl<'de> de::MapAccess<'de> for MapAccess<'de> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>> {
+        self.values
+            .next()
+            .map(|key| seed.deserialize(Deserializer::new(key)))
+            .transpose()
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value> {
+        let value = self
+            .values
+            .next()
+            .ok_or_else(|| Error::Custom("map key without value".to_string()))?;
+        seed.deserialize(Deserializer::new(value))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.values.len() / 2)
+    }
+}
+
+struct EnumAccess<'de> {
+    index: u32,
+    /// The fields of the variant.
+    values: Vec<Value<'de>>,
+}
+
+impl<'de> de::EnumAccess<'de> for EnumAccess<'de> {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self)> {
+        let index: de::value::U32Deserializer<Error> = self.index.into_deserializer();
+        let variant = seed.deserialize(index)?;
+        Ok((variant, self))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for EnumAccess<'de> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<()> {
+        if self.values.is_empty() {
+            Ok(())
+        } else {
+            Err(de::Error::invalid_type(Unexpected::Seq, &"unit variant"))
+        }
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(mut self, seed: T) -> Result<T::Value> {
+        if self.values.len() != 1 {
+            return Err(de::Error::invalid_length(
+                self.values.len(),
+                &"newtype variant",
+            ));
+        }
+        seed.deserialize(Deserializer::new(self.values.remove(0)))
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value> {
+        visitor.visit_seq(SeqAccess::new(self.values))
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_seq(SeqAccess::new(self.values))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pod::{serialize::to_vec, Builder, Fraction, Rectangle};
+    use serde::{Deserialize, Serialize};
+    use std::{collections::BTreeMap, fmt::Debug};
+
+    fn round_trip<T>(value: T)
+    where
+        T: Serialize + for<'de> Deserialize<'de> + PartialEq + Debug,
+    {
+        let pod = to_vec(&value).unwrap();
+        let parsed: T = from_bytes(&pod).unwrap();
+        assert_eq!(value, parsed);
+    }
+
+    #[test]
+    fn primitives() {
+        round_trip(true);
+        round_trip(-3i8);
+        round_trip(u8::MAX);
+        round_trip(-300i16);
+        round_trip(i32::MIN);
+        round_trip(u32::MAX);
+        round_trip(i64::MIN);
+        round_trip(u64::MAX);
+        round_trip(1.5f32);
+        round_trip(-2.25f64);
+        round_trip('é');
+        round_trip(String::from("foo"));
+        round_trip(());
+        round_trip(Some(1));
+        round_trip(None::<i32>);
+    }
+
+    #[test]
+    fn borrowed_str() {
+        let pod = to_vec("foo").unwrap();
+        let parsed: &str = from_bytes(&pod).unwrap();
+        assert_eq!(parsed, "foo");
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Channel {
+        Mono,
+        Left,
+        Right,
+        Aux(u32),
+        Custom { name: String, index: i64 },
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Unit;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Newtype(i32);
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Params {
+        name: Option<String>,
+        rate: u32,
+        channels: Vec<Channel>,
+        volumes: Vec<f32>,
+        position: (i32, i64),
+        unit: Unit,
+        newtype: Newtype,
+        custom: Channel,
+        aux: Vec<Channel>,
+    }
+
+    #[test]
+    fn containers() {
+        round_trip(Vec::<i32>::new());
+        round_trip(vec![1u64, 2, 3]);
+        round_trip(Channel::Right);
+        round_trip(Channel::Aux(3));
+        round_trip(Channel::Custom {
+            name: "foo".into(),
+            index: 12,
+        });
+        round_trip(Params {
+            name: Some("test".into()),
+            rate: 44100,
+            channels: vec![Channel::Left, Channel::Right],
+            volumes: vec![0.5, 1.0],
+            position: (-1, 1 << 40),
+            unit: Unit,
+            newtype: Newtype(7),
+            custom: Channel::Custom {
+                name: "bar".into(),
+                index: -1,
+            },
+            aux: Vec::new(),
+        });
+
+        let mut map = BTreeMap::new();
+        map.insert("a".to_string(), 1);
+        map.insert("b".to_string(), 2);
+        round_trip(map);
+    }
+
+    #[test]
+    fn builder_pods() {
+        let mut buffer = Vec::new();
+        let mut builder = Builder::with_vec(&mut buffer);
+        builder.push_struct().unwrap();
+        builder
+            .rectangle(Rectangle {
+                width: 640,
+                height: 480,
+            })
+            .unwrap();
+        builder.fraction(Fraction { num: 30, denom: 1 }).unwrap();
+        builder
+            .push_object(spa_sys::SPA_TYPE_OBJECT_Format, 0)
+            .unwrap();
+        builder.prop(1, 0).unwrap();
+        builder.int(2).unwrap();
+        builder.pop().unwrap();
+        builder.pop().unwrap();
+
+        let parsed: ((u32, u32), (u32, u32), BTreeMap<u32, i32>) = from_bytes(&buffer).unwrap();
+        assert_eq!(parsed.0, (640, 480));
+        assert_eq!(parsed.1, (30, 1));
+        assert_eq!(parsed.2.get(&1), Some(&2));
+    }
+
+    #[test]
+    fn invalid_type() {
+        let pod = to_vec("foo").unwrap();
+        assert!(matches!(from_bytes::<i32>(&pod), Err(Error::Custom(_))));
+        assert!(matches!(
+            from_bytes::<i32>(&pod[..4]),
+            Err(Error::Parse(ParseError::Truncated))
+        ));
+    }
+}
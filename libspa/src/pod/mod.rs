@@ -0,0 +1,64 @@
+// Copyright 2021, Collabora Ltd.
+// SPDX-License-Identifier: MIT
+
+//! Types and functions to work with SPA PODs.
+//!
+//! A POD ("Plain Old Data") is the self-describing binary format used by SPA and PipeWire
+//! to exchange parameters, formats and control messages.
+//!
+//! Every POD starts with a 32 bit size followed by a 32 bit type and its body.
+//! The size does not include the header, and the body is padded so that the next POD
+//! is always aligned on 8 bytes.
+
+pub mod builder;
+pub use builder::Builder;
+#[cfg(feature = "serde")]
+pub mod deserialize;
+pub mod parser;
+pub use parser::{
+    Choice, Control, Object, ParseError, Pod, Property, PropertyFlags, Sequence, Value,
+};
+#[cfg(feature = "serde")]
+pub mod serialize;
+
+/// A rectangle, as stored in a `Rectangle` POD.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Rectangle {
+    pub width: u32,
+    pub height: u32,
+}
+
+/// A fraction, as stored in a `Fraction` POD.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Fraction {
+    pub num: u32,
+    pub denom: u32,
+}
+
+/// The kind of values a `Choice` POD is offering.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChoiceType {
+    /// Only the default value is valid.
+    None,
+    /// The default value, followed by a minimum and a maximum.
+    Range,
+    /// The default value, followed by a minimum, a maximum and a step.
+    Step,
+    /// The default value, followed by the list of valid values.
+    Enum,
+    /// The default value, followed by the flags that may be set.
+    Flags,
+}
+
+impl ChoiceType {
+    /// The raw `spa_choice_type` of this choice.
+    pub fn as_raw(self) -> u32 {
+        match self {
+            ChoiceType::None => spa_sys::spa_choice_type_SPA_CHOICE_None,
+            ChoiceType::Range => spa_sys::spa_choice_type_SPA_CHOICE_Range,
+            ChoiceType::Step => spa_sys::spa_choice_type_SPA_CHOICE_Step,
+            ChoiceType::Enum => spa_sys::spa_choice_type_SPA_CHOICE_Enum,
+            ChoiceType::Flags => spa_sys::spa_choice_type_SPA_CHOICE_Flags,
+        }
+    }
+}
@@ -0,0 +1,658 @@
+// Copyright 2021, Collabora Ltd.
+// SPDX-License-Identifier: MIT
+
+use bitflags::bitflags;
+use std::{
+    convert::TryInto,
+    fmt, mem,
+    os::raw::c_void,
+    str::{self, Utf8Error},
+};
+
+use super::{ChoiceType, Fraction, Rectangle};
+
+/// Size of the `spa_pod` header preceding every POD.
+const HEADER_SIZE: usize = 8;
+
+/// How deep containers can be nested before parsing gives up.
+///
+/// This prevents malformed PODs from exhausting the stack.
+const MAX_DEPTH: usize = 64;
+
+/// Errors that can happen when parsing a POD.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseError {
+    /// The data is too small to contain the POD it describes.
+    Truncated,
+    /// The body of a POD is too small for its type.
+    InvalidSize { type_: u32, size: u32 },
+    /// The type of a POD is unknown.
+    UnknownType(u32),
+    /// The kind of a `Choice` POD is unknown.
+    UnknownChoiceType(u32),
+    /// A `String` POD is not null terminated.
+    UnterminatedString,
+    /// A `String` POD is not valid utf-8.
+    InvalidUtf8(Utf8Error),
+    /// Containers are nested too deeply.
+    TooDeep,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::Truncated => write!(f, "POD is truncated"),
+            ParseError::InvalidSize { type_, size } => {
+                write!(f, "invalid size {} for POD of type {}", size, type_)
+            }
+            ParseError::UnknownType(type_) => write!(f, "unknown POD type {}", type_),
+            ParseError::UnknownChoiceType(type_) => write!(f, "unknown choice type {}", type_),
+            ParseError::UnterminatedString => write!(f, "string is not null terminated"),
+            ParseError::InvalidUtf8(e) => write!(f, "string is not valid utf-8: {}", e),
+            ParseError::TooDeep => write!(f, "containers are nested too deeply"),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl ChoiceType {
+    /// Get the choice type matching a raw `spa_choice_type`, if any.
+    pub fn from_raw(raw: u32) -> Option<Self> {
+        match raw {
+            spa_sys::spa_choice_type_SPA_CHOICE_None => Some(ChoiceType::None),
+            spa_sys::spa_choice_type_SPA_CHOICE_Range => Some(ChoiceType::Range),
+            spa_sys::spa_choice_type_SPA_CHOICE_Step => Some(ChoiceType::Step),
+            spa_sys::spa_choice_type_SPA_CHOICE_Enum => Some(ChoiceType::Enum),
+            spa_sys::spa_choice_type_SPA_CHOICE_Flags => Some(ChoiceType::Flags),
+            _ => None,
+        }
+    }
+}
+
+/// A borrowed POD.
+///
+/// The POD header has been checked to fit in the borrowed data, but its content is only validated
+/// when it is parsed with [`value`](Self::value). Parsing never reads outside of the POD, so it is
+/// safe to use on PODs received from other processes.
+///
+/// # Examples
+/// ```rust
+/// use libspa::pod::{Builder, Pod, Value};
+///
+/// let mut buffer = Vec::new();
+/// let mut builder = Builder::with_vec(&mut buffer);
+/// builder.push_struct().unwrap();
+/// builder.int(42).unwrap();
+/// builder.string("foo").unwrap();
+/// builder.pop().unwrap();
+///
+/// let pod = Pod::from_bytes(&buffer).unwrap();
+/// assert_eq!(
+///     pod.value().unwrap(),
+///     Value::Struct(vec![Value::Int(42), Value::String("foo")])
+/// );
+/// ```
+#[derive(Clone, Copy)]
+pub struct Pod<'a> {
+    /// The header and body of the POD, without trailing padding.
+    data: &'a [u8],
+}
+
+impl<'a> Pod<'a> {
+    /// Borrow the POD at the start of `bytes`.
+    ///
+    /// Any data following the POD is ignored.
+    pub fn from_bytes(bytes: &'a [u8]) -> Result<Self, ParseError> {
+        let (_, size) = read_header(bytes)?;
+        let end = HEADER_SIZE
+            .checked_add(size as usize)
+            .ok_or(ParseError::Truncated)?;
+        if bytes.len() < end {
+            return Err(ParseError::Truncated);
+        }
+
+        Ok(Self {
+            data: &bytes[..end],
+        })
+    }
+
+    /// Borrow a raw `spa_pod`.
+    ///
+    /// # Safety
+    /// `pod` must be non-null and point to a `spa_pod` header followed by at least the number of
+    /// bytes announced in its `size` field, which must stay valid and unmodified for `'a`.
+    pub unsafe fn from_raw(pod: *const spa_sys::spa_pod) -> Self {
+        debug_assert!(
+            !pod.is_null(),
+            "Pod must not be created from a NULL pointer"
+        );
+
+        let size = (*pod).size as usize;
+        Self {
+            data: std::slice::from_raw_parts(pod.cast(), HEADER_SIZE + size),
+        }
+    }
+
+    /// Obtain a pointer to the raw `spa_pod`.
+    ///
+    /// The pointer is only valid for as long as the data the POD was created from.
+    pub fn as_ptr(&self) -> *const spa_sys::spa_pod {
+        self.data.as_ptr().cast()
+    }
+
+    /// The raw SPA type of the POD.
+    pub fn type_(&self) -> u32 {
+        read_u32(self.data, 4)
+    }
+
+    /// The size of the POD body.
+    pub fn size(&self) -> u32 {
+        read_u32(self.data, 0)
+    }
+
+    /// The header and the body of the POD.
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// The body of the POD.
+    pub fn body(&self) -> &'a [u8] {
+        &self.data[HEADER_SIZE..]
+    }
+
+    /// Parse the POD and all its children.
+    pub fn value(&self) -> Result<Value<'a>, ParseError> {
+        parse_body(self.type_(), self.body(), 0)
+    }
+}
+
+impl fmt::Debug for Pod<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.value() {
+            Ok(value) => f.debug_tuple("Pod").field(&value).finish(),
+            Err(err) => f
+                .debug_struct("Pod")
+                .field("type", &self.type_())
+                .field("size", &self.size())
+                .field("error", &err)
+                .finish(),
+        }
+    }
+}
+
+/// A parsed POD.
+///
+/// Strings and bytes borrow the POD they were parsed from.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value<'a> {
+    None,
+    Bool(bool),
+    Id(u32),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    String(&'a str),
+    Bytes(&'a [u8]),
+    Rectangle(Rectangle),
+    Fraction(Fraction),
+    Bitmap(&'a [u8]),
+    /// An array of values, which all have the same type.
+    Array(Vec<Value<'a>>),
+    Struct(Vec<Value<'a>>),
+    Object(Object<'a>),
+    Sequence(Sequence<'a>),
+    /// A pointer, with the SPA type of the data it points to.
+    Pointer(u32, *const c_void),
+    Fd(i64),
+    Choice(Choice<'a>),
+}
+
+/// A parsed `Object` POD.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Object<'a> {
+    /// The object type, such as `SPA_TYPE_OBJECT_Format`.
+    pub type_: u32,
+    /// The object id, such as `SPA_PARAM_EnumFormat`.
+    pub id: u32,
+    pub properties: Vec<Property<'a>>,
+}
+
+impl<'a> Object<'a> {
+    /// Get the property with the provided key, if the object has one.
+    pub fn property(&self, key: u32) -> Option<&Property<'a>> {
+        self.properties.iter().find(|p| p.key == key)
+    }
+}
+
+bitflags! {
+    pub struct PropertyFlags: u32 {
+        const READONLY = spa_sys::SPA_POD_PROP_FLAG_READONLY;
+        const HARDWARE = spa_sys::SPA_POD_PROP_FLAG_HARDWARE;
+        const HINT_DICT = spa_sys::SPA_POD_PROP_FLAG_HINT_DICT;
+        const MANDATORY = spa_sys::SPA_POD_PROP_FLAG_MANDATORY;
+        const DONT_FIXATE = spa_sys::SPA_POD_PROP_FLAG_DONT_FIXATE;
+    }
+}
+
+/// A property of an [`Object`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Property<'a> {
+    pub key: u32,
+    pub flags: PropertyFlags,
+    pub value: Value<'a>,
+}
+
+/// A parsed `Sequence` POD.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sequence<'a> {
+    /// The unit of the control offsets.
+    pub unit: u32,
+    pub controls: Vec<Control<'a>>,
+}
+
+/// A timed control of a [`Sequence`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Control<'a> {
+    /// The offset of the control, relative to the start of the sequence.
+    pub offset: u32,
+    /// The raw `spa_control_type` of the control.
+    pub type_: u32,
+    pub value: Value<'a>,
+}
+
+/// A parsed `Choice` POD.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Choice<'a> {
+    pub type_: ChoiceType,
+    pub flags: u32,
+    /// The default value, followed by the values whose meaning depends on `type_`.
+    pub values: Vec<Value<'a>>,
+}
+
+impl<'a> Choice<'a> {
+    /// The default value of the choice.
+    pub fn default(&self) -> Option<&Value<'a>> {
+        self.values.first()
+    }
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_ne_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+fn read_i32(data: &[u8], offset: usize) -> i32 {
+    i32::from_ne_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+fn read_i64(data: &[u8], offset: usize) -> i64 {
+    i64::from_ne_bytes(data[offset..offset + 8].try_into().unwrap())
+}
+
+/// Read the type and size of the POD header at the start of `data`.
+fn read_header(data: &[u8]) -> Result<(u32, u32), ParseError> {
+    if data.len() < HEADER_SIZE {
+        return Err(ParseError::Truncated);
+    }
+    Ok((read_u32(data, 4), read_u32(data, 0)))
+}
+
+fn round_up_8(size: usize) -> usize {
+    (size + 7) & !7
+}
+
+/// Split the POD at the start of `data` into its type and body, returning the remaining data
+/// after the padding of the POD as well.
+fn split_pod(data: &[u8]) -> Result<(u32, &[u8], &[u8]), ParseError> {
+    let (type_, size) = read_header(data)?;
+    let end = HEADER_SIZE
+        .checked_add(size as usize)
+        .ok_or(ParseError::Truncated)?;
+    if data.len() < end {
+        return Err(ParseError::Truncated);
+    }
+    // The padding of the last POD of a container may be missing.
+    let next = round_up_8(end).min(data.len());
+
+    Ok((type_, &data[HEADER_SIZE..end], &data[next..]))
+}
+
+fn check_size(type_: u32, body: &[u8], min: usize) -> Result<(), ParseError> {
+    if body.len() < min {
+        Err(ParseError::InvalidSize {
+            type_,
+            size: body.len() as u32,
+        })
+    } else {
+        Ok(())
+    }
+}
+
+/// Parse the body of a POD of type `type_`.
+fn parse_body(type_: u32, body: &[u8], depth: usize) -> Result<Value<'_>, ParseError> {
+    if depth > MAX_DEPTH {
+        return Err(ParseError::TooDeep);
+    }
+
+    let value = match type_ {
+        spa_sys::SPA_TYPE_None => Value::None,
+        spa_sys::SPA_TYPE_Bool => {
+            check_size(type_, body, 4)?;
+            Value::Bool(read_i32(body, 0) != 0)
+        }
+        spa_sys::SPA_TYPE_Id => {
+            check_size(type_, body, 4)?;
+            Value::Id(read_u32(body, 0))
+        }
+        spa_sys::SPA_TYPE_Int => {
+            check_size(type_, body, 4)?;
+            Value::Int(read_i32(body, 0))
+        }
+        spa_sys::SPA_TYPE_Long => {
+            check_size(type_, body, 8)?;
+            Value::Long(read_i64(body, 0))
+        }
+        spa_sys::SPA_TYPE_Float => {
+            check_size(type_, body, 4)?;
+            Value::Float(f32::from_bits(read_u32(body, 0)))
+        }
+        spa_sys::SPA_TYPE_Double => {
+            check_size(type_, body, 8)?;
+            Value::Double(f64::from_bits(read_i64(body, 0) as u64))
+        }
+        spa_sys::SPA_TYPE_String => {
+            // Like in C, anything following the first null byte is ignored.
+            let nul = body
+                .iter()
+                .position(|&b| b == 0)
+                .ok_or(ParseError::UnterminatedString)?;
+            Value::String(str::from_utf8(&body[..nul]).map_err(ParseError::InvalidUtf8)?)
+        }
+        spa_sys::SPA_TYPE_Bytes => Value::Bytes(body),
+        spa_sys::SPA_TYPE_Rectangle => {
+            check_size(type_, body, 8)?;
+            Value::Rectangle(Rectangle {
+                width: read_u32(body, 0),
+                height: read_u32(body, 4),
+            })
+        }
+        spa_sys::SPA_TYPE_Fraction => {
+            check_size(type_, body, 8)?;
+            Value::Fraction(Fraction {
+                num: read_u32(body, 0),
+                denom: read_u32(body, 4),
+            })
+        }
+        spa_sys::SPA_TYPE_Bitmap => Value::Bitmap(body),
+        spa_sys::SPA_TYPE_Array => {
+            check_size(type_, body, HEADER_SIZE)?;
+            Value::Array(parse_values(body, depth)?)
+        }
+        spa_sys::SPA_TYPE_Struct => {
+            let mut values = Vec::new();
+            let mut data = body;
+            while !data.is_empty() {
+                let (type_, body, next) = split_pod(data)?;
+                values.push(parse_body(type_, body, depth + 1)?);
+                data = next;
+            }
+            Value::Struct(values)
+        }
+        spa_sys::SPA_TYPE_Object => {
+            check_size(type_, body, 8)?;
+            let mut properties = Vec::new();
+            let mut data = &body[8..];
+            while !data.is_empty() {
+                if data.len() < 8 {
+                    return Err(ParseError::Truncated);
+                }
+                let (type_, value, next) = split_pod(&data[8..])?;
+                properties.push(Property {
+                    key: read_u32(data, 0),
+                    flags: PropertyFlags::from_bits_truncate(read_u32(data, 4)),
+                    value: parse_body(type_, value, depth + 1)?,
+                });
+                data = next;
+            }
+            Value::Object(Object {
+                type_: read_u32(body, 0),
+                id: read_u32(body, 4),
+                properties,
+            })
+        }
+        spa_sys::SPA_TYPE_Sequence => {
+            check_size(type_, body, 8)?;
+            let mut controls = Vec::new();
+            let mut data = &body[8..];
+            while !data.is_empty() {
+                if data.len() < 8 {
+                    return Err(ParseError::Truncated);
+                }
+                let (type_, value, next) = split_pod(&data[8..])?;
+                controls.push(Control {
+                    offset: read_u32(data, 0),
+                    type_: read_u32(data, 4),
+                    value: parse_body(type_, value, depth + 1)?,
+                });
+                data = next;
+            }
+            Value::Sequence(Sequence {
+                unit: read_u32(body, 0),
+                controls,
+            })
+        }
+        spa_sys::SPA_TYPE_Pointer => {
+            check_size(type_, body, 8 + mem::size_of::<usize>())?;
+            let ptr =
+                usize::from_ne_bytes(body[8..8 + mem::size_of::<usize>()].try_into().unwrap());
+            Value::Pointer(read_u32(body, 0), ptr as *const c_void)
+        }
+        spa_sys::SPA_TYPE_Fd => {
+            check_size(type_, body, 8)?;
+            Value::Fd(read_i64(body, 0))
+        }
+        spa_sys::SPA_TYPE_Choice => {
+            check_size(type_, body, 8 + HEADER_SIZE)?;
+            let choice_type = read_u32(body, 0);
+            Value::Choice(Choice {
+                type_: ChoiceType::from_raw(choice_type)
+                    .ok_or(ParseError::UnknownChoiceType(choice_type))?,
+                flags: read_u32(body, 4),
+                values: parse_values(&body[8..], depth)?,
+            })
+        }
+        _ => return Err(ParseError::UnknownType(type_)),
+    };
+
+    Ok(value)
+}
+
+/// Parse the values of an array or choice body, starting with the header describing them.
+fn parse_values(data: &[u8], depth: usize) -> Result<Vec<Value<'_>>, ParseError> {
+    let (type_, size) = read_header(data)?;
+    let values = &data[HEADER_SIZE..];
+    if size == 0 {
+        return Ok(Vec::new());
+    }
+
+    values
+        .chunks_exact(size as usize)
+        .map(|body| parse_body(type_, body, depth + 1))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pod::Builder;
+
+    fn build<F>(f: F) -> Vec<u8>
+    where
+        F: FnOnce(&mut Builder) -> std::io::Result<()>,
+    {
+        let mut buffer = Vec::new();
+        let mut builder = Builder::with_vec(&mut buffer);
+        f(&mut builder).unwrap();
+        buffer
+    }
+
+    fn parse(data: &[u8]) -> Result<Value<'_>, ParseError> {
+        Pod::from_bytes(data)?.value()
+    }
+
+    #[test]
+    fn primitives() {
+        assert_eq!(parse(&build(|b| b.none())), Ok(Value::None));
+        assert_eq!(parse(&build(|b| b.bool(true))), Ok(Value::Bool(true)));
+        assert_eq!(parse(&build(|b| b.id(3))), Ok(Value::Id(3)));
+        assert_eq!(parse(&build(|b| b.int(-5))), Ok(Value::Int(-5)));
+        assert_eq!(parse(&build(|b| b.long(1 << 40))), Ok(Value::Long(1 << 40)));
+        assert_eq!(parse(&build(|b| b.float(0.5))), Ok(Value::Float(0.5)));
+        assert_eq!(parse(&build(|b| b.double(-2.5))), Ok(Value::Double(-2.5)));
+        assert_eq!(parse(&build(|b| b.string("foo"))), Ok(Value::String("foo")));
+        assert_eq!(
+            parse(&build(|b| b.bytes(&[1, 2, 3]))),
+            Ok(Value::Bytes(&[1, 2, 3]))
+        );
+        assert_eq!(
+            parse(&build(|b| b.fraction(Fraction { num: 30, denom: 1 }))),
+            Ok(Value::Fraction(Fraction { num: 30, denom: 1 }))
+        );
+        assert_eq!(parse(&build(|b| b.fd(2))), Ok(Value::Fd(2)));
+    }
+
+    #[test]
+    fn containers() {
+        let data = build(|b| {
+            b.push_object(spa_sys::SPA_TYPE_OBJECT_Format, 3)?;
+            b.prop(1, spa_sys::SPA_POD_PROP_FLAG_READONLY)?;
+            b.push_array()?;
+            b.float(1.0)?;
+            b.float(2.0)?;
+            b.pop()?;
+            b.prop(2, 0)?;
+            b.push_choice(ChoiceType::Enum, 0)?;
+            b.id(1)?;
+            b.id(1)?;
+            b.id(2)?;
+            b.pop()?;
+            b.prop(3, 0)?;
+            b.push_struct()?;
+            b.string("bar")?;
+            b.push_sequence(0)?;
+            b.control(4, spa_sys::spa_control_type_SPA_CONTROL_Midi)?;
+            b.bytes(&[0x90])?;
+            b.pop()?;
+            b.pop()?;
+            b.pop()
+        });
+
+        let object = Object {
+            type_: spa_sys::SPA_TYPE_OBJECT_Format,
+            id: 3,
+            properties: vec![
+                Property {
+                    key: 1,
+                    flags: PropertyFlags::READONLY,
+                    value: Value::Array(vec![Value::Float(1.0), Value::Float(2.0)]),
+                },
+                Property {
+                    key: 2,
+                    flags: PropertyFlags::empty(),
+                    value: Value::Choice(Choice {
+                        type_: ChoiceType::Enum,
+                        flags: 0,
+                        values: vec![Value::Id(1), Value::Id(1), Value::Id(2)],
+                    }),
+                },
+                Property {
+                    key: 3,
+                    flags: PropertyFlags::empty(),
+                    value: Value::Struct(vec![
+                        Value::String("bar"),
+                        Value::Sequence(Sequence {
+                            unit: 0,
+                            controls: vec![Control {
+                                offset: 4,
+                                type_: spa_sys::spa_control_type_SPA_CONTROL_Midi,
+                                value: Value::Bytes(&[0x90]),
+                            }],
+                        }),
+                    ]),
+                },
+            ],
+        };
+
+        let value = parse(&data).unwrap();
+        assert_eq!(value, Value::Object(object));
+        if let Value::Object(object) = value {
+            assert_eq!(object.property(2).unwrap().key, 2);
+            assert!(object.property(4).is_none());
+        }
+    }
+
+    #[test]
+    fn empty_array() {
+        let data = build(|b| {
+            b.push_array()?;
+            b.pop()
+        });
+        assert_eq!(parse(&data), Ok(Value::Array(Vec::new())));
+    }
+
+    #[test]
+    fn malformed() {
+        // Too small for a header.
+        assert_eq!(parse(&[0; 4]), Err(ParseError::Truncated));
+
+        // Size bigger than the data.
+        let mut data = build(|b| b.int(1));
+        data[0] = 32;
+        assert_eq!(parse(&data), Err(ParseError::Truncated));
+
+        // Body too small for its type.
+        let mut data = build(|b| b.long(1));
+        data[0] = 4;
+        assert_eq!(
+            parse(&data),
+            Err(ParseError::InvalidSize {
+                type_: spa_sys::SPA_TYPE_Long,
+                size: 4
+            })
+        );
+
+        // Child of a struct larger than the struct itself.
+        let mut data = build(|b| {
+            b.push_struct()?;
+            b.int(1)?;
+            b.pop()
+        });
+        data[8] = 16;
+        assert_eq!(parse(&data), Err(ParseError::Truncated));
+
+        // String without a null byte.
+        let mut data = build(|b| b.string("1234567"));
+        data[15] = b'8';
+        assert_eq!(parse(&data), Err(ParseError::UnterminatedString));
+
+        // Unknown type.
+        let mut data = build(|b| b.int(1));
+        data[4] = 0xff;
+        assert_eq!(parse(&data), Err(ParseError::UnknownType(0xff)));
+    }
+
+    #[test]
+    fn too_deep() {
+        let data = build(|b| {
+            for _ in 0..=MAX_DEPTH + 1 {
+                b.push_struct()?;
+            }
+            for _ in 0..=MAX_DEPTH + 1 {
+                b.pop()?;
+            }
+            Ok(())
+        });
+        assert_eq!(parse(&data), Err(ParseError::TooDeep));
+    }
+}
@@ -0,0 +1,490 @@
+// Copyright 2021, Collabora Ltd.
+// SPDX-License-Identifier: MIT
+
+//! Serialize Rust types into PODs using [serde](https://serde.rs).
+//!
+//! Types are mapped to PODs this way:
+//! - `bool` is written as a `Bool`
+//! - `i8`, `i16`, `i32`, `u8`, `u16` and `u32` are written as an `Int`,
+//!   `i64` and `u64` as a `Long`, `f32` as a `Float` and `f64` as a `Double`
+//! - strings and chars are written as a `String`, byte buffers as `Bytes`
+//! - `None`, `()` and unit structs are written as a `None`, `Some(value)` as its value
+//! - sequences are written as an `Array`, so their elements must all be written as the same
+//!   fixed size type
+//! - structs, tuples and tuple structs are written as a `Struct` of their fields
+//! - maps are written as a `Struct` of their keys and values, alternated
+//! - unit enum variants are written as an `Id` containing the variant index, other variants as
+//!   a `Struct` containing that `Id` followed by their fields
+//!
+//! The names of fields and variants are not written, so the POD can only be read back by
+//! a type having its fields and variants in the same order.
+
+use serde::ser::{self, Serialize};
+use std::{fmt, io};
+
+use super::Builder;
+
+/// Errors that can happen when serializing a POD.
+#[derive(Debug)]
+pub enum Error {
+    /// The POD could not be written, see [`Builder`].
+    Io(io::Error),
+    /// A value that is not a fixed size type was added to an array.
+    UnsupportedArrayValue,
+    /// Values of different types were added to the same array.
+    MixedArrayTypes,
+    /// An error reported by the type being serialized.
+    Custom(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "failed to write POD: {}", e),
+            Error::UnsupportedArrayValue => write!(f, "arrays can only contain fixed size values"),
+            Error::MixedArrayTypes => write!(f, "arrays can only contain values of the same type"),
+            Error::Custom(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl ser::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Error::Custom(msg.to_string())
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// Serialize `value` into a newly allocated buffer.
+///
+/// # Examples
+/// ```rust
+/// use libspa::pod::{deserialize, serialize};
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Debug, PartialEq, Serialize, Deserialize)]
+/// struct Volume {
+///     muted: bool,
+///     channels: Vec<f32>,
+/// }
+///
+/// let volume = Volume {
+///     muted: false,
+///     channels: vec![0.5, 0.8],
+/// };
+/// let pod = serialize::to_vec(&volume).unwrap();
+/// let parsed: Volume = deserialize::from_bytes(&pod).unwrap();
+///
+/// assert_eq!(volume, parsed);
+/// ```
+pub fn to_vec<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>> {
+    let mut buffer = Vec::new();
+    let mut builder = Builder::with_vec(&mut buffer);
+    value.serialize(&mut Serializer::new(&mut builder))?;
+    Ok(buffer)
+}
+
+/// A serializer writing values into a [`Builder`].
+pub struct Serializer<'b, 'd> {
+    builder: &'b mut Builder<'d>,
+    /// Whether we are currently writing the elements of an array.
+    in_array: bool,
+    /// The type of the elements of the array being written, once known.
+    array_type: Option<u32>,
+}
+
+impl<'b, 'd> Serializer<'b, 'd> {
+    /// Create a serializer appending values to `builder`.
+    pub fn new(builder: &'b mut Builder<'d>) -> Self {
+        Self {
+            builder,
+            in_array: false,
+            array_type: None,
+        }
+    }
+
+    /// Check that a fixed size value of type `type_` can be written.
+    fn fixed(&mut self, type_: u32) -> Result<()> {
+        if self.in_array {
+            match self.array_type {
+                None => self.array_type = Some(type_),
+                Some(t) if t == type_ => {}
+                Some(_) => return Err(Error::MixedArrayTypes),
+            }
+        }
+        Ok(())
+    }
+
+    /// Check that a value that is not fixed size can be written.
+    fn unsized_(&self) -> Result<()> {
+        if self.in_array {
+            Err(Error::UnsupportedArrayValue)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn push_struct(&mut self) -> Result<()> {
+        self.unsized_()?;
+        self.builder.push_struct()?;
+        Ok(())
+    }
+
+    fn push_variant(&mut self, variant_index: u32) -> Result<()> {
+        self.push_struct()?;
+        self.builder.id(variant_index)?;
+        Ok(())
+    }
+}
+
+impl<'a, 'b, 'd> ser::Serializer for &'a mut Serializer<'b, 'd> {
+    type Ok = ();
+    type Error = Error;
+
+    type SerializeSeq = Self;
+    type SerializeTuple = Self;
+    type SerializeTupleStruct = Self;
+    type SerializeTupleVariant = Self;
+    type SerializeMap = Self;
+    type SerializeStruct = Self;
+    type SerializeStructVariant = Self;
+
+    fn serialize_bool(self, v: bool) -> Result<()> {
+        self.fixed(spa_sys::SPA_TYPE_Bool)?;
+        Ok(self.builder.bool(v)?)
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<()> {
+        self.serialize_i32(v.into())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<()> {
+        self.serialize_i32(v.into())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<()> {
+        self.fixed(spa_sys::SPA_TYPE_Int)?;
+        Ok(self.builder.int(v)?)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<()> {
+        self.fixed(spa_sys::SPA_TYPE_Long)?;
+        Ok(self.builder.long(v)?)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<()> {
+        self.serialize_i32(v.into())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<()> {
+        self.serialize_i32(v.into())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<()> {
+        self.serialize_i32(v as i32)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<()> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<()> {
+        self.fixed(spa_sys::SPA_TYPE_Float)?;
+        Ok(self.builder.float(v)?)
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<()> {
+        self.fixed(spa_sys::SPA_TYPE_Double)?;
+        Ok(self.builder.double(v)?)
+    }
+
+    fn serialize_char(self, v: char) -> Result<()> {
+        self.serialize_str(v.encode_utf8(&mut [0; 4]))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<()> {
+        self.unsized_()?;
+        Ok(self.builder.string(v)?)
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<()> {
+        self.unsized_()?;
+        Ok(self.builder.bytes(v)?)
+    }
+
+    fn serialize_none(self) -> Result<()> {
+        self.unsized_()?;
+        Ok(self.builder.none()?)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<()> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<()> {
+        self.serialize_none()
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<()> {
+        self.serialize_none()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+    ) -> Result<()> {
+        self.fixed(spa_sys::SPA_TYPE_Id)?;
+        Ok(self.builder.id(variant_index)?)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        self.push_variant(variant_index)?;
+        value.serialize(&mut *self)?;
+        Ok(self.builder.pop()?)
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self> {
+        self.unsized_()?;
+        self.builder.push_array()?;
+        self.in_array = true;
+        self.array_type = None;
+        Ok(self)
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self> {
+        self.push_struct()?;
+        Ok(self)
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, _len: usize) -> Result<Self> {
+        self.push_struct()?;
+        Ok(self)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self> {
+        self.push_variant(variant_index)?;
+        Ok(self)
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self> {
+        self.push_struct()?;
+        Ok(self)
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self> {
+        self.push_struct()?;
+        Ok(self)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self> {
+        self.push_variant(variant_index)?;
+        Ok(self)
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
+
+impl<'a, 'b, 'd> ser::SerializeSeq for &'a mut Serializer<'b, 'd> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<()> {
+        self.in_array = false;
+        Ok(self.builder.pop()?)
+    }
+}
+
+impl<'a, 'b, 'd> ser::SerializeTuple for &'a mut Serializer<'b, 'd> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<()> {
+        Ok(self.builder.pop()?)
+    }
+}
+
+impl<'a, 'b, 'd> ser::SerializeTupleStruct for &'a mut Serializer<'b, 'd> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<()> {
+        Ok(self.builder.pop()?)
+    }
+}
+
+impl<'a, 'b, 'd> ser::SerializeTupleVariant for &'a mut Serializer<'b, 'd> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<()> {
+        Ok(self.builder.pop()?)
+    }
+}
+
+impl<'a, 'b, 'd> ser::SerializeMap for &'a mut Serializer<'b, 'd> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<()> {
+        key.serialize(&mut **self)
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<()> {
+        Ok(self.builder.pop()?)
+    }
+}
+
+impl<'a, 'b, 'd> ser::SerializeStruct for &'a mut Serializer<'b, 'd> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        _key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<()> {
+        Ok(self.builder.pop()?)
+    }
+}
+
+impl<'a, 'b, 'd> ser::SerializeStructVariant for &'a mut Serializer<'b, 'd> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        _key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<()> {
+        Ok(self.builder.pop()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pod::{Pod, Value};
+    use serde::Serialize;
+
+    fn serialize<T: Serialize>(value: &T) -> Vec<u8> {
+        to_vec(value).unwrap()
+    }
+
+    #[test]
+    fn mapping() {
+        #[derive(Serialize)]
+        #[allow(dead_code)]
+        enum Format {
+            S16,
+            F32,
+        }
+
+        #[derive(Serialize)]
+        struct Params {
+            format: Format,
+            rate: u32,
+            name: Option<&'static str>,
+            channels: Vec<i32>,
+        }
+
+        let params = Params {
+            format: Format::F32,
+            rate: 48000,
+            name: None,
+            channels: vec![1, 2],
+        };
+        let pod = serialize(&params);
+        assert_eq!(
+            Pod::from_bytes(&pod).unwrap().value().unwrap(),
+            Value::Struct(vec![
+                Value::Id(1),
+                Value::Int(48000),
+                Value::None,
+                Value::Array(vec![Value::Int(1), Value::Int(2)]),
+            ])
+        );
+    }
+
+    #[test]
+    fn invalid_arrays() {
+        assert!(matches!(
+            to_vec(&vec!["foo"]),
+            Err(Error::UnsupportedArrayValue)
+        ));
+        assert!(matches!(
+            to_vec(&vec![vec![1]]),
+            Err(Error::UnsupportedArrayValue)
+        ));
+        assert!(matches!(
+            to_vec(&vec![Some(1), None]),
+            Err(Error::UnsupportedArrayValue)
+        ));
+    }
+}
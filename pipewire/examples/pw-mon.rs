@@ -86,10 +86,10 @@ fn monitor(remote: Option<String>) -> Result<()> {
         .done(|_id, _seq| {
             // TODO
         })
-        .error(move |id, seq, res, message| {
-            eprintln!("error id:{} seq:{} res:{}: {}", id, seq, res, message);
+        .error(move |error| {
+            eprintln!("error: {}", error);
 
-            if id == 0 {
+            if error.id() == pw::PW_ID_CORE {
                 if let Some(main_loop) = main_loop_weak.upgrade() {
                     main_loop.quit();
                 }
@@ -109,35 +109,35 @@ fn monitor(remote: Option<String>) -> Result<()> {
             if let Some(registry) = registry_weak.upgrade() {
                 let p: Option<(Box<dyn ProxyT>, Box<dyn Listener>)> = match obj.type_ {
                     ObjectType::Node => {
-                        let node: Node = registry.bind(&obj).unwrap();
+                        let node: Node = registry.bind(obj).unwrap();
                         let obj_listener = node
                             .add_listener_local()
                             .info(|info| {
                                 dbg!(info);
                             })
-                            .param(|seq, id, index, next| {
-                                dbg!((seq, id, index, next));
+                            .param(|seq, id, index, next, param| {
+                                dbg!((seq, id, index, next, param));
                             })
                             .register();
 
                         Some((Box::new(node), Box::new(obj_listener)))
                     }
                     ObjectType::Port => {
-                        let port: Port = registry.bind(&obj).unwrap();
+                        let port: Port = registry.bind(obj).unwrap();
                         let obj_listener = port
                             .add_listener_local()
                             .info(|info| {
                                 dbg!(info);
                             })
-                            .param(|seq, id, index, next| {
-                                dbg!((seq, id, index, next));
+                            .param(|seq, id, index, next, param| {
+                                dbg!((seq, id, index, next, param));
                             })
                             .register();
 
                         Some((Box::new(port), Box::new(obj_listener)))
                     }
                     ObjectType::Link => {
-                        let link: Link = registry.bind(&obj).unwrap();
+                        let link: Link = registry.bind(obj).unwrap();
                         let obj_listener = link
                             .add_listener_local()
                             .info(|info| {
@@ -1,7 +1,7 @@
 //! This program is the rust equivalent of https://gitlab.freedesktop.org/pipewire/pipewire/-/blob/master/doc/tutorial3.md.
 
-use pipewire::*;
-use std::{cell::Cell, rc::Rc};
+use futures::{FutureExt, StreamExt};
+use pipewire::{async_::RegistryEvent, *};
 
 fn main() {
     pipewire::init();
@@ -16,38 +16,19 @@ fn roundtrip() {
     let context = Context::new(&mainloop).expect("Failed to create context");
     let core = context.connect(None).expect("Failed to connect to core");
     let registry = core.get_registry();
+    let mut events = registry.events();
 
-    // To comply with Rust's safety rules, we wrap this variable in an `Rc` and  a `Cell`.
-    let done = Rc::new(Cell::new(false));
+    // The server announces all the existing globals before answering the sync.
+    mainloop
+        .run_until(core.sync_future())
+        .expect("Failed to sync with the server");
 
-    // Create new reference for each variable so that they can be moved into the closure.
-    let done_clone = done.clone();
-    let loop_clone = mainloop.clone();
-
-    // Trigger the sync event. The server's answer won't be processed until we start the main loop,
-    // so we can safely do this before setting up a callback. This lets us avoid using a Cell.
-    let pending = core.sync(0);
-
-    let _listener_core = core
-        .add_listener_local()
-        .done(move |id, seq| {
-            if id == PW_ID_CORE && seq == pending {
-                done_clone.set(true);
-                loop_clone.quit();
-            }
-        })
-        .register();
-    let _listener_reg = registry
-        .add_listener_local()
-        .global(|global| {
+    while let Some(Some(event)) = events.next().now_or_never() {
+        if let RegistryEvent::Global(global) = event {
             println!(
                 "object: id:{} type:{}/{}",
                 global.id, global.type_, global.version
             )
-        })
-        .register();
-
-    while !done.get() {
-        mainloop.run();
+        }
     }
 }
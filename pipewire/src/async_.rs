@@ -0,0 +1,240 @@
+// Copyright 2021, Collabora Ltd.
+// SPDX-License-Identifier: MIT
+
+//! Futures and streams for the events of the PipeWire objects, available with the `async`
+//! feature.
+//!
+//! They only make progress while the loop of their objects runs, which can be done until one of
+//! them completes with [`MainLoopInner::run_until`](crate::MainLoopInner::run_until).
+
+use futures::{
+    channel::{mpsc, oneshot},
+    ready, Stream,
+};
+use std::{
+    cell::RefCell,
+    future::Future,
+    pin::Pin,
+    rc::Rc,
+    task::{Context, Poll},
+};
+
+use crate::{
+    core_::{self, Core, PW_ID_CORE},
+    proxy::{ProxyListener, ProxyT},
+    registry::{self, GlobalObject, Registry},
+    Error, Properties,
+};
+use spa::dict::ReadableDict;
+
+/// A sender shared by the callbacks of a listener, only the first one to be called is used.
+type SharedSender<T> = Rc<RefCell<Option<oneshot::Sender<T>>>>;
+
+fn send<T>(sender: &SharedSender<T>, value: T) {
+    if let Some(sender) = sender.borrow_mut().take() {
+        // The receiver is owned by the future which also owns the listener, so it is still there.
+        let _ = sender.send(value);
+    }
+}
+
+impl Core {
+    /// Like [`sync`](Self::sync), but returns a future resolving once the server has processed
+    /// all the previous requests.
+    ///
+    /// This is a separate method so that enabling the `async` feature doesn't change `sync`.
+    pub fn sync_future(&self) -> SyncFuture {
+        let (sender, receiver) = oneshot::channel();
+        let sender: SharedSender<_> = Rc::new(RefCell::new(Some(sender)));
+        let sender_error = sender.clone();
+        let pending = self.sync(0);
+
+        let listener = self
+            .add_listener_local()
+            .done(move |id, seq| {
+                if id == PW_ID_CORE && seq == pending {
+                    send(&sender, Ok(()));
+                }
+            })
+            .error(move |error| {
+                if error.id() == PW_ID_CORE && error.seq() == pending {
+                    send(&sender_error, Err(error.clone().into()));
+                }
+            })
+            .register();
+
+        SyncFuture {
+            receiver,
+            _listener: listener,
+        }
+    }
+
+    /// Like [`create_object`](Self::create_object), but returns a future resolving to the object
+    /// and the id of its global once the server has created it, or to the error it reported.
+    pub fn create_object_future<P: ProxyT + Unpin, D: ReadableDict>(
+        &self,
+        factory_name: &str,
+        properties: &D,
+    ) -> Result<CreateObjectFuture<P>, Error> {
+        let proxy: P = self.create_object(factory_name, properties)?;
+
+        let (sender, receiver) = oneshot::channel();
+        let sender: SharedSender<_> = Rc::new(RefCell::new(Some(sender)));
+        let sender_error = sender.clone();
+
+        let listener = proxy
+            .upcast_ref()
+            .add_listener_local()
+            .bound(move |id| send(&sender, Ok(id)))
+            .error(move |error| send(&sender_error, Err(error.clone().into())))
+            .register();
+
+        Ok(CreateObjectFuture {
+            receiver,
+            listener: Some(listener),
+            proxy: Some(proxy),
+        })
+    }
+}
+
+/// Future returned by [`Core::sync_future`].
+pub struct SyncFuture {
+    receiver: oneshot::Receiver<Result<(), Error>>,
+    _listener: core_::Listener,
+}
+
+impl Future for SyncFuture {
+    type Output = Result<(), Error>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let res = ready!(Pin::new(&mut self.receiver).poll(cx));
+
+        Poll::Ready(res.expect("SyncFuture polled after completion"))
+    }
+}
+
+/// Future returned by [`Core::create_object_future`].
+pub struct CreateObjectFuture<P: ProxyT> {
+    receiver: oneshot::Receiver<Result<u32, Error>>,
+    // Declared before the proxy so it is removed before the proxy is destroyed.
+    listener: Option<ProxyListener>,
+    proxy: Option<P>,
+}
+
+impl<P: ProxyT + Unpin> Future for CreateObjectFuture<P> {
+    type Output = Result<(P, u32), Error>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let res = ready!(Pin::new(&mut self.receiver).poll(cx));
+        let res = res.expect("CreateObjectFuture polled after completion");
+
+        self.listener = None;
+        let proxy = self.proxy.take().unwrap();
+
+        Poll::Ready(res.map(|id| (proxy, id)))
+    }
+}
+
+/// An event of a [`Registry`], as returned by [`Registry::events`].
+pub enum RegistryEvent {
+    /// A global object was added.
+    Global(GlobalObject<Properties>),
+    /// The global object with this id was removed.
+    GlobalRemove(u32),
+}
+
+impl Registry {
+    /// A stream of the events of the registry, starting with all the existing globals.
+    pub fn events(&self) -> RegistryEvents {
+        let (sender, receiver) = mpsc::unbounded();
+        let sender_remove = sender.clone();
+
+        let listener = self
+            .add_listener_local()
+            .global(move |global| {
+                // The props of the global are only valid during the callback, so copy them.
+                let _ = sender.unbounded_send(RegistryEvent::Global(global.to_owned()));
+            })
+            .global_remove(move |id| {
+                let _ = sender_remove.unbounded_send(RegistryEvent::GlobalRemove(id));
+            })
+            .register();
+
+        RegistryEvents {
+            receiver,
+            _listener: listener,
+        }
+    }
+}
+
+/// Stream returned by [`Registry::events`].
+pub struct RegistryEvents {
+    receiver: mpsc::UnboundedReceiver<RegistryEvent>,
+    _listener: registry::Listener,
+}
+
+impl Stream for RegistryEvents {
+    type Item = RegistryEvent;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.receiver).poll_next(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::StreamExt;
+    use std::io;
+
+    use super::*;
+    use crate::{
+        link::Link, properties, types::ObjectType, utils::connect_to_private_server, MainLoop,
+    };
+
+    #[test]
+    fn sync_future() {
+        crate::init();
+        let main_loop = MainLoop::new().unwrap();
+        let (_context, core) = connect_to_private_server(&main_loop);
+
+        main_loop.run_until(core.sync_future()).unwrap();
+        // Each sync resolves on its own done event.
+        main_loop.run_until(core.sync_future()).unwrap();
+    }
+
+    #[test]
+    fn create_object_future_error() {
+        crate::init();
+        let main_loop = MainLoop::new().unwrap();
+        let (_context, core) = connect_to_private_server(&main_loop);
+
+        let future = core
+            .create_object_future::<Link, _>(
+                "no-such-factory",
+                &properties! { "object.linger" => "false" },
+            )
+            .unwrap();
+
+        match main_loop.run_until(future) {
+            Err(Error::ServerError(error)) => assert_eq!(error.kind(), io::ErrorKind::NotFound),
+            res => panic!("unexpected result {:?}", res.map(|(_, id)| id)),
+        }
+    }
+
+    #[test]
+    fn registry_events() {
+        crate::init();
+        let main_loop = MainLoop::new().unwrap();
+        let (_context, core) = connect_to_private_server(&main_loop);
+        let registry = core.get_registry();
+        let mut events = registry.events();
+
+        // The core of the server is always the first global.
+        match main_loop.run_until(events.next()) {
+            Some(RegistryEvent::Global(global)) => {
+                assert_eq!(global.id, PW_ID_CORE);
+                assert_eq!(global.type_, ObjectType::Core);
+            }
+            _ => panic!("expected the global of the core"),
+        }
+    }
+}
@@ -0,0 +1,84 @@
+// Copyright 2021, Collabora Ltd.
+// SPDX-License-Identifier: MIT
+
+//! Drive a PipeWire loop from a calloop event loop, available with the `calloop` feature.
+
+use ::calloop::{
+    generic::{Fd, Generic},
+    EventSource, Interest, Mode, Poll, PostAction, Readiness, Token, TokenFactory,
+};
+use std::{io, time::Duration};
+
+use crate::loop_::Loop;
+
+/// An event source dispatching the events of a PipeWire loop, instead of running it.
+///
+/// The callback of the source is called after each dispatch, with the number of file
+/// descriptors of the PipeWire loop that had events.
+///
+/// # Examples
+/// ```ignore
+/// use pipewire as pw;
+///
+/// let mainloop = pw::MainLoop::new()?;
+/// let mut event_loop = calloop::EventLoop::<()>::try_new()?;
+///
+/// event_loop
+///     .handle()
+///     .insert_source(pw::calloop::LoopSource::new(mainloop.clone()), |_, _, _| {})?;
+/// ```
+pub struct LoopSource<L: Loop> {
+    loop_: L,
+    source: Generic<Fd>,
+}
+
+impl<L: Loop> LoopSource<L> {
+    pub fn new(loop_: L) -> Self {
+        let source = Generic::new(Fd(loop_.fd()), Interest::READ, Mode::Level);
+
+        Self { loop_, source }
+    }
+
+    pub fn loop_(&self) -> &L {
+        &self.loop_
+    }
+}
+
+impl<L: Loop> EventSource for LoopSource<L> {
+    type Event = i32;
+    type Metadata = ();
+    type Ret = ();
+
+    fn process_events<F>(
+        &mut self,
+        readiness: Readiness,
+        token: Token,
+        mut callback: F,
+    ) -> io::Result<PostAction>
+    where
+        F: FnMut(Self::Event, &mut Self::Metadata) -> Self::Ret,
+    {
+        let loop_ = &self.loop_;
+
+        self.source.process_events(readiness, token, |_, _| {
+            let n_fds = loop_
+                .iterate(Duration::from_secs(0))
+                .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
+            callback(n_fds, &mut ());
+
+            Ok(PostAction::Continue)
+        })
+    }
+
+    fn register(&mut self, poll: &mut Poll, token_factory: &mut TokenFactory) -> io::Result<()> {
+        self.source.register(poll, token_factory)
+    }
+
+    fn reregister(&mut self, poll: &mut Poll, token_factory: &mut TokenFactory) -> io::Result<()> {
+        self.source.reregister(poll, token_factory)
+    }
+
+    fn unregister(&mut self, poll: &mut Poll) -> io::Result<()> {
+        self.source.unregister(poll)
+    }
+}
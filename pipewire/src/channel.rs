@@ -0,0 +1,330 @@
+// Copyright 2021, Collabora Ltd.
+// SPDX-License-Identifier: MIT
+
+//! A channel to send messages from any thread to a callback called from a loop.
+//!
+//! This is the way to drive the PipeWire objects of a loop from other threads, such as a UI
+//! thread, as these objects may only be used from the thread of their loop.
+//!
+//! # Examples
+//! ```no_run
+//! use pipewire::{channel, MainLoop};
+//!
+//! enum Command {
+//!     Quit,
+//! }
+//!
+//! pipewire::init();
+//! let main_loop = MainLoop::new().unwrap();
+//! let (sender, receiver) = channel::channel();
+//!
+//! let _receiver = receiver
+//!     .attach_local(&main_loop, {
+//!         let main_loop = main_loop.clone();
+//!         move |command| match command {
+//!             Command::Quit => main_loop.quit(),
+//!         }
+//!     })
+//!     .unwrap();
+//!
+//! std::thread::spawn(move || sender.send(Command::Quit));
+//! main_loop.run();
+//! ```
+
+use std::{
+    collections::VecDeque,
+    fmt,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use crate::{
+    error::Error,
+    loop_::{EventSource, LocalLoop, Loop, RawEventSignaler},
+};
+
+/// The state shared by the two ends of a channel.
+struct Channel<T> {
+    queue: Mutex<VecDeque<T>>,
+    /// The event to signal when a message is sent, set while the receiver is attached.
+    event: Mutex<Option<RawEventSignaler>>,
+    receiver_dropped: AtomicBool,
+}
+
+/// Create a new channel, returning its two ends.
+///
+/// The messages sent with the [`Sender`] are queued until the [`Receiver`] is attached to a loop,
+/// which then calls its callback for each of them.
+pub fn channel<T>() -> (Sender<T>, Receiver<T>) {
+    let channel = Arc::new(Channel {
+        queue: Mutex::new(VecDeque::new()),
+        event: Mutex::new(None),
+        receiver_dropped: AtomicBool::new(false),
+    });
+
+    (
+        Sender {
+            channel: channel.clone(),
+        },
+        Receiver { channel },
+    )
+}
+
+/// The sending end of a [`channel`], which can be cloned and sent to other threads.
+pub struct Sender<T> {
+    channel: Arc<Channel<T>>,
+}
+
+impl<T> Sender<T> {
+    /// Send a message to the receiver.
+    ///
+    /// Returns the message back if the receiver was dropped.
+    pub fn send(&self, t: T) -> Result<(), T> {
+        if self.channel.receiver_dropped.load(Ordering::Acquire) {
+            return Err(t);
+        }
+
+        self.channel.queue.lock().unwrap().push_back(t);
+
+        // Keep the event locked while signaling it, so the receiver can't be detached meanwhile.
+        if let Some(event) = &*self.channel.event.lock().unwrap() {
+            // The event is only set while its source is alive.
+            // Signaling can only fail if the loop is out of resources, and the message is queued
+            // anyway, so it will be received with the next one.
+            let _ = unsafe { event.signal_event() };
+        }
+
+        Ok(())
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        Self {
+            channel: self.channel.clone(),
+        }
+    }
+}
+
+impl<T> fmt::Debug for Sender<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Sender").finish()
+    }
+}
+
+/// The receiving end of a [`channel`], which must be attached to a loop to receive messages.
+pub struct Receiver<T> {
+    channel: Arc<Channel<T>>,
+}
+
+impl<T: 'static> Receiver<T> {
+    /// Attach the receiver to `loop_`, which will call `callback` for each message sent.
+    ///
+    /// The messages sent before the receiver was attached are received first.
+    ///
+    /// The callback is called from the thread of the loop, so it and the messages must be
+    /// [`Send`]. With a [`ThreadLoop`](crate::ThreadLoop), this must be called with its lock held.
+    /// See [`attach_local`](Self::attach_local) for loops only used from their thread.
+    pub fn attach<L, F>(self, loop_: &L, callback: F) -> Result<AttachedReceiver<T, L>, Error>
+    where
+        L: Loop,
+        F: Fn(T) + Send + 'static,
+        T: Send,
+    {
+        let source = loop_.add_event(self.receive(callback))?;
+        Ok(self.attach_source(source))
+    }
+
+    /// Attach the receiver to `loop_`, which will call `callback` for each message sent.
+    ///
+    /// The messages sent before the receiver was attached are received first.
+    pub fn attach_local<L, F>(self, loop_: &L, callback: F) -> Result<AttachedReceiver<T, L>, Error>
+    where
+        L: LocalLoop,
+        F: Fn(T) + 'static,
+    {
+        let source = loop_.add_event_local(self.receive(callback))?;
+        Ok(self.attach_source(source))
+    }
+
+    /// The callback of the event source, receiving the queued messages.
+    fn receive<F: Fn(T)>(&self, callback: F) -> impl Fn(u64) {
+        let channel = self.channel.clone();
+        move |_count| loop {
+            // Release the queue before calling the callback, which may send other messages.
+            let message = channel.queue.lock().unwrap().pop_front();
+            match message {
+                Some(message) => callback(message),
+                None => break,
+            }
+        }
+    }
+
+    fn attach_source<L: Loop>(self, source: EventSource<L>) -> AttachedReceiver<T, L> {
+        *self.channel.event.lock().unwrap() = Some(source.raw_signaler());
+        if !self.channel.queue.lock().unwrap().is_empty() {
+            // Failing to signal is ignored like in `Sender::send`.
+            let _ = source.signal_event();
+        }
+
+        AttachedReceiver {
+            channel: self.channel.clone(),
+            receiver: Some(self),
+            source,
+        }
+    }
+}
+
+impl<T> fmt::Debug for Receiver<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Receiver").finish()
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        self.channel.receiver_dropped.store(true, Ordering::Release);
+    }
+}
+
+/// A [`Receiver`] attached to a loop with [`Receiver::attach`] or [`Receiver::attach_local`].
+///
+/// The receiver is dropped with it, unless it is detached with [`detach`](Self::detach).
+pub struct AttachedReceiver<'l, T, L: Loop> {
+    channel: Arc<Channel<T>>,
+    receiver: Option<Receiver<T>>,
+    // Keep the event of the loop calling the callback
+    #[allow(dead_code)]
+    source: EventSource<'l, L>,
+}
+
+impl<T, L: Loop> AttachedReceiver<'_, T, L> {
+    /// Detach the receiver from the loop, the messages sent from now on are queued until it is
+    /// attached again.
+    pub fn detach(mut self) -> Receiver<T> {
+        self.receiver.take().unwrap()
+    }
+}
+
+impl<T, L: Loop> fmt::Debug for AttachedReceiver<'_, T, L> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("AttachedReceiver").finish()
+    }
+}
+
+impl<T, L: Loop> Drop for AttachedReceiver<'_, T, L> {
+    fn drop(&mut self) {
+        // The source is destroyed after this, once the senders can no longer signal it.
+        *self.channel.event.lock().unwrap() = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc, thread, time::Duration};
+
+    use super::*;
+    use crate::{MainLoop, ThreadLoop};
+
+    #[test]
+    fn send_before_attach() {
+        crate::init();
+        let main_loop = MainLoop::new().unwrap();
+        let (sender, receiver) = channel();
+
+        for n in 0..3 {
+            sender.send(n).unwrap();
+        }
+
+        let received = Rc::new(RefCell::new(Vec::new()));
+        let _receiver = receiver
+            .attach_local(&main_loop, {
+                let received = received.clone();
+                let main_loop = main_loop.clone();
+                move |n: u32| {
+                    received.borrow_mut().push(n);
+                    if n == 2 {
+                        main_loop.quit();
+                    }
+                }
+            })
+            .unwrap();
+        main_loop.run();
+
+        assert_eq!(*received.borrow(), [0, 1, 2]);
+    }
+
+    #[test]
+    fn send_from_thread() {
+        crate::init();
+        let main_loop = MainLoop::new().unwrap();
+        let (sender, receiver) = channel();
+
+        let received = Rc::new(RefCell::new(Vec::new()));
+        let _receiver = receiver
+            .attach_local(&main_loop, {
+                let received = received.clone();
+                let main_loop = main_loop.clone();
+                move |n: u32| {
+                    received.borrow_mut().push(n);
+                    if n == 99 {
+                        main_loop.quit();
+                    }
+                }
+            })
+            .unwrap();
+
+        let thread = thread::spawn(move || {
+            for n in 0..100 {
+                sender.send(n).unwrap();
+            }
+        });
+        main_loop.run();
+        thread.join().unwrap();
+
+        assert_eq!(*received.borrow(), (0..100).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn receiver_dropped() {
+        crate::init();
+        let main_loop = MainLoop::new().unwrap();
+        let (sender, receiver) = channel();
+
+        let receiver = receiver
+            .attach_local(&main_loop, |_: u32| {})
+            .unwrap()
+            .detach();
+        assert_eq!(sender.send(0), Ok(()));
+
+        drop(receiver);
+        assert_eq!(sender.send(1), Err(1));
+    }
+
+    #[test]
+    fn thread_loop() {
+        crate::init();
+        let thread_loop = ThreadLoop::new(Some("test-loop")).unwrap();
+        let (sender, receiver) = channel();
+        let (received_sender, received) = std::sync::mpsc::channel();
+
+        let lock = thread_loop.lock();
+        let receiver = receiver
+            .attach(&thread_loop, move |n: u32| received_sender.send(n).unwrap())
+            .unwrap();
+        thread_loop.start().unwrap();
+        drop(lock);
+
+        for n in 0..10 {
+            sender.send(n).unwrap();
+        }
+        for n in 0..10 {
+            assert_eq!(received.recv_timeout(Duration::from_secs(5)), Ok(n));
+        }
+
+        thread_loop.stop();
+        drop(receiver);
+    }
+}
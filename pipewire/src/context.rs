@@ -1,46 +1,186 @@
 // Copyright 2020, Collabora Ltd.
 // SPDX-License-Identifier: MIT
 
-use std::ptr;
+use std::ffi::CString;
+use std::io;
+use std::os::unix::io::{IntoRawFd, OwnedFd};
+use std::ptr::{self, NonNull};
+use std::rc::Rc;
 
 use crate::core_::Core;
 use crate::error::Error;
 use crate::loop_::Loop;
+use crate::module::ImplModule;
 use crate::properties::Properties;
+use spa::dict::ReadableDict;
 
+/// A context, shared by its clones and by the cores connected with it.
 #[derive(Debug)]
-pub struct Context<T: Loop + Clone>(*mut pw_sys::pw_context, T);
+pub struct Context<T: Loop + Clone> {
+    inner: Rc<ContextInner<T>>,
+}
+
+#[derive(Debug)]
+struct ContextInner<T: Loop + Clone> {
+    ptr: *mut pw_sys::pw_context,
+    // Keep the loop alive as long as the context
+    #[allow(dead_code)]
+    loop_: T,
+}
 
 impl<T: Loop + Clone> Context<T> {
-    // TODO: properties argument
     pub fn new(loop_: &T) -> Result<Self, Error> {
+        Self::new_raw(loop_, ptr::null_mut())
+    }
+
+    /// Create a new context configured with `properties`.
+    ///
+    /// This is how the configuration of the context is picked, such as a config file with
+    /// `config.name`, or a private configuration with `context.profile.modules`.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use pipewire::{properties, Context, MainLoop};
+    ///
+    /// pipewire::init();
+    /// let main_loop = MainLoop::new().unwrap();
+    /// let context = Context::with_properties(
+    ///     &main_loop,
+    ///     properties! {
+    ///         "config.name" => "my-client.conf",
+    ///         "media.category" => "Manager"
+    ///     },
+    /// )
+    /// .unwrap();
+    /// ```
+    pub fn with_properties(loop_: &T, properties: Properties) -> Result<Self, Error> {
+        Self::new_raw(loop_, properties.into_raw())
+    }
+
+    fn new_raw(loop_: &T, properties: *mut pw_sys::pw_properties) -> Result<Self, Error> {
         unsafe {
-            let context = pw_sys::pw_context_new(loop_.as_ptr(), ptr::null_mut(), 0);
+            // The context takes ownership of the properties, even if it fails to be created.
+            let context = pw_sys::pw_context_new(loop_.as_ptr(), properties, 0);
             if context.is_null() {
                 Err(Error::CreationFailed)
             } else {
-                Ok(Context(context, loop_.clone()))
+                Ok(Context {
+                    inner: Rc::new(ContextInner {
+                        ptr: context,
+                        loop_: loop_.clone(),
+                    }),
+                })
             }
         }
     }
 
-    pub fn connect(&self, properties: Option<Properties>) -> Result<Core, Error> {
+    /// A copy of the properties of the context.
+    pub fn properties(&self) -> Properties {
+        unsafe {
+            let props = pw_sys::pw_context_get_properties(self.inner.ptr);
+            Properties::from_ptr(pw_sys::pw_properties_copy(props))
+        }
+    }
+
+    /// Update the properties of the context with the key-value pairs of `dict`.
+    ///
+    /// Returns the number of properties that changed.
+    pub fn update_properties<D: ReadableDict>(&self, dict: &D) -> usize {
+        let changed =
+            unsafe { pw_sys::pw_context_update_properties(self.inner.ptr, dict.get_dict_ptr()) };
+
+        changed.max(0) as usize
+    }
+
+    /// Connect to the PipeWire instance, whose name is taken from `properties` or the
+    /// environment.
+    ///
+    /// The returned core keeps the context alive until it is dropped, which disconnects it.
+    ///
+    /// On failure, the error has the errno of the cause, such as `ECONNREFUSED` or `ENOENT` if
+    /// there is no PipeWire instance to connect to, or `EPERM` if the access was denied.
+    pub fn connect(&self, properties: Option<Properties>) -> Result<Core, Error>
+    where
+        T: 'static,
+    {
         let properties = properties.map_or(ptr::null_mut(), |p| p.into_raw());
 
         unsafe {
-            let core = pw_sys::pw_context_connect(self.0, properties, 0);
+            let core = pw_sys::pw_context_connect(self.inner.ptr, properties, 0);
             if core.is_null() {
-                // TODO: check errno to set better error
-                Err(Error::CreationFailed)
+                Err(Error::SpaError(io::Error::last_os_error()))
+            } else {
+                Ok(Core::from_ptr(core, self.inner.clone()))
+            }
+        }
+    }
+
+    /// Connect to a PipeWire instance over the already connected socket `fd`, such as the one
+    /// handed out to sandboxed applications by the `OpenPipeWireRemote` method of the camera
+    /// portal.
+    ///
+    /// The socket is closed when the core is disconnected. If this fails, PipeWire may not have
+    /// taken the socket over yet, such as when the core could not be created, in which case it is
+    /// leaked rather than closed.
+    pub fn connect_fd(&self, fd: OwnedFd, properties: Option<Properties>) -> Result<Core, Error>
+    where
+        T: 'static,
+    {
+        let properties = properties.map_or(ptr::null_mut(), |p| p.into_raw());
+
+        unsafe {
+            let core =
+                pw_sys::pw_context_connect_fd(self.inner.ptr, fd.into_raw_fd(), properties, 0);
+            if core.is_null() {
+                Err(Error::SpaError(io::Error::last_os_error()))
             } else {
-                Ok(Core::from_ptr(core))
+                Ok(Core::from_ptr(core, self.inner.clone()))
+            }
+        }
+    }
+
+    /// Load the module `name`, such as `libpipewire-module-loopback`, in the process with the
+    /// arguments `args`.
+    ///
+    /// The module is unloaded when the returned handle is dropped.
+    ///
+    /// # Panics
+    /// If `name` or `args` contain a null byte.
+    pub fn load_module(
+        &self,
+        name: &str,
+        args: Option<&str>,
+        properties: Option<Properties>,
+    ) -> Result<ImplModule<'_>, Error> {
+        let name = CString::new(name).expect("Null byte in name parameter");
+        let args = args.map(|args| CString::new(args).expect("Null byte in args parameter"));
+        let properties = properties.map_or(ptr::null_mut(), |p| p.into_raw());
+
+        unsafe {
+            let module = pw_sys::pw_context_load_module(
+                self.inner.ptr,
+                name.as_ptr(),
+                args.as_ref().map_or(ptr::null(), |args| args.as_ptr()),
+                properties,
+            );
+            match NonNull::new(module) {
+                Some(module) => Ok(ImplModule::from_ptr(module)),
+                None => Err(Error::SpaError(io::Error::last_os_error())),
             }
         }
     }
 }
 
-impl<T: Loop + Clone> Drop for Context<T> {
+impl<T: Loop + Clone> Clone for Context<T> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<T: Loop + Clone> Drop for ContextInner<T> {
     fn drop(&mut self) {
-        unsafe { pw_sys::pw_context_destroy(self.0) }
+        unsafe { pw_sys::pw_context_destroy(self.ptr) }
     }
 }
@@ -4,27 +4,49 @@
 use bitflags::bitflags;
 use libc::{c_char, c_void};
 use std::ffi::{CStr, CString};
+use std::os::unix::io::RawFd;
 use std::pin::Pin;
-use std::{fmt, mem};
+use std::rc::Rc;
+use std::{any::Any, borrow::Cow, fmt, mem};
 
 use crate::{
     proxy::{Proxy, ProxyT},
     registry::Registry,
-    Error,
+    utils::message_seq,
+    Error, ServerError,
 };
-use spa::{dict::ForeignDict, spa_interface_call_method};
+use spa::{buffer::DataType, dict::ForeignDict, spa_interface_call_method};
 
 pub const PW_ID_CORE: u32 = pw_sys::PW_ID_CORE;
 
+/// A connection to a PipeWire instance, created with [`Context::connect`](crate::Context::connect).
+///
+/// The connection is shared by the clones of the core, by the proxies created from it and by the
+/// listeners registered on them, and is closed once they are all dropped.
+#[derive(Debug, Clone)]
+pub struct Core {
+    inner: Rc<CoreInner>,
+}
+
 #[derive(Debug)]
-pub struct Core(*mut pw_sys::pw_core);
+struct CoreInner {
+    ptr: *mut pw_sys::pw_core,
+    // Keep the context alive as long as the core is connected
+    #[allow(dead_code)]
+    context: Rc<dyn Any>,
+}
 
 impl Core {
-    pub(crate) fn from_ptr(core: *mut pw_sys::pw_core) -> Self {
-        Core(core)
+    pub(crate) fn from_ptr(core: *mut pw_sys::pw_core, context: Rc<dyn Any>) -> Self {
+        Core {
+            inner: Rc::new(CoreInner { ptr: core, context }),
+        }
+    }
+
+    pub(crate) fn as_ptr(&self) -> *mut pw_sys::pw_core {
+        self.inner.ptr
     }
 
-    // TODO: add non-local version when we'll bind pw_thread_loop_start()
     #[must_use]
     pub fn add_listener_local(&self) -> ListenerLocalBuilder {
         ListenerLocalBuilder {
@@ -33,11 +55,18 @@ impl Core {
         }
     }
 
+    /// Like [`add_listener_local`](Self::add_listener_local), but the callbacks can be called
+    /// from another thread, such as the one of a [`ThreadLoop`](crate::ThreadLoop).
+    #[must_use]
+    pub fn add_listener(&self) -> ListenerBuilder {
+        ListenerBuilder(self.add_listener_local())
+    }
+
     #[must_use]
     pub fn get_registry(&self) -> Registry {
         let registry = unsafe {
             spa_interface_call_method!(
-                self.0,
+                self.as_ptr(),
                 pw_sys::pw_core_methods,
                 get_registry,
                 pw_sys::PW_VERSION_REGISTRY,
@@ -45,7 +74,7 @@ impl Core {
             )
         };
 
-        Registry::new(registry)
+        Registry::new(registry, self.clone())
     }
 
     /* FIXME: Return type is a SPA Result as seen here:
@@ -55,10 +84,71 @@ impl Core {
     */
     pub fn sync(&self, seq: i32) -> i32 {
         unsafe {
-            spa_interface_call_method!(self.0, pw_sys::pw_core_methods, sync, PW_ID_CORE, seq)
+            spa_interface_call_method!(
+                self.as_ptr(),
+                pw_sys::pw_core_methods,
+                sync,
+                PW_ID_CORE,
+                seq
+            )
         }
     }
 
+    /// Answer a ping with `id` and `seq`.
+    ///
+    /// The core already answers the pings it receives, see [`ListenerLocalBuilder::ping`].
+    /// Returns the sequence number of the message.
+    pub fn pong(&self, id: u32, seq: i32) -> Result<i32, Error> {
+        let res = unsafe {
+            spa_interface_call_method!(self.as_ptr(), pw_sys::pw_core_methods, pong, id, seq)
+        };
+
+        message_seq(res)
+    }
+
+    /// Report an error with the object `id` to the server, with the negative errno `res`
+    /// and a `message`.
+    ///
+    /// Returns the sequence number of the message.
+    ///
+    /// # Panics
+    /// If `message` contains a null byte.
+    pub fn error(&self, id: u32, seq: i32, res: i32, message: &str) -> Result<i32, Error> {
+        let message = CString::new(message).expect("Null byte in message parameter");
+
+        let res = unsafe {
+            spa_interface_call_method!(
+                self.as_ptr(),
+                pw_sys::pw_core_methods,
+                error,
+                id,
+                seq,
+                res,
+                message.as_ptr()
+            )
+        };
+
+        message_seq(res)
+    }
+
+    /// Ask the server to destroy the object of `proxy`.
+    ///
+    /// The proxy itself stays valid until it is dropped, once the server removed the object.
+    /// Returns the sequence number of the message, which the errors about it reported to the
+    /// `error` callback of the core listeners have too.
+    pub fn destroy<P: ProxyT>(&self, proxy: &P) -> Result<i32, Error> {
+        let res = unsafe {
+            spa_interface_call_method!(
+                self.as_ptr(),
+                pw_sys::pw_core_methods,
+                destroy,
+                proxy.upcast_ref().as_ptr().cast()
+            )
+        };
+
+        message_seq(res)
+    }
+
     /// Create a new object on the PipeWire server from a factory.
     ///
     /// You will need specify what type you are expecting to be constructed by either using type inference or the
@@ -119,7 +209,7 @@ impl Core {
 
         let res = unsafe {
             spa_interface_call_method!(
-                self.0,
+                self.as_ptr(),
                 pw_sys::pw_core_methods,
                 create_object,
                 factory_name.as_ptr(),
@@ -131,19 +221,33 @@ impl Core {
         };
 
         if !res.is_null() {
-            Proxy::new(res.cast()).downcast().map_err(|(_, e)| e)
+            Proxy::new(res.cast(), self.clone())
+                .downcast()
+                .map_err(|(_, e)| e)
         } else {
             Err(Error::CreationFailed)
         }
     }
 }
+impl Drop for CoreInner {
+    fn drop(&mut self) {
+        unsafe {
+            pw_sys::pw_core_disconnect(self.ptr);
+        }
+    }
+}
+
 #[derive(Default)]
 struct ListenerLocalCallbacks {
     info: Option<Box<dyn Fn(&Info)>>,
     done: Option<Box<dyn Fn(u32, i32)>>,
+    error: Option<Box<dyn Fn(&ServerError)>>,
+    ping: Option<Box<dyn Fn(u32, i32)>>,
+    remove_id: Option<Box<dyn Fn(u32)>>,
+    bound_id: Option<Box<dyn Fn(u32, u32)>>,
     #[allow(clippy::type_complexity)]
-    error: Option<Box<dyn Fn(u32, i32, i32, &str)>>, // TODO: return a proper Error enum?
-                                                     // TODO: ping, remove_id, bound_id, add_mem, remove_mem
+    add_mem: Option<Box<dyn Fn(u32, Option<DataType>, RawFd, MemblockFlags)>>,
+    remove_mem: Option<Box<dyn Fn(u32)>>,
 }
 
 pub struct ListenerLocalBuilder<'a> {
@@ -151,6 +255,80 @@ pub struct ListenerLocalBuilder<'a> {
     cbs: ListenerLocalCallbacks,
 }
 
+/// Builder of a listener whose callbacks can be called from another thread.
+pub struct ListenerBuilder<'a>(ListenerLocalBuilder<'a>);
+
+impl<'a> ListenerBuilder<'a> {
+    #[must_use]
+    pub fn info<F>(self, info: F) -> Self
+    where
+        F: Fn(&Info) + Send + 'static,
+    {
+        Self(self.0.info(info))
+    }
+
+    #[must_use]
+    pub fn done<F>(self, done: F) -> Self
+    where
+        F: Fn(u32, i32) + Send + 'static,
+    {
+        Self(self.0.done(done))
+    }
+
+    #[must_use]
+    pub fn error<F>(self, error: F) -> Self
+    where
+        F: Fn(&ServerError) + Send + 'static,
+    {
+        Self(self.0.error(error))
+    }
+
+    #[must_use]
+    pub fn ping<F>(self, ping: F) -> Self
+    where
+        F: Fn(u32, i32) + Send + 'static,
+    {
+        Self(self.0.ping(ping))
+    }
+
+    #[must_use]
+    pub fn remove_id<F>(self, remove_id: F) -> Self
+    where
+        F: Fn(u32) + Send + 'static,
+    {
+        Self(self.0.remove_id(remove_id))
+    }
+
+    #[must_use]
+    pub fn bound_id<F>(self, bound_id: F) -> Self
+    where
+        F: Fn(u32, u32) + Send + 'static,
+    {
+        Self(self.0.bound_id(bound_id))
+    }
+
+    #[must_use]
+    pub fn add_mem<F>(self, add_mem: F) -> Self
+    where
+        F: Fn(u32, Option<DataType>, RawFd, MemblockFlags) + Send + 'static,
+    {
+        Self(self.0.add_mem(add_mem))
+    }
+
+    #[must_use]
+    pub fn remove_mem<F>(self, remove_mem: F) -> Self
+    where
+        F: Fn(u32) + Send + 'static,
+    {
+        Self(self.0.remove_mem(remove_mem))
+    }
+
+    #[must_use]
+    pub fn register(self) -> Listener {
+        self.0.register()
+    }
+}
+
 pub struct Listener {
     // Need to stay allocated while the listener is registered
     #[allow(dead_code)]
@@ -158,6 +336,9 @@ pub struct Listener {
     listener: Pin<Box<spa_sys::spa_hook>>,
     #[allow(dead_code)]
     data: Box<ListenerLocalCallbacks>,
+    // Keep the core connected as long as the listener is registered on it
+    #[allow(dead_code)]
+    core: Core,
 }
 
 impl Listener {
@@ -191,15 +372,72 @@ impl<'a> ListenerLocalBuilder<'a> {
         self
     }
 
+    /// Called with the errors the server reports about the objects of the connection, and about
+    /// the connection itself with the id [`PW_ID_CORE`].
     #[must_use]
     pub fn error<F>(mut self, error: F) -> Self
     where
-        F: Fn(u32, i32, i32, &str) + 'static,
+        F: Fn(&ServerError) + 'static,
     {
         self.cbs.error = Some(Box::new(error));
         self
     }
 
+    /// Called with the id and seq of a ping from the server.
+    ///
+    /// The core answers the pings by itself, so this must not call [`Core::pong`].
+    #[must_use]
+    pub fn ping<F>(mut self, ping: F) -> Self
+    where
+        F: Fn(u32, i32) + 'static,
+    {
+        self.cbs.ping = Some(Box::new(ping));
+        self
+    }
+
+    /// Called with the id of a proxy the server removed.
+    #[must_use]
+    pub fn remove_id<F>(mut self, remove_id: F) -> Self
+    where
+        F: Fn(u32) + 'static,
+    {
+        self.cbs.remove_id = Some(Box::new(remove_id));
+        self
+    }
+
+    /// Called with the id of a proxy and the id of the global it was bound to.
+    #[must_use]
+    pub fn bound_id<F>(mut self, bound_id: F) -> Self
+    where
+        F: Fn(u32, u32) + 'static,
+    {
+        self.cbs.bound_id = Some(Box::new(bound_id));
+        self
+    }
+
+    /// Called when the server shares a memory block, with its id, the type of its memory, its
+    /// file descriptor and its flags.
+    ///
+    /// The file descriptor is owned by the core and must be duplicated to be kept.
+    #[must_use]
+    pub fn add_mem<F>(mut self, add_mem: F) -> Self
+    where
+        F: Fn(u32, Option<DataType>, RawFd, MemblockFlags) + 'static,
+    {
+        self.cbs.add_mem = Some(Box::new(add_mem));
+        self
+    }
+
+    /// Called with the id of a memory block that is no longer shared.
+    #[must_use]
+    pub fn remove_mem<F>(mut self, remove_mem: F) -> Self
+    where
+        F: Fn(u32) + 'static,
+    {
+        self.cbs.remove_mem = Some(Box::new(remove_mem));
+        self
+    }
+
     #[must_use]
     pub fn register(self) -> Listener {
         unsafe extern "C" fn core_events_info(
@@ -228,8 +466,50 @@ impl<'a> ListenerLocalBuilder<'a> {
             message: *const c_char,
         ) {
             let callbacks = (data as *mut ListenerLocalCallbacks).as_ref().unwrap();
-            let message = CStr::from_ptr(message).to_str().unwrap();
-            callbacks.error.as_ref().unwrap()(id, seq, res, message);
+            // The message comes from the server, which may not send valid UTF-8.
+            let message = if message.is_null() {
+                Cow::Borrowed("")
+            } else {
+                CStr::from_ptr(message).to_string_lossy()
+            };
+            let error = ServerError::new(id, seq, res, &message);
+            callbacks.error.as_ref().unwrap()(&error);
+        }
+
+        unsafe extern "C" fn core_events_ping(data: *mut c_void, id: u32, seq: i32) {
+            let callbacks = (data as *mut ListenerLocalCallbacks).as_ref().unwrap();
+            callbacks.ping.as_ref().unwrap()(id, seq);
+        }
+
+        unsafe extern "C" fn core_events_remove_id(data: *mut c_void, id: u32) {
+            let callbacks = (data as *mut ListenerLocalCallbacks).as_ref().unwrap();
+            callbacks.remove_id.as_ref().unwrap()(id);
+        }
+
+        unsafe extern "C" fn core_events_bound_id(data: *mut c_void, id: u32, global_id: u32) {
+            let callbacks = (data as *mut ListenerLocalCallbacks).as_ref().unwrap();
+            callbacks.bound_id.as_ref().unwrap()(id, global_id);
+        }
+
+        unsafe extern "C" fn core_events_add_mem(
+            data: *mut c_void,
+            id: u32,
+            type_: u32,
+            fd: i32,
+            flags: u32,
+        ) {
+            let callbacks = (data as *mut ListenerLocalCallbacks).as_ref().unwrap();
+            callbacks.add_mem.as_ref().unwrap()(
+                id,
+                DataType::from_raw(type_),
+                fd,
+                MemblockFlags::from_bits_truncate(flags),
+            );
+        }
+
+        unsafe extern "C" fn core_events_remove_mem(data: *mut c_void, id: u32) {
+            let callbacks = (data as *mut ListenerLocalCallbacks).as_ref().unwrap();
+            callbacks.remove_mem.as_ref().unwrap()(id);
         }
 
         let e = unsafe {
@@ -245,12 +525,27 @@ impl<'a> ListenerLocalBuilder<'a> {
             if self.cbs.error.is_some() {
                 e.error = Some(core_events_error);
             }
+            if self.cbs.ping.is_some() {
+                e.ping = Some(core_events_ping);
+            }
+            if self.cbs.remove_id.is_some() {
+                e.remove_id = Some(core_events_remove_id);
+            }
+            if self.cbs.bound_id.is_some() {
+                e.bound_id = Some(core_events_bound_id);
+            }
+            if self.cbs.add_mem.is_some() {
+                e.add_mem = Some(core_events_add_mem);
+            }
+            if self.cbs.remove_mem.is_some() {
+                e.remove_mem = Some(core_events_remove_mem);
+            }
 
             e
         };
 
         let (listener, data) = unsafe {
-            let ptr = self.core.0;
+            let ptr = self.core.as_ptr();
             let data = Box::into_raw(Box::new(self.cbs));
             let mut listener: Pin<Box<spa_sys::spa_hook>> = Box::pin(mem::zeroed());
             // Have to cast from pw-sys namespaced type to the equivalent spa-sys type
@@ -274,6 +569,7 @@ impl<'a> ListenerLocalBuilder<'a> {
             events: e,
             listener,
             data,
+            core: self.core.clone(),
         }
     }
 }
@@ -354,3 +650,15 @@ bitflags! {
         const PROPS = pw_sys::PW_CORE_CHANGE_MASK_PROPS as u64;
     }
 }
+
+bitflags! {
+    /// The flags of a memory block shared with [`ListenerLocalBuilder::add_mem`].
+    pub struct MemblockFlags: u32 {
+        const READABLE = pw_sys::pw_memblock_flags_PW_MEMBLOCK_FLAG_READABLE;
+        const WRITABLE = pw_sys::pw_memblock_flags_PW_MEMBLOCK_FLAG_WRITABLE;
+        const SEAL = pw_sys::pw_memblock_flags_PW_MEMBLOCK_FLAG_SEAL;
+        const MAP = pw_sys::pw_memblock_flags_PW_MEMBLOCK_FLAG_MAP;
+        const DONT_CLOSE = pw_sys::pw_memblock_flags_PW_MEMBLOCK_FLAG_DONT_CLOSE;
+        const DONT_NOTIFY = pw_sys::pw_memblock_flags_PW_MEMBLOCK_FLAG_DONT_NOTIFY;
+    }
+}
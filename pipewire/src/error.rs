@@ -1,6 +1,7 @@
 // Copyright 2020, Collabora Ltd.
 // SPDX-License-Identifier: MIT
 
+use std::{fmt, io};
 use thiserror::Error;
 #[derive(Error, Debug)]
 pub enum Error {
@@ -10,4 +11,87 @@ pub enum Error {
     NoMemory,
     #[error("Wrong proxy type")]
     WrongProxyType,
+    #[error("SPA error: {0}")]
+    SpaError(#[from] io::Error),
+    #[error("Server error: {0}")]
+    ServerError(#[from] ServerError),
+}
+
+/// An error reported by the server about one of the objects of the connection.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub struct ServerError {
+    id: u32,
+    seq: i32,
+    errno: i32,
+    message: String,
+}
+
+impl ServerError {
+    /// Build an error from the arguments of the `error` event of PipeWire, where `res` is a
+    /// negative errno.
+    pub(crate) fn new(id: u32, seq: i32, res: i32, message: &str) -> Self {
+        Self {
+            id,
+            seq,
+            errno: -res,
+            message: message.to_owned(),
+        }
+    }
+
+    /// The id of the object the error is about.
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// The sequence number of the request that caused the error.
+    pub fn seq(&self) -> i32 {
+        self.seq
+    }
+
+    /// The errno code of the error, as a positive number.
+    pub fn errno(&self) -> i32 {
+        self.errno
+    }
+
+    /// The kind of the errno code of the error.
+    pub fn kind(&self) -> io::ErrorKind {
+        io::Error::from_raw_os_error(self.errno).kind()
+    }
+
+    /// The message of the error.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl fmt::Display for ServerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "object {} (seq {}): {} ({})",
+            self.id,
+            self.seq,
+            self.message,
+            io::Error::from_raw_os_error(self.errno)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn server_error() {
+        let error = ServerError::new(42, 3, -libc::ENOENT, "no such node");
+
+        assert_eq!(error.id(), 42);
+        assert_eq!(error.seq(), 3);
+        assert_eq!(error.errno(), libc::ENOENT);
+        assert_eq!(error.kind(), io::ErrorKind::NotFound);
+        assert_eq!(error.message(), "no such node");
+        assert!(error
+            .to_string()
+            .starts_with("object 42 (seq 3): no such node"));
+    }
 }
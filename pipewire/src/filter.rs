@@ -0,0 +1,701 @@
+// Copyright 2021, Collabora Ltd.
+// SPDX-License-Identifier: MIT
+
+//! Filters, to write nodes with several input and output ports.
+//!
+//! A [`Filter`] is the PipeWire equivalent of a JACK client: ports are added to it with
+//! [`Filter::add_port`], each holding some user data of type `D`, and the `process` callback is
+//! called once per graph cycle to read the samples of the input ports and write those of the
+//! output ports with [`Port::dsp_buffer`].
+
+use bitflags::bitflags;
+use libc::{c_char, c_void};
+use std::{
+    borrow::Cow,
+    cell::{Cell, Ref, RefCell, RefMut},
+    ffi::{CStr, CString},
+    fmt, io,
+    marker::PhantomData,
+    mem,
+    pin::Pin,
+    ptr::{self, NonNull},
+    slice,
+};
+
+use crate::{core_::Core, error::Error, properties::Properties};
+use spa::{io::Position, pod::Pod, Direction, SpaResult};
+
+/// A filter, created from a [`Core`] it cannot outlive.
+///
+/// Every port of the filter holds a value of type `D`, such as the channel it carries.
+pub struct Filter<'c, D> {
+    ptr: NonNull<pw_sys::pw_filter>,
+    ports: RefCell<Vec<Port<D>>>,
+    next_port_id: Cell<u32>,
+    core: PhantomData<&'c Core>,
+}
+
+impl<'c, D> Filter<'c, D> {
+    /// Create a new, unconnected, filter without any port.
+    ///
+    /// # Panics
+    /// If `name` contains a null byte.
+    pub fn new(core: &'c Core, name: &str, properties: Properties) -> Result<Self, Error> {
+        let name = CString::new(name).expect("Null byte in name parameter");
+
+        let filter =
+            unsafe { pw_sys::pw_filter_new(core.as_ptr(), name.as_ptr(), properties.into_raw()) };
+
+        NonNull::new(filter)
+            .map(|ptr| Filter {
+                ptr,
+                ports: RefCell::new(Vec::new()),
+                next_port_id: Cell::new(0),
+                core: PhantomData,
+            })
+            .ok_or(Error::CreationFailed)
+    }
+
+    pub fn as_ptr(&self) -> *mut pw_sys::pw_filter {
+        self.ptr.as_ptr()
+    }
+
+    #[must_use]
+    pub fn add_listener_local(&self) -> ListenerLocalBuilder<'_, 'c, D> {
+        ListenerLocalBuilder {
+            filter: self,
+            cbs: ListenerLocalCallbacks::new(),
+        }
+    }
+
+    /// Add a port to the filter, holding `data`.
+    ///
+    /// # Parameters
+    /// - `direction`: whether the port receives ([`Direction::Input`]) or sends
+    ///   ([`Direction::Output`]) data
+    /// - `flags`: how the port should handle buffers
+    /// - `properties`: the properties of the port, such as `format.dsp` and `port.name`
+    /// - `params`: the params the port supports
+    ///
+    /// # Panics
+    /// If called while the ports are borrowed, such as from the `param_changed` callback.
+    pub fn add_port(
+        &self,
+        direction: Direction,
+        flags: PortFlags,
+        properties: Properties,
+        params: &[Pod],
+        data: D,
+    ) -> Result<PortId, Error> {
+        let mut params: Vec<*const spa_sys::spa_pod> = params.iter().map(Pod::as_ptr).collect();
+
+        // The data is kept on the Rust side, so no memory needs to be allocated for it. The
+        // returned pointer is only used to identify the port.
+        let port_data = unsafe {
+            pw_sys::pw_filter_add_port(
+                self.as_ptr(),
+                direction.as_raw(),
+                flags.bits(),
+                0,
+                properties.into_raw(),
+                params.as_mut_ptr(),
+                params.len() as u32,
+            )
+        };
+        let port_data = NonNull::new(port_data).ok_or(Error::CreationFailed)?;
+
+        let id = PortId(self.next_port_id.get());
+        self.next_port_id.set(id.0 + 1);
+
+        self.ports.borrow_mut().push(Port {
+            id,
+            port_data,
+            direction,
+            data,
+        });
+
+        Ok(id)
+    }
+
+    /// Remove a port from the filter, returning its data.
+    ///
+    /// Returns `None` if the filter has no such port.
+    ///
+    /// # Panics
+    /// If called while the ports are borrowed, such as from the `param_changed` callback.
+    pub fn remove_port(&self, id: PortId) -> Option<D> {
+        let mut ports = self.ports.borrow_mut();
+        let index = ports.iter().position(|port| port.id == id)?;
+        let port = ports.remove(index);
+
+        unsafe { pw_sys::pw_filter_remove_port(port.port_data.as_ptr()) };
+
+        Some(port.data)
+    }
+
+    /// The ports of the filter.
+    ///
+    /// # Panics
+    /// If the ports are mutably borrowed.
+    pub fn ports(&self) -> Ref<'_, [Port<D>]> {
+        Ref::map(self.ports.borrow(), Vec::as_slice)
+    }
+
+    /// The ports of the filter, to access their buffers from the `process` callback.
+    ///
+    /// # Panics
+    /// If the ports are already borrowed.
+    pub fn ports_mut(&self) -> RefMut<'_, [Port<D>]> {
+        RefMut::map(self.ports.borrow_mut(), Vec::as_mut_slice)
+    }
+
+    /// Connect the filter to the graph.
+    ///
+    /// # Parameters
+    /// - `flags`: how the filter should behave
+    /// - `params`: the params of the node of the filter
+    pub fn connect(&self, flags: FilterFlags, params: &[Pod]) -> Result<(), Error> {
+        let mut params: Vec<*const spa_sys::spa_pod> = params.iter().map(Pod::as_ptr).collect();
+
+        let res = unsafe {
+            pw_sys::pw_filter_connect(
+                self.as_ptr(),
+                flags.bits(),
+                params.as_mut_ptr(),
+                params.len() as u32,
+            )
+        };
+
+        SpaResult::from_raw(res).into_sync_result()?;
+        Ok(())
+    }
+
+    /// Disconnect the filter from the graph, it can be connected again afterwards.
+    pub fn disconnect(&self) -> Result<(), Error> {
+        let res = unsafe { pw_sys::pw_filter_disconnect(self.as_ptr()) };
+
+        SpaResult::from_raw(res).into_sync_result()?;
+        Ok(())
+    }
+
+    /// Update the params of a port, or of the node of the filter if `port` is `None`.
+    ///
+    /// Returns an error if the filter has no such port.
+    pub fn update_params(&self, port: Option<PortId>, params: &[Pod]) -> Result<(), Error> {
+        let port_data = match port {
+            Some(id) => self
+                .ports()
+                .iter()
+                .find(|port| port.id == id)
+                .map(|port| port.port_data.as_ptr())
+                .ok_or_else(|| io::Error::from_raw_os_error(libc::ENOENT))?,
+            None => ptr::null_mut(),
+        };
+        let mut params: Vec<*const spa_sys::spa_pod> = params.iter().map(Pod::as_ptr).collect();
+
+        let res = unsafe {
+            pw_sys::pw_filter_update_params(
+                self.as_ptr(),
+                port_data,
+                params.as_mut_ptr(),
+                params.len() as u32,
+            )
+        };
+
+        SpaResult::from_raw(res).into_sync_result()?;
+        Ok(())
+    }
+
+    /// Activate or deactivate the filter.
+    pub fn set_active(&self, active: bool) -> Result<(), Error> {
+        let res = unsafe { pw_sys::pw_filter_set_active(self.as_ptr(), active) };
+
+        SpaResult::from_raw(res).into_sync_result()?;
+        Ok(())
+    }
+
+    /// Flush the queued buffers.
+    ///
+    /// If `drain` is `true`, the `drained` callback is called once all the buffers are played.
+    pub fn flush(&self, drain: bool) -> Result<(), Error> {
+        let res = unsafe { pw_sys::pw_filter_flush(self.as_ptr(), drain) };
+
+        SpaResult::from_raw(res).into_sync_result()?;
+        Ok(())
+    }
+
+    pub fn name(&self) -> &str {
+        unsafe {
+            CStr::from_ptr(pw_sys::pw_filter_get_name(self.as_ptr()))
+                .to_str()
+                .unwrap()
+        }
+    }
+
+    pub fn state(&self) -> FilterState<'_> {
+        let mut error: *const c_char = ptr::null();
+        let state = unsafe { pw_sys::pw_filter_get_state(self.as_ptr(), &mut error) };
+
+        unsafe { FilterState::from_raw(state, error) }
+    }
+
+    /// The id of the node of the filter, only valid once the filter is connected.
+    pub fn node_id(&self) -> u32 {
+        unsafe { pw_sys::pw_filter_get_node_id(self.as_ptr()) }
+    }
+
+    fn find_port(&self, port_data: *mut c_void) -> Option<Ref<'_, Port<D>>> {
+        let ports = self.ports.borrow();
+        let index = ports
+            .iter()
+            .position(|port| port.port_data.as_ptr() == port_data)?;
+
+        Some(Ref::map(ports, |ports| &ports[index]))
+    }
+}
+
+impl<D> fmt::Debug for Filter<'_, D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Filter")
+            .field("name", &self.name())
+            .field("state", &self.state())
+            .field("node-id", &self.node_id())
+            .finish()
+    }
+}
+
+impl<D> Drop for Filter<'_, D> {
+    fn drop(&mut self) {
+        // Also frees the ports, their Rust data is dropped with `self.ports`.
+        unsafe { pw_sys::pw_filter_destroy(self.as_ptr()) }
+    }
+}
+
+/// Identifies a port of a [`Filter`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PortId(u32);
+
+/// A port of a [`Filter`].
+pub struct Port<D> {
+    id: PortId,
+    port_data: NonNull<c_void>,
+    direction: Direction,
+    data: D,
+}
+
+impl<D> Port<D> {
+    pub fn id(&self) -> PortId {
+        self.id
+    }
+
+    pub fn direction(&self) -> Direction {
+        self.direction
+    }
+
+    /// The data the port was added with.
+    pub fn data(&self) -> &D {
+        &self.data
+    }
+
+    pub fn data_mut(&mut self) -> &mut D {
+        &mut self.data
+    }
+
+    /// Get the buffer of `n_samples` 32 bits float samples of a DSP port, to read from an input
+    /// port or to fill for an output port.
+    ///
+    /// This is meant to be called once per cycle from the `process` callback, usually with the
+    /// duration of the [`Clock`](spa::io::Clock) as `n_samples`. Returns `None` if there is no
+    /// buffer available.
+    ///
+    /// # Safety
+    /// PipeWire does not check the size of the buffer, so `n_samples` must not be above the
+    /// maximum duration of a cycle, which is the size the buffers of DSP ports are allocated for.
+    pub unsafe fn dsp_buffer(&mut self, n_samples: u32) -> Option<&mut [f32]> {
+        let buffer = pw_sys::pw_filter_get_dsp_buffer(self.port_data.as_ptr(), n_samples);
+        if buffer.is_null() {
+            None
+        } else {
+            Some(slice::from_raw_parts_mut(buffer.cast(), n_samples as usize))
+        }
+    }
+
+    /// Get a buffer of the port, if one is available.
+    ///
+    /// # Safety
+    /// The buffer must be handed back with [`queue_raw_buffer`](Self::queue_raw_buffer) and must not
+    /// be used afterwards.
+    pub unsafe fn dequeue_raw_buffer(&mut self) -> *mut pw_sys::pw_buffer {
+        pw_sys::pw_filter_dequeue_buffer(self.port_data.as_ptr())
+    }
+
+    /// Hand a buffer obtained with [`dequeue_raw_buffer`](Self::dequeue_raw_buffer) back to
+    /// the port.
+    ///
+    /// # Safety
+    /// `buffer` must have been dequeued from this port and not queued since.
+    pub unsafe fn queue_raw_buffer(&mut self, buffer: *mut pw_sys::pw_buffer) -> Result<(), Error> {
+        let res = pw_sys::pw_filter_queue_buffer(self.port_data.as_ptr(), buffer);
+
+        SpaResult::from_raw(res).into_sync_result()?;
+        Ok(())
+    }
+}
+
+impl<D: fmt::Debug> fmt::Debug for Port<D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Port")
+            .field("id", &self.id)
+            .field("direction", &self.direction)
+            .field("data", &self.data)
+            .finish()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FilterState<'a> {
+    /// The filter is in error, with the error message, which is empty if there is none.
+    Error(Cow<'a, str>),
+    Unconnected,
+    Connecting,
+    Paused,
+    Streaming,
+    /// A state unknown to this version of the bindings.
+    Unknown(u32),
+}
+
+impl<'a> FilterState<'a> {
+    /// # Safety
+    /// `error` must be null or point to a string valid for `'a`.
+    unsafe fn from_raw(state: pw_sys::pw_filter_state, error: *const c_char) -> Self {
+        match state {
+            pw_sys::pw_filter_state_PW_FILTER_STATE_ERROR => {
+                let error = if error.is_null() {
+                    Cow::Borrowed("")
+                } else {
+                    CStr::from_ptr(error).to_string_lossy()
+                };
+                FilterState::Error(error)
+            }
+            pw_sys::pw_filter_state_PW_FILTER_STATE_UNCONNECTED => FilterState::Unconnected,
+            pw_sys::pw_filter_state_PW_FILTER_STATE_CONNECTING => FilterState::Connecting,
+            pw_sys::pw_filter_state_PW_FILTER_STATE_PAUSED => FilterState::Paused,
+            pw_sys::pw_filter_state_PW_FILTER_STATE_STREAMING => FilterState::Streaming,
+            _ => FilterState::Unknown(state),
+        }
+    }
+}
+
+bitflags! {
+    pub struct FilterFlags: u32 {
+        /// Start the filter inactive, [`Filter::set_active`] needs to be called to start it.
+        const INACTIVE = pw_sys::pw_filter_flags_PW_FILTER_FLAG_INACTIVE;
+        /// Be a driver of the graph.
+        const DRIVER = pw_sys::pw_filter_flags_PW_FILTER_FLAG_DRIVER;
+    }
+}
+
+bitflags! {
+    pub struct PortFlags: u32 {
+        /// Map the buffers in memory, so their data can be accessed directly.
+        const MAP_BUFFERS = pw_sys::pw_filter_port_flags_PW_FILTER_PORT_FLAG_MAP_BUFFERS;
+        /// Let the application allocate the buffer memory.
+        const ALLOC_BUFFERS = pw_sys::pw_filter_port_flags_PW_FILTER_PORT_FLAG_ALLOC_BUFFERS;
+    }
+}
+
+#[allow(clippy::type_complexity)]
+struct ListenerLocalCallbacks<'c, D> {
+    filter: *const Filter<'c, D>,
+    state_changed: Option<Box<dyn Fn(&Filter<D>, FilterState, FilterState)>>,
+    param_changed: Option<Box<dyn Fn(&Filter<D>, Option<&Port<D>>, u32, Option<&Pod>)>>,
+    process: Option<Box<dyn Fn(&Filter<D>, Option<&Position>)>>,
+    drained: Option<Box<dyn Fn(&Filter<D>)>>,
+}
+
+impl<'c, D> ListenerLocalCallbacks<'c, D> {
+    fn new() -> Self {
+        Self {
+            filter: ptr::null(),
+            state_changed: None,
+            param_changed: None,
+            process: None,
+            drained: None,
+        }
+    }
+
+    /// Borrow the filter the callbacks are registered on.
+    ///
+    /// # Safety
+    /// Must only be called from a filter event, while the listener is registered.
+    unsafe fn filter(&self) -> &Filter<'c, D> {
+        &*self.filter
+    }
+}
+
+pub struct ListenerLocalBuilder<'f, 'c, D> {
+    filter: &'f Filter<'c, D>,
+    cbs: ListenerLocalCallbacks<'c, D>,
+}
+
+/// A listener on the events of a [`Filter`], which it cannot outlive.
+pub struct FilterListener<'f, 'c, D> {
+    // Need to stay allocated while the listener is registered
+    #[allow(dead_code)]
+    events: Pin<Box<pw_sys::pw_filter_events>>,
+    listener: Pin<Box<spa_sys::spa_hook>>,
+    #[allow(dead_code)]
+    data: Box<ListenerLocalCallbacks<'c, D>>,
+    filter: PhantomData<&'f ()>,
+}
+
+impl<D> FilterListener<'_, '_, D> {
+    pub fn unregister(self) {
+        // Consuming the listener will call drop()
+    }
+}
+
+impl<D> Drop for FilterListener<'_, '_, D> {
+    fn drop(&mut self) {
+        spa::hook::remove(*self.listener);
+    }
+}
+
+impl<'f, 'c, D> ListenerLocalBuilder<'f, 'c, D> {
+    /// Called with the old and new state when the state of the filter changes.
+    #[must_use]
+    pub fn state_changed<F>(mut self, state_changed: F) -> Self
+    where
+        F: Fn(&Filter<D>, FilterState, FilterState) + 'static,
+    {
+        self.cbs.state_changed = Some(Box::new(state_changed));
+        self
+    }
+
+    /// Called with the port, or `None` for the node of the filter, the param id and the new
+    /// value when a param changes, such as the negotiated `Format` of a port.
+    ///
+    /// The ports are borrowed during the call, so they cannot be added or removed from it.
+    #[must_use]
+    pub fn param_changed<F>(mut self, param_changed: F) -> Self
+    where
+        F: Fn(&Filter<D>, Option<&Port<D>>, u32, Option<&Pod>) + 'static,
+    {
+        self.cbs.param_changed = Some(Box::new(param_changed));
+        self
+    }
+
+    /// Called on each cycle of the graph with its position, to process the buffers of the ports.
+    ///
+    /// This is called from the thread of the loop, as the ports are not shared with the
+    /// realtime thread.
+    #[must_use]
+    pub fn process<F>(mut self, process: F) -> Self
+    where
+        F: Fn(&Filter<D>, Option<&Position>) + 'static,
+    {
+        self.cbs.process = Some(Box::new(process));
+        self
+    }
+
+    /// Called once all the buffers are played after [`Filter::flush`] was called with
+    /// `drain` set.
+    #[must_use]
+    pub fn drained<F>(mut self, drained: F) -> Self
+    where
+        F: Fn(&Filter<D>) + 'static,
+    {
+        self.cbs.drained = Some(Box::new(drained));
+        self
+    }
+
+    #[must_use]
+    pub fn register(mut self) -> FilterListener<'f, 'c, D> {
+        unsafe extern "C" fn filter_events_state_changed<D>(
+            data: *mut c_void,
+            old: pw_sys::pw_filter_state,
+            state: pw_sys::pw_filter_state,
+            error: *const c_char,
+        ) {
+            let callbacks = (data as *mut ListenerLocalCallbacks<D>).as_ref().unwrap();
+            let filter = callbacks.filter();
+            let old = FilterState::from_raw(old, ptr::null());
+            let state = FilterState::from_raw(state, error);
+            callbacks.state_changed.as_ref().unwrap()(filter, old, state);
+        }
+
+        unsafe extern "C" fn filter_events_param_changed<D>(
+            data: *mut c_void,
+            port_data: *mut c_void,
+            id: u32,
+            param: *const spa_sys::spa_pod,
+        ) {
+            let callbacks = (data as *mut ListenerLocalCallbacks<D>).as_ref().unwrap();
+            let filter = callbacks.filter();
+            let port = if port_data.is_null() {
+                None
+            } else {
+                filter.find_port(port_data)
+            };
+            let param = if param.is_null() {
+                None
+            } else {
+                Some(Pod::from_raw(param))
+            };
+            callbacks.param_changed.as_ref().unwrap()(filter, port.as_deref(), id, param.as_ref());
+        }
+
+        unsafe extern "C" fn filter_events_process<D>(
+            data: *mut c_void,
+            position: *mut spa_sys::spa_io_position,
+        ) {
+            let callbacks = (data as *mut ListenerLocalCallbacks<D>).as_ref().unwrap();
+            let filter = callbacks.filter();
+            let position = Position::from_raw(position);
+            callbacks.process.as_ref().unwrap()(filter, position);
+        }
+
+        unsafe extern "C" fn filter_events_drained<D>(data: *mut c_void) {
+            let callbacks = (data as *mut ListenerLocalCallbacks<D>).as_ref().unwrap();
+            let filter = callbacks.filter();
+            callbacks.drained.as_ref().unwrap()(filter);
+        }
+
+        let e = unsafe {
+            let mut e: Pin<Box<pw_sys::pw_filter_events>> = Box::pin(mem::zeroed());
+            e.version = pw_sys::PW_VERSION_FILTER_EVENTS;
+
+            if self.cbs.state_changed.is_some() {
+                e.state_changed = Some(filter_events_state_changed::<D>);
+            }
+            if self.cbs.param_changed.is_some() {
+                e.param_changed = Some(filter_events_param_changed::<D>);
+            }
+            if self.cbs.process.is_some() {
+                e.process = Some(filter_events_process::<D>);
+            }
+            if self.cbs.drained.is_some() {
+                e.drained = Some(filter_events_drained::<D>);
+            }
+
+            e
+        };
+
+        self.cbs.filter = self.filter;
+
+        let (listener, data) = unsafe {
+            let data = Box::into_raw(Box::new(self.cbs));
+            let mut listener: Pin<Box<spa_sys::spa_hook>> = Box::pin(mem::zeroed());
+            let listener_ptr: *mut spa_sys::spa_hook = listener.as_mut().get_unchecked_mut();
+
+            pw_sys::pw_filter_add_listener(
+                self.filter.as_ptr(),
+                listener_ptr,
+                e.as_ref().get_ref(),
+                data as *mut _,
+            );
+
+            (listener, Box::from_raw(data))
+        };
+
+        FilterListener {
+            events: e,
+            listener,
+            data,
+            filter: PhantomData,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use super::*;
+    use crate::{
+        properties,
+        utils::{connect_to_private_server, iterate_until},
+        MainLoop,
+    };
+
+    #[test]
+    fn connect() {
+        crate::init();
+        let main_loop = MainLoop::new().unwrap();
+        let (_context, core) = connect_to_private_server(&main_loop);
+
+        let filter = Filter::new(
+            &core,
+            "test-filter",
+            properties! {
+                "media.type" => "Audio",
+                "media.category" => "Filter"
+            },
+        )
+        .unwrap();
+        assert_eq!(filter.name(), "test-filter");
+        assert_eq!(filter.state(), FilterState::Unconnected);
+
+        let input = filter
+            .add_port(
+                Direction::Input,
+                PortFlags::MAP_BUFFERS,
+                properties! {
+                    "format.dsp" => "32 bit float mono audio",
+                    "port.name" => "input"
+                },
+                &[],
+                "in",
+            )
+            .unwrap();
+        let output = filter
+            .add_port(
+                Direction::Output,
+                PortFlags::MAP_BUFFERS,
+                properties! {
+                    "format.dsp" => "32 bit float mono audio",
+                    "port.name" => "output"
+                },
+                &[],
+                "out",
+            )
+            .unwrap();
+        assert_ne!(input, output);
+        assert_eq!(
+            filter
+                .ports()
+                .iter()
+                .map(|port| (port.id(), port.direction(), *port.data()))
+                .collect::<Vec<_>>(),
+            vec![
+                (input, Direction::Input, "in"),
+                (output, Direction::Output, "out")
+            ]
+        );
+
+        let states = Rc::new(RefCell::new(Vec::new()));
+        let _listener = filter
+            .add_listener_local()
+            .state_changed({
+                let states = states.clone();
+                move |_, _, state| states.borrow_mut().push(format!("{:?}", state))
+            })
+            .register();
+
+        filter.connect(FilterFlags::INACTIVE, &[]).unwrap();
+        iterate_until(&main_loop, || match filter.state() {
+            FilterState::Error(error) => panic!("filter error: {}", error),
+            FilterState::Connecting | FilterState::Unconnected => false,
+            _ => true,
+        });
+        assert_eq!(filter.state(), FilterState::Paused);
+        assert!(states.borrow().contains(&"Paused".to_string()));
+
+        assert_eq!(filter.remove_port(input), Some("in"));
+        assert_eq!(filter.remove_port(input), None);
+        assert_eq!(filter.ports().len(), 1);
+
+        filter.disconnect().unwrap();
+        assert_eq!(filter.state(), FilterState::Unconnected);
+    }
+}
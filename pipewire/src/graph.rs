@@ -0,0 +1,302 @@
+// Copyright 2021, Collabora Ltd.
+// SPDX-License-Identifier: MIT
+
+//! Queries over the objects of an [`ObjectManager`](crate::object_manager::ObjectManager).
+//!
+//! # Examples
+//! ```no_run
+//! use pipewire::{
+//!     object_manager::{Interest, ObjectManager},
+//!     port::Direction,
+//!     types::ObjectType,
+//!     Context, MainLoop,
+//! };
+//!
+//! pipewire::init();
+//! let main_loop = MainLoop::new().unwrap();
+//! let context = Context::new(&main_loop).unwrap();
+//! let core = context.connect(None).unwrap();
+//! let object_manager = ObjectManager::new(
+//!     &core,
+//!     vec![
+//!         Interest::new(ObjectType::Node),
+//!         Interest::new(ObjectType::Port),
+//!         Interest::new(ObjectType::Link),
+//!     ],
+//! );
+//!
+//! // Run the loop until the objects are known...
+//!
+//! let graph = object_manager.graph();
+//! for sink in graph.nodes().with_media_class("Audio/Sink") {
+//!     let ports = graph.ports_of(sink.id(), Direction::Input).count();
+//!     println!("sink {} has {} input ports", sink.id(), ports);
+//! }
+//! ```
+
+use std::{collections::HashMap, fmt, rc::Rc};
+
+use crate::{object_manager::Object, port::Direction, types::ObjectType};
+use spa::dict::ReadableDict;
+
+const KEY_OBJECT_SERIAL: &str = "object.serial";
+const KEY_MEDIA_CLASS: &str = "media.class";
+const KEY_NODE_NAME: &str = "node.name";
+const KEY_NODE_ID: &str = "node.id";
+const KEY_PORT_DIRECTION: &str = "port.direction";
+const KEY_LINK_OUTPUT_NODE: &str = "link.output.node";
+const KEY_LINK_INPUT_NODE: &str = "link.input.node";
+
+/// The value of the property `key` of `object`, if any.
+fn prop<'a>(object: &'a Object, key: &str) -> Option<&'a str> {
+    object.props().and_then(|props| props.get(key))
+}
+
+/// The value of the property `key` of `object` parsed as an id, if any.
+fn prop_id(object: &Object, key: &str) -> Option<u32> {
+    prop(object, key).and_then(|value| value.parse().ok())
+}
+
+/// A view of a snapshot of the objects of an object manager, returned by
+/// [`ObjectManager::graph`](crate::object_manager::ObjectManager::graph).
+///
+/// The view doesn't change when the objects change, get a new one to query the latest objects.
+pub struct Graph {
+    objects: HashMap<u32, Rc<Object>>,
+}
+
+impl Graph {
+    pub(crate) fn new(objects: HashMap<u32, Rc<Object>>) -> Self {
+        Self { objects }
+    }
+
+    /// The object with the id `id`.
+    pub fn get(&self, id: u32) -> Option<&Object> {
+        self.objects.get(&id)
+    }
+
+    /// All the objects.
+    pub fn objects(&self) -> Query<'_> {
+        Query {
+            iter: Box::new(self.objects.values().map(|object| &**object)),
+        }
+    }
+
+    /// The objects of type `type_`.
+    pub fn objects_of_type(&self, type_: ObjectType) -> Query<'_> {
+        self.objects()
+            .filter(move |object| *object.type_() == type_)
+    }
+
+    pub fn nodes(&self) -> Query<'_> {
+        self.objects_of_type(ObjectType::Node)
+    }
+
+    pub fn ports(&self) -> Query<'_> {
+        self.objects_of_type(ObjectType::Port)
+    }
+
+    pub fn links(&self) -> Query<'_> {
+        self.objects_of_type(ObjectType::Link)
+    }
+
+    /// The ports of the node `node_id` in the direction `direction`.
+    pub fn ports_of(&self, node_id: u32, direction: Direction) -> Query<'_> {
+        let direction = match direction {
+            Direction::Input => "in",
+            Direction::Output => "out",
+        };
+
+        self.ports()
+            .filter(move |port| prop_id(port, KEY_NODE_ID) == Some(node_id))
+            .with_property(KEY_PORT_DIRECTION, direction)
+    }
+
+    /// The links from the output ports of the node `output_node` to the input ports of the node
+    /// `input_node`.
+    pub fn links_between(&self, output_node: u32, input_node: u32) -> Query<'_> {
+        self.links().filter(move |link| {
+            prop_id(link, KEY_LINK_OUTPUT_NODE) == Some(output_node)
+                && prop_id(link, KEY_LINK_INPUT_NODE) == Some(input_node)
+        })
+    }
+
+    /// The id of the object with the serial number `serial`, which unlike its id is never
+    /// reused by the server.
+    pub fn id_of_serial(&self, serial: u64) -> Option<u32> {
+        self.objects()
+            .find(|object| {
+                prop(object, KEY_OBJECT_SERIAL).and_then(|value| value.parse().ok()) == Some(serial)
+            })
+            .map(Object::id)
+    }
+
+    /// The id of the node named `name`.
+    pub fn id_of_node_name(&self, name: &str) -> Option<u32> {
+        self.nodes()
+            .with_property(KEY_NODE_NAME, name)
+            .next()
+            .map(Object::id)
+    }
+}
+
+impl fmt::Debug for Graph {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Graph")
+            .field("objects", &self.objects)
+            .finish()
+    }
+}
+
+/// An iterator over the objects of a [`Graph`] matching some conditions.
+pub struct Query<'a> {
+    iter: Box<dyn Iterator<Item = &'a Object> + 'a>,
+}
+
+impl<'a> Query<'a> {
+    /// Only keep the objects for which `predicate` returns `true`.
+    #[must_use]
+    pub fn filter<P>(self, predicate: P) -> Self
+    where
+        P: Fn(&Object) -> bool + 'a,
+    {
+        Self {
+            iter: Box::new(self.iter.filter(move |object| predicate(*object))),
+        }
+    }
+
+    /// Only keep the objects whose property `key` is `value`.
+    #[must_use]
+    pub fn with_property(self, key: &str, value: &str) -> Self {
+        let key = key.to_owned();
+        let value = value.to_owned();
+
+        self.filter(move |object| prop(object, &key) == Some(value.as_str()))
+    }
+
+    /// Only keep the objects of the media class `media_class`, such as `Audio/Sink`.
+    #[must_use]
+    pub fn with_media_class(self, media_class: &str) -> Self {
+        self.with_property(KEY_MEDIA_CLASS, media_class)
+    }
+}
+
+impl<'a> Iterator for Query<'a> {
+    type Item = &'a Object;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.next()
+    }
+}
+
+impl fmt::Debug for Query<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Query").finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{registry::GlobalObject, Properties};
+
+    fn object(id: u32, type_: ObjectType, props: Properties) -> (u32, Rc<Object>) {
+        let global = GlobalObject::for_tests(id, type_, props);
+
+        (id, Rc::new(Object::from_global(global)))
+    }
+
+    fn graph() -> Graph {
+        Graph::new(
+            vec![
+                object(
+                    30,
+                    ObjectType::Node,
+                    crate::properties! {
+                        "media.class" => "Audio/Sink",
+                        "node.name" => "sink",
+                        "object.serial" => "130"
+                    },
+                ),
+                object(
+                    31,
+                    ObjectType::Node,
+                    crate::properties! {
+                        "media.class" => "Audio/Source",
+                        "node.name" => "source",
+                        "object.serial" => "131"
+                    },
+                ),
+                object(
+                    40,
+                    ObjectType::Port,
+                    crate::properties! {
+                        "node.id" => "30",
+                        "port.direction" => "in"
+                    },
+                ),
+                object(
+                    41,
+                    ObjectType::Port,
+                    crate::properties! {
+                        "node.id" => "30",
+                        "port.direction" => "out"
+                    },
+                ),
+                object(
+                    42,
+                    ObjectType::Port,
+                    crate::properties! {
+                        "node.id" => "31",
+                        "port.direction" => "out"
+                    },
+                ),
+                object(
+                    50,
+                    ObjectType::Link,
+                    crate::properties! {
+                        "link.output.node" => "31",
+                        "link.input.node" => "30"
+                    },
+                ),
+            ]
+            .into_iter()
+            .collect(),
+        )
+    }
+
+    fn ids(query: Query) -> Vec<u32> {
+        let mut ids: Vec<u32> = query.map(Object::id).collect();
+        ids.sort_unstable();
+        ids
+    }
+
+    #[test]
+    fn nodes() {
+        let graph = graph();
+
+        assert_eq!(ids(graph.nodes()), [30, 31]);
+        assert_eq!(ids(graph.nodes().with_media_class("Audio/Sink")), [30]);
+        assert!(ids(graph.nodes().with_media_class("Video/Sink")).is_empty());
+    }
+
+    #[test]
+    fn ports_and_links() {
+        let graph = graph();
+
+        assert_eq!(ids(graph.ports_of(30, Direction::Input)), [40]);
+        assert_eq!(ids(graph.ports_of(30, Direction::Output)), [41]);
+        assert_eq!(ids(graph.links_between(31, 30)), [50]);
+        assert!(ids(graph.links_between(30, 31)).is_empty());
+    }
+
+    #[test]
+    fn resolve_ids() {
+        let graph = graph();
+
+        assert_eq!(graph.id_of_serial(131), Some(31));
+        assert_eq!(graph.id_of_serial(132), None);
+        assert_eq!(graph.id_of_node_name("sink"), Some(30));
+        assert_eq!(graph.id_of_node_name("other"), None);
+    }
+}
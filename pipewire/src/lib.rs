@@ -3,31 +3,45 @@
 
 use std::ptr;
 
+#[cfg(feature = "async")]
+pub mod async_;
+#[cfg(feature = "calloop")]
+pub mod calloop;
+pub mod channel;
 mod error;
 pub use error::*;
 pub mod loop_;
 pub use loop_::*;
 mod main_loop;
 pub use main_loop::*;
+mod thread_loop;
+pub use thread_loop::*;
 mod context;
 pub use context::*;
 mod core_;
 pub use core_::*;
 mod properties;
 pub use properties::*;
+pub mod filter;
+pub mod graph;
 pub mod link;
+pub mod module;
 pub mod node;
+pub mod object_manager;
 pub mod port;
 pub mod proxy;
 pub mod registry;
 pub use spa;
+pub mod stream;
+#[cfg(feature = "tokio")]
+pub mod tokio;
 pub mod types;
 mod utils;
 
 // Re-export all the traits in a prelude module, so that applications
 // can always "use pipewire::prelude::*" without getting conflicts
 pub mod prelude {
-    pub use crate::loop_::Loop;
+    pub use crate::loop_::{LocalLoop, Loop};
 }
 
 /// Initialize PipeWire
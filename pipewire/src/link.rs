@@ -1,11 +1,18 @@
 use std::{
+    any::Any,
     ffi::{c_void, CStr},
     fmt, mem,
     pin::Pin,
+    ptr,
+    rc::Rc,
 };
 
 use bitflags::bitflags;
-use spa::dict::ForeignDict;
+use spa::{
+    dict::ForeignDict,
+    param::{FormatError, FormatInfo},
+    pod::Pod,
+};
 
 use crate::{
     proxy::{Listener, Proxy, ProxyT},
@@ -46,6 +53,13 @@ impl Link {
             cbs: ListenerLocalCallbacks::default(),
         }
     }
+
+    /// Like [`add_listener_local`](Self::add_listener_local), but the callbacks can be called
+    /// from another thread, such as the one of a [`ThreadLoop`](crate::ThreadLoop).
+    #[must_use]
+    pub fn add_listener(&self) -> LinkListenerBuilder {
+        LinkListenerBuilder(self.add_listener_local())
+    }
 }
 
 pub struct LinkListener {
@@ -55,6 +69,9 @@ pub struct LinkListener {
     listener: Pin<Box<spa_sys::spa_hook>>,
     #[allow(dead_code)]
     data: Box<ListenerLocalCallbacks>,
+    // Keep the proxy alive as long as the listener is registered on it
+    #[allow(dead_code)]
+    proxy: Rc<dyn Any>,
 }
 
 impl<'a> Listener for LinkListener {}
@@ -75,6 +92,24 @@ pub struct LinkListenerLocalBuilder<'link> {
     cbs: ListenerLocalCallbacks,
 }
 
+/// Builder of a listener whose callbacks can be called from another thread.
+pub struct LinkListenerBuilder<'a>(LinkListenerLocalBuilder<'a>);
+
+impl<'a> LinkListenerBuilder<'a> {
+    #[must_use]
+    pub fn info<F>(self, info: F) -> Self
+    where
+        F: Fn(&LinkInfo) + Send + 'static,
+    {
+        Self(self.0.info(info))
+    }
+
+    #[must_use]
+    pub fn register(self) -> LinkListener {
+        self.0.register()
+    }
+}
+
 impl<'a> LinkListenerLocalBuilder<'a> {
     #[must_use]
     pub fn info<F>(mut self, info: F) -> Self
@@ -129,29 +164,48 @@ impl<'a> LinkListenerLocalBuilder<'a> {
             events: e,
             listener,
             data,
+            proxy: self.link.proxy.keep_alive(),
         }
     }
 }
 
+/// The info of a link, which is either borrowed from the `info` callback, or a copy owning its
+/// data made with `clone`.
 pub struct LinkInfo {
     ptr: *const pw_sys::pw_link_info,
     props: Option<ForeignDict>,
+    owned: bool,
 }
 
 impl LinkInfo {
     fn new(ptr: *const pw_sys::pw_link_info) -> Self {
         assert!(!ptr.is_null());
-        let props_ptr = unsafe { (*ptr).props };
         Self {
             ptr,
-            props: if props_ptr.is_null() {
-                None
-            } else {
-                Some(unsafe { ForeignDict::from_ptr(props_ptr) })
-            },
+            props: Self::props_of(ptr),
+            owned: false,
+        }
+    }
+
+    fn props_of(ptr: *const pw_sys::pw_link_info) -> Option<ForeignDict> {
+        let props_ptr = unsafe { (*ptr).props };
+        if props_ptr.is_null() {
+            None
+        } else {
+            Some(unsafe { ForeignDict::from_ptr(props_ptr) })
         }
     }
 
+    /// Update this copy with the fields of `update` that changed, as received by the `info`
+    /// callback, to keep the latest info of the link.
+    pub(crate) fn update(&mut self, update: &Self) {
+        assert!(self.owned, "only a copy of the info can be updated");
+
+        unsafe { pw_sys::pw_link_info_update(self.ptr as *mut _, update.ptr) };
+        // The properties are copied again when they changed.
+        self.props = Self::props_of(self.ptr);
+    }
+
     pub fn id(&self) -> u32 {
         unsafe { (*self.ptr).id }
     }
@@ -194,13 +248,54 @@ impl LinkInfo {
         LinkChangeMask::from_bits(mask).expect("Invalid raw change_mask")
     }
 
-    // TODO: format (requires SPA Pod support before it can be implemented)
+    /// The format negotiated on the link, if any.
+    pub fn format(&self) -> Option<Pod<'_>> {
+        let format = unsafe { (*self.ptr).format };
+        if format.is_null() {
+            None
+        } else {
+            Some(unsafe { Pod::from_raw(format) })
+        }
+    }
+
+    /// Decode the format negotiated on the link, if any.
+    ///
+    /// Raw audio and video formats are fully decoded, other formats only report their media type.
+    pub fn format_info(&self) -> Option<Result<FormatInfo, FormatError>> {
+        self.format().map(|format| FormatInfo::parse(&format))
+    }
 
     pub fn props(&self) -> Option<&ForeignDict> {
         self.props.as_ref()
     }
 }
 
+impl Clone for LinkInfo {
+    fn clone(&self) -> Self {
+        unsafe {
+            // Copy all the fields, not only the ones that changed.
+            let mut info = *self.ptr;
+            info.change_mask = LinkChangeMask::all().bits();
+            let ptr = pw_sys::pw_link_info_update(ptr::null_mut(), &info);
+            (*ptr).change_mask = (*self.ptr).change_mask;
+
+            Self {
+                ptr,
+                props: Self::props_of(ptr),
+                owned: true,
+            }
+        }
+    }
+}
+
+impl Drop for LinkInfo {
+    fn drop(&mut self) {
+        if self.owned {
+            unsafe { pw_sys::pw_link_info_free(self.ptr as *mut _) }
+        }
+    }
+}
+
 bitflags! {
     pub struct LinkChangeMask: u64 {
         const STATE = pw_sys::PW_LINK_CHANGE_MASK_STATE as u64;
@@ -220,7 +315,7 @@ impl fmt::Debug for LinkInfo {
             .field("change-mask", &self.change_mask())
             .field("state", &self.state())
             .field("props", &self.props())
-            // TODO: .field("format", &self.format())
+            .field("format", &self.format())
             .finish()
     }
 }
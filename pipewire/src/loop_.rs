@@ -1,15 +1,195 @@
 // Copyright 2020, Collabora Ltd.
 // SPDX-License-Identifier: MIT
 
+use bitflags::bitflags;
 use libc::{c_int, c_void};
 use signal::Signal;
-use spa::spa_interface_call_method;
+use spa::{spa_interface_call_method, SpaResult};
+use std::{
+    io,
+    os::unix::io::{AsRawFd, RawFd},
+    ptr,
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
 
-use crate::utils::assert_main_thread;
+use crate::{error::Error, utils::assert_main_thread};
+
+/// Call a method of the `spa_loop_utils` of a loop.
+///
+/// This needs to be called from within an `unsafe` block.
+macro_rules! loop_utils_call {
+    ($loop_:expr, $method:ident, $( $arg:expr ),*) => {{
+        let mut iface = $loop_
+            .as_ptr()
+            .as_ref()
+            .unwrap()
+            .utils
+            .as_ref()
+            .unwrap()
+            .iface;
+
+        spa_interface_call_method!(
+            &mut iface as *mut spa_sys::spa_interface,
+            spa_sys::spa_loop_utils_methods,
+            $method,
+            $($arg),*
+        )
+    }};
+}
 
 pub trait Loop {
     fn as_ptr(&self) -> *mut pw_sys::pw_loop;
 
+    /// The file descriptor of the loop, which becomes readable when the loop has events to
+    /// dispatch with [`iterate`](Self::iterate).
+    ///
+    /// This allows driving the loop from another event loop instead of running it.
+    fn fd(&self) -> RawFd {
+        unsafe {
+            let mut iface = self
+                .as_ptr()
+                .as_ref()
+                .unwrap()
+                .control
+                .as_ref()
+                .unwrap()
+                .iface;
+
+            spa_interface_call_method!(
+                &mut iface as *mut spa_sys::spa_interface,
+                spa_sys::spa_loop_control_methods,
+                get_fd
+            )
+        }
+    }
+
+    /// Dispatch the pending events of the loop, waiting at most `timeout` for some to arrive.
+    ///
+    /// Returns the number of file descriptors that had events, so `0` once the loop is idle.
+    ///
+    /// The loop is entered for the duration of the call, so this must not be called while it is
+    /// running, such as from one of its callbacks or from another thread than the one running a
+    /// [`ThreadLoop`](crate::ThreadLoop).
+    fn iterate(&self, timeout: Duration) -> Result<i32, Error> {
+        let res = unsafe {
+            let mut iface = self
+                .as_ptr()
+                .as_ref()
+                .unwrap()
+                .control
+                .as_ref()
+                .unwrap()
+                .iface;
+            let iface = &mut iface as *mut spa_sys::spa_interface;
+
+            spa_interface_call_method!(iface, spa_sys::spa_loop_control_methods, enter);
+            let res = spa_interface_call_method!(
+                iface,
+                spa_sys::spa_loop_control_methods,
+                iterate,
+                timeout.as_millis().min(c_int::MAX as u128) as c_int
+            );
+            spa_interface_call_method!(iface, spa_sys::spa_loop_control_methods, leave);
+
+            res
+        };
+
+        let n_fds = SpaResult::from_raw(res).into_sync_result()?;
+        Ok(n_fds)
+    }
+
+    /// Call `func` from the thread of the loop and return its result, waiting for the loop to
+    /// run it.
+    ///
+    /// `func` is called right away if this is called from the thread of the loop. Otherwise,
+    /// the loop must be iterated by its thread, or this waits forever.
+    ///
+    /// Returns an `EIO` error if the loop accepted `func` without calling it. It may still be
+    /// called later, which is why it must be `'static`.
+    fn invoke<F, R>(&self, func: F) -> Result<R, Error>
+    where
+        F: FnOnce() -> R + Send + 'static,
+        R: Send + 'static,
+        Self: Sized;
+
+    /// Queue `func` to be called from the thread of the loop, without waiting for it.
+    ///
+    /// `func` is called right away if this is called from the thread of the loop.
+    fn invoke_nonblocking<F>(&self, func: F) -> Result<(), Error>
+    where
+        F: FnOnce() + Send + 'static,
+        Self: Sized;
+
+    /// Add a timer to the loop, calling `callback` with the number of expirations since the last
+    /// call each time it fires.
+    ///
+    /// The timer is disarmed until [`TimerSource::update_timer`] is called.
+    ///
+    /// The callback is called from the thread of the loop, so it must be [`Send`]. See
+    /// [`LocalLoop::add_timer_local`] for loops only used from their thread.
+    fn add_timer<F>(&self, callback: F) -> Result<TimerSource<Self>, Error>
+    where
+        F: Fn(u64) + Send + 'static,
+        Self: Sized,
+    {
+        add_timer_raw(self, callback)
+    }
+
+    /// Add an idle source to the loop, calling `callback` on each iteration of the loop while
+    /// it is enabled.
+    ///
+    /// The callback is called from the thread of the loop, so it must be [`Send`]. See
+    /// [`LocalLoop::add_idle_local`] for loops only used from their thread.
+    fn add_idle<F>(&self, enabled: bool, callback: F) -> Result<IdleSource<Self>, Error>
+    where
+        F: Fn() + Send + 'static,
+        Self: Sized,
+    {
+        add_idle_raw(self, enabled, callback)
+    }
+
+    /// Watch the file descriptor of `io`, calling `callback` with the events that occurred each
+    /// time one of those of `event_mask` does.
+    ///
+    /// `io` is kept alive as long as the source, so its file descriptor stays valid.
+    ///
+    /// The callback is called from the thread of the loop, so it and `io` must be [`Send`].
+    /// See [`LocalLoop::add_io_local`] for loops only used from their thread.
+    fn add_io<I, F>(
+        &self,
+        io: I,
+        event_mask: IoFlags,
+        callback: F,
+    ) -> Result<IoSource<I, Self>, Error>
+    where
+        I: AsRawFd + Send + 'static,
+        F: Fn(&mut I, IoFlags) + Send + 'static,
+        Self: Sized,
+    {
+        add_io_raw(self, io, event_mask, callback)
+    }
+
+    /// Add an event source to the loop, calling `callback` with the number of times the event
+    /// was signaled with [`EventSource::signal_event`] since the last call.
+    ///
+    /// The callback is called from the thread of the loop, so it must be [`Send`]. See
+    /// [`LocalLoop::add_event_local`] for loops only used from their thread.
+    fn add_event<F>(&self, callback: F) -> Result<EventSource<Self>, Error>
+    where
+        F: Fn(u64) + Send + 'static,
+        Self: Sized,
+    {
+        add_event_raw(self, callback)
+    }
+}
+
+/// A loop whose sources are only used from the thread running it, so their callbacks don't need
+/// to be [`Send`].
+///
+/// This is not implemented by [`ThreadLoop`](crate::ThreadLoop), as its thread runs the callbacks
+/// while the other threads can add and remove sources.
+pub trait LocalLoop: Loop {
     #[must_use]
     fn add_signal_local<F>(&self, signal: Signal, callback: F) -> Source<F, Self>
     where
@@ -57,6 +237,56 @@ pub trait Loop {
         }
     }
 
+    /// Add a timer to the loop, calling `callback` with the number of expirations since the last
+    /// call each time it fires.
+    ///
+    /// The timer is disarmed until [`TimerSource::update_timer`] is called.
+    fn add_timer_local<F>(&self, callback: F) -> Result<TimerSource<Self>, Error>
+    where
+        F: Fn(u64) + 'static,
+        Self: Sized,
+    {
+        add_timer_raw(self, callback)
+    }
+
+    /// Add an idle source to the loop, calling `callback` on each iteration of the loop while
+    /// it is enabled.
+    fn add_idle_local<F>(&self, enabled: bool, callback: F) -> Result<IdleSource<Self>, Error>
+    where
+        F: Fn() + 'static,
+        Self: Sized,
+    {
+        add_idle_raw(self, enabled, callback)
+    }
+
+    /// Watch the file descriptor of `io`, calling `callback` with the events that occurred each
+    /// time one of those of `event_mask` does.
+    ///
+    /// `io` is kept alive as long as the source, so its file descriptor stays valid.
+    fn add_io_local<I, F>(
+        &self,
+        io: I,
+        event_mask: IoFlags,
+        callback: F,
+    ) -> Result<IoSource<I, Self>, Error>
+    where
+        I: AsRawFd + 'static,
+        F: Fn(&mut I, IoFlags) + 'static,
+        Self: Sized,
+    {
+        add_io_raw(self, io, event_mask, callback)
+    }
+
+    /// Add an event source to the loop, calling `callback` with the number of times the event
+    /// was signaled with [`EventSource::signal_event`] since the last call.
+    fn add_event_local<F>(&self, callback: F) -> Result<EventSource<Self>, Error>
+    where
+        F: Fn(u64) + 'static,
+        Self: Sized,
+    {
+        add_event_raw(self, callback)
+    }
+
     fn destroy_source<F>(&self, source: &Source<F, Self>)
     where
         F: Fn() + 'static,
@@ -81,10 +311,11 @@ pub trait Loop {
         }
     }
 }
+
 pub struct Source<'a, F, L>
 where
     F: Fn() + 'static,
-    L: Loop,
+    L: LocalLoop,
 {
     source: *mut spa_sys::spa_source,
     loop_: &'a L,
@@ -96,9 +327,571 @@ where
 impl<'a, F, L> Drop for Source<'a, F, L>
 where
     F: Fn() + 'static,
-    L: Loop,
+    L: LocalLoop,
 {
     fn drop(&mut self) {
         self.loop_.destroy_source(&self)
     }
 }
+
+/// Call `spa_loop_invoke()` on `loop_`, without any data.
+unsafe fn loop_invoke(
+    loop_: *mut pw_sys::pw_loop,
+    func: spa_sys::spa_invoke_func_t,
+    block: bool,
+    user_data: *mut c_void,
+) -> c_int {
+    let mut iface = loop_.as_ref().unwrap().loop_.as_ref().unwrap().iface;
+
+    spa_interface_call_method!(
+        &mut iface as *mut spa_sys::spa_interface,
+        spa_sys::spa_loop_methods,
+        invoke,
+        func,
+        0,
+        ptr::null(),
+        0,
+        block,
+        user_data
+    )
+}
+
+/// Call `func` from the thread of `loop_` and return its result, waiting for the loop to run it.
+///
+/// # Safety
+/// `loop_` must be a valid loop. Unless this is called from the thread of the loop, no other
+/// thread may invoke on it at the same time, as older versions of PipeWire queue the invocations
+/// in a single producer ring buffer and share the eventfd acknowledging them.
+pub(crate) unsafe fn invoke_raw<F, R>(loop_: *mut pw_sys::pw_loop, func: F) -> Result<R, Error>
+where
+    F: FnOnce() -> R + Send + 'static,
+    R: Send + 'static,
+{
+    struct Invocation<F, R> {
+        func: Option<F>,
+        result: Option<R>,
+        /// Set once `result` is written, possibly from the thread of the loop.
+        done: AtomicBool,
+    }
+
+    unsafe extern "C" fn call_closure<F, R>(
+        _loop: *mut spa_sys::spa_loop,
+        _async: bool,
+        _seq: u32,
+        _data: *const c_void,
+        _size: usize,
+        user_data: *mut c_void,
+    ) -> c_int
+    where
+        F: FnOnce() -> R,
+    {
+        // Only borrow the fields, as the caller may check `done` meanwhile.
+        let invocation = user_data as *mut Invocation<F, R>;
+        let func = (*invocation).func.take().unwrap();
+        (*invocation).result = Some(func());
+        (*invocation).done.store(true, Ordering::Release);
+        0
+    }
+
+    let invocation = Box::into_raw(Box::new(Invocation {
+        func: Some(func),
+        result: None,
+        done: AtomicBool::new(false),
+    }));
+
+    let res = SpaResult::from_raw(loop_invoke(
+        loop_,
+        Some(call_closure::<F, R>),
+        true,
+        invocation as *mut c_void,
+    ));
+
+    if (*invocation).done.load(Ordering::Acquire) {
+        let invocation = Box::from_raw(invocation);
+        Ok(invocation.result.unwrap())
+    } else if res.is_err() {
+        // The invocation was not queued.
+        drop(Box::from_raw(invocation));
+        Err(res.unwrap_error().into())
+    } else {
+        // The loop accepted the invocation but did not run it yet. It may still run it later,
+        // so the invocation is leaked rather than freed under its feet.
+        Err(io::Error::from_raw_os_error(libc::EIO).into())
+    }
+}
+
+/// Queue `func` to be called from the thread of `loop_`.
+///
+/// # Safety
+/// `loop_` must be a valid loop. Unless this is called from the thread of the loop, no other
+/// thread may invoke on it at the same time, see [`invoke_raw`].
+pub(crate) unsafe fn invoke_nonblocking_raw<F>(
+    loop_: *mut pw_sys::pw_loop,
+    func: F,
+) -> Result<(), Error>
+where
+    F: FnOnce() + Send + 'static,
+{
+    unsafe extern "C" fn call_closure<F>(
+        _loop: *mut spa_sys::spa_loop,
+        _async: bool,
+        _seq: u32,
+        _data: *const c_void,
+        _size: usize,
+        user_data: *mut c_void,
+    ) -> c_int
+    where
+        F: FnOnce(),
+    {
+        let func = Box::from_raw(user_data as *mut F);
+        func();
+        0
+    }
+
+    let data = Box::into_raw(Box::new(func));
+    let res = SpaResult::from_raw(loop_invoke(
+        loop_,
+        Some(call_closure::<F>),
+        false,
+        data as *mut _,
+    ));
+
+    // The invocation is either done, queued and returned as async, or failed to be queued.
+    if res.is_err() {
+        drop(Box::from_raw(data));
+        return Err(res.unwrap_error().into());
+    }
+
+    Ok(())
+}
+
+/// Add a timer to `loop_`, see [`Loop::add_timer`].
+fn add_timer_raw<L, F>(loop_: &L, callback: F) -> Result<TimerSource<L>, Error>
+where
+    L: Loop,
+    F: Fn(u64) + 'static,
+{
+    unsafe extern "C" fn call_closure<F>(data: *mut c_void, expirations: u64)
+    where
+        F: Fn(u64),
+    {
+        let callback = (data as *mut F).as_ref().unwrap();
+        callback(expirations);
+    }
+
+    let data = Box::into_raw(Box::new(callback));
+
+    let (source, data) = unsafe {
+        let source = loop_utils_call!(loop_, add_timer, Some(call_closure::<F>), data as *mut _);
+
+        (source, Box::from_raw(data))
+    };
+    if source.is_null() {
+        return Err(io::Error::last_os_error().into());
+    }
+
+    Ok(TimerSource {
+        source,
+        loop_,
+        data,
+    })
+}
+
+/// Add an idle source to `loop_`, see [`Loop::add_idle`].
+fn add_idle_raw<L, F>(loop_: &L, enabled: bool, callback: F) -> Result<IdleSource<L>, Error>
+where
+    L: Loop,
+    F: Fn() + 'static,
+{
+    unsafe extern "C" fn call_closure<F>(data: *mut c_void)
+    where
+        F: Fn(),
+    {
+        let callback = (data as *mut F).as_ref().unwrap();
+        callback();
+    }
+
+    let data = Box::into_raw(Box::new(callback));
+
+    let (source, data) = unsafe {
+        let source = loop_utils_call!(
+            loop_,
+            add_idle,
+            enabled,
+            Some(call_closure::<F>),
+            data as *mut _
+        );
+
+        (source, Box::from_raw(data))
+    };
+    if source.is_null() {
+        return Err(io::Error::last_os_error().into());
+    }
+
+    Ok(IdleSource {
+        source,
+        loop_,
+        data,
+    })
+}
+
+/// Watch the file descriptor of `io` from `loop_`, see [`Loop::add_io`].
+fn add_io_raw<L, I, F>(
+    loop_: &L,
+    io: I,
+    event_mask: IoFlags,
+    callback: F,
+) -> Result<IoSource<I, L>, Error>
+where
+    L: Loop,
+    I: AsRawFd + 'static,
+    F: Fn(&mut I, IoFlags) + 'static,
+{
+    unsafe extern "C" fn call_closure<I, F>(data: *mut c_void, _fd: c_int, mask: u32)
+    where
+        F: Fn(&mut I, IoFlags),
+    {
+        let data = (data as *mut IoSourceData<I, F>).as_mut().unwrap();
+        (data.callback)(&mut data.io, IoFlags::from_bits_truncate(mask));
+    }
+
+    let fd = io.as_raw_fd();
+    let data = Box::into_raw(Box::new(IoSourceData { io, callback }));
+
+    let (source, data) = unsafe {
+        let source = loop_utils_call!(
+            loop_,
+            add_io,
+            fd,
+            event_mask.bits(),
+            // Never let the loop close the fd, it is owned by `io`.
+            false,
+            Some(call_closure::<I, F>),
+            data as *mut _
+        );
+
+        (source, Box::from_raw(data))
+    };
+    if source.is_null() {
+        return Err(io::Error::last_os_error().into());
+    }
+
+    Ok(IoSource {
+        source,
+        loop_,
+        data,
+    })
+}
+
+/// Add an event source to `loop_`, see [`Loop::add_event`].
+fn add_event_raw<L, F>(loop_: &L, callback: F) -> Result<EventSource<L>, Error>
+where
+    L: Loop,
+    F: Fn(u64) + 'static,
+{
+    unsafe extern "C" fn call_closure<F>(data: *mut c_void, count: u64)
+    where
+        F: Fn(u64),
+    {
+        let callback = (data as *mut F).as_ref().unwrap();
+        callback(count);
+    }
+
+    let data = Box::into_raw(Box::new(callback));
+
+    let (source, data) = unsafe {
+        let source = loop_utils_call!(loop_, add_event, Some(call_closure::<F>), data as *mut _);
+
+        (source, Box::from_raw(data))
+    };
+    if source.is_null() {
+        return Err(io::Error::last_os_error().into());
+    }
+
+    Ok(EventSource {
+        source,
+        loop_,
+        data,
+    })
+}
+
+/// Destroy a raw source of `loop_`.
+fn destroy_raw_source<L: Loop>(loop_: &L, source: *mut spa_sys::spa_source) {
+    if !source.is_null() {
+        unsafe { loop_utils_call!(loop_, destroy_source, source) }
+    }
+}
+
+fn to_timespec(duration: Duration) -> spa_sys::timespec {
+    spa_sys::timespec {
+        tv_sec: duration.as_secs() as _,
+        tv_nsec: duration.subsec_nanos() as _,
+    }
+}
+
+/// A timer of a loop, created with [`Loop::add_timer`] and removed when dropped.
+pub struct TimerSource<'l, L: Loop> {
+    source: *mut spa_sys::spa_source,
+    loop_: &'l L,
+    // Store data wrapper to prevent leak
+    #[allow(dead_code)]
+    data: Box<dyn Fn(u64)>,
+}
+
+impl<L: Loop> TimerSource<'_, L> {
+    /// Arm the timer to fire after `value`, then every `interval` if it is not `None`.
+    ///
+    /// A zero `value` disarms the timer.
+    pub fn update_timer(&self, value: Duration, interval: Option<Duration>) -> Result<(), Error> {
+        let mut value = to_timespec(value);
+        let mut interval = interval.map(to_timespec);
+        let interval_ptr = interval
+            .as_mut()
+            .map_or(ptr::null_mut(), |interval| interval as *mut _);
+
+        let res = unsafe {
+            loop_utils_call!(
+                self.loop_,
+                update_timer,
+                self.source,
+                &mut value,
+                interval_ptr,
+                false
+            )
+        };
+
+        SpaResult::from_raw(res).into_sync_result()?;
+        Ok(())
+    }
+}
+
+impl<L: Loop> Drop for TimerSource<'_, L> {
+    fn drop(&mut self) {
+        destroy_raw_source(self.loop_, self.source)
+    }
+}
+
+/// An idle source of a loop, created with [`Loop::add_idle`] and removed when dropped.
+pub struct IdleSource<'l, L: Loop> {
+    source: *mut spa_sys::spa_source,
+    loop_: &'l L,
+    // Store data wrapper to prevent leak
+    #[allow(dead_code)]
+    data: Box<dyn Fn()>,
+}
+
+impl<L: Loop> IdleSource<'_, L> {
+    /// Enable or disable calling the callback of the source.
+    pub fn enable(&self, enabled: bool) -> Result<(), Error> {
+        let res = unsafe { loop_utils_call!(self.loop_, enable_idle, self.source, enabled) };
+
+        SpaResult::from_raw(res).into_sync_result()?;
+        Ok(())
+    }
+}
+
+impl<L: Loop> Drop for IdleSource<'_, L> {
+    fn drop(&mut self) {
+        destroy_raw_source(self.loop_, self.source)
+    }
+}
+
+bitflags! {
+    /// The events watched by an [`IoSource`].
+    pub struct IoFlags: u32 {
+        /// The file descriptor can be read.
+        const IN = spa_sys::SPA_IO_IN;
+        /// The file descriptor can be written.
+        const OUT = spa_sys::SPA_IO_OUT;
+        /// An error occurred on the file descriptor.
+        const ERR = spa_sys::SPA_IO_ERR;
+        /// The other end of the file descriptor was closed.
+        const HUP = spa_sys::SPA_IO_HUP;
+    }
+}
+
+struct IoSourceData<I, F: ?Sized> {
+    io: I,
+    callback: F,
+}
+
+/// A file descriptor watched by a loop, created with [`Loop::add_io`] and removed when
+/// dropped.
+pub struct IoSource<'l, I, L: Loop> {
+    source: *mut spa_sys::spa_source,
+    loop_: &'l L,
+    // Store data wrapper to prevent leak
+    #[allow(dead_code)]
+    data: Box<IoSourceData<I, dyn Fn(&mut I, IoFlags)>>,
+}
+
+impl<I, L: Loop> IoSource<'_, I, L> {
+    /// Change the events that are watched.
+    pub fn update_io(&self, event_mask: IoFlags) -> Result<(), Error> {
+        let res =
+            unsafe { loop_utils_call!(self.loop_, update_io, self.source, event_mask.bits()) };
+
+        SpaResult::from_raw(res).into_sync_result()?;
+        Ok(())
+    }
+}
+
+impl<I, L: Loop> Drop for IoSource<'_, I, L> {
+    fn drop(&mut self) {
+        destroy_raw_source(self.loop_, self.source)
+    }
+}
+
+/// An event of a loop, created with [`Loop::add_event`] and removed when dropped.
+pub struct EventSource<'l, L: Loop> {
+    source: *mut spa_sys::spa_source,
+    loop_: &'l L,
+    // Store data wrapper to prevent leak
+    #[allow(dead_code)]
+    data: Box<dyn Fn(u64)>,
+}
+
+impl<L: Loop> EventSource<'_, L> {
+    /// Signal the event, so its callback is called from the loop.
+    pub fn signal_event(&self) -> Result<(), Error> {
+        let res = unsafe { loop_utils_call!(self.loop_, signal_event, self.source) };
+
+        SpaResult::from_raw(res).into_sync_result()?;
+        Ok(())
+    }
+
+    /// A handle to signal the event from any thread.
+    pub(crate) fn raw_signaler(&self) -> RawEventSignaler {
+        RawEventSignaler {
+            loop_: self.loop_.as_ptr(),
+            source: self.source,
+        }
+    }
+}
+
+impl<L: Loop> Drop for EventSource<'_, L> {
+    fn drop(&mut self) {
+        destroy_raw_source(self.loop_, self.source)
+    }
+}
+
+/// The pointers needed to signal an [`EventSource`], which can be sent to other threads since
+/// signaling an event only writes to its file descriptor.
+///
+/// It does not keep the source alive, so users must make sure it is not used once the source
+/// is dropped.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct RawEventSignaler {
+    loop_: *mut pw_sys::pw_loop,
+    source: *mut spa_sys::spa_source,
+}
+
+unsafe impl Send for RawEventSignaler {}
+unsafe impl Sync for RawEventSignaler {}
+
+impl RawEventSignaler {
+    fn as_ptr(&self) -> *mut pw_sys::pw_loop {
+        self.loop_
+    }
+
+    /// Signal the event.
+    ///
+    /// # Safety
+    /// The [`EventSource`] this was created from must still be alive.
+    pub(crate) unsafe fn signal_event(&self) -> Result<(), Error> {
+        let res = loop_utils_call!(self, signal_event, self.source);
+
+        SpaResult::from_raw(res).into_sync_result()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, rc::Rc, sync::mpsc, thread};
+
+    use super::*;
+    use crate::{MainLoop, ThreadLoop};
+
+    #[test]
+    fn timer_local() {
+        crate::init();
+        let main_loop = MainLoop::new().unwrap();
+
+        let expired = Rc::new(Cell::new(0));
+        let timer = main_loop
+            .add_timer_local({
+                let expired = expired.clone();
+                move |expirations| expired.set(expired.get() + expirations)
+            })
+            .unwrap();
+        timer.update_timer(Duration::from_millis(1), None).unwrap();
+
+        while expired.get() == 0 {
+            main_loop.iterate(Duration::from_secs(1)).unwrap();
+        }
+        assert_eq!(expired.get(), 1);
+    }
+
+    #[test]
+    fn thread_loop_sources() {
+        crate::init();
+        let thread_loop = ThreadLoop::new(Some("test-loop")).unwrap();
+        let (sender, receiver) = mpsc::channel();
+
+        let lock = thread_loop.lock();
+        let timer = thread_loop
+            .add_timer({
+                let sender = sender.clone();
+                move |_| sender.send("timer").unwrap()
+            })
+            .unwrap();
+        let event = thread_loop
+            .add_event(move |_| sender.send("event").unwrap())
+            .unwrap();
+        thread_loop.start().unwrap();
+
+        timer.update_timer(Duration::from_millis(1), None).unwrap();
+        drop(lock);
+        assert_eq!(
+            receiver.recv_timeout(Duration::from_secs(5)).unwrap(),
+            "timer"
+        );
+
+        event.signal_event().unwrap();
+        assert_eq!(
+            receiver.recv_timeout(Duration::from_secs(5)).unwrap(),
+            "event"
+        );
+
+        thread_loop.stop();
+        drop((timer, event));
+    }
+
+    #[test]
+    fn thread_loop_invoke() {
+        crate::init();
+        let thread_loop = ThreadLoop::new(Some("test-loop")).unwrap();
+        thread_loop.start().unwrap();
+
+        let threads: Vec<_> = (0..4)
+            .map(|n| {
+                let thread_loop = thread_loop.clone();
+                thread::spawn(move || {
+                    for i in 0..100 {
+                        let in_thread = thread_loop.clone();
+                        let res = thread_loop
+                            .invoke(move || (in_thread.in_thread(), n * 100 + i))
+                            .unwrap();
+                        assert_eq!(res, (true, n * 100 + i));
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        thread_loop.stop();
+    }
+}
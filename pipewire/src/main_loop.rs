@@ -1,12 +1,24 @@
 // Copyright 2020, Collabora Ltd.
 // SPDX-License-Identifier: MIT
 
+use std::io;
 use std::ops::Deref;
+use std::os::unix::io::{AsRawFd, RawFd};
 use std::ptr;
 use std::rc::{Rc, Weak};
+use std::sync::{Arc, Mutex};
+#[cfg(feature = "async")]
+use std::{
+    future::Future,
+    sync::atomic::{AtomicBool, Ordering},
+    task::{Context, Poll},
+};
+
+#[cfg(feature = "async")]
+use futures::task::ArcWake;
 
 use crate::error::Error;
-use crate::loop_::Loop;
+use crate::loop_::{invoke_nonblocking_raw, invoke_raw, LocalLoop, Loop};
 
 #[derive(Debug, Clone)]
 pub struct MainLoop {
@@ -37,7 +49,33 @@ impl Deref for MainLoop {
 
 impl Loop for MainLoop {
     fn as_ptr(&self) -> *mut pw_sys::pw_loop {
-        unsafe { pw_sys::pw_main_loop_get_loop(self.inner.0) }
+        unsafe { pw_sys::pw_main_loop_get_loop(self.inner.ptr) }
+    }
+
+    fn invoke<F, R>(&self, func: F) -> Result<R, Error>
+    where
+        F: FnOnce() -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        // A MainLoop is not Send, so this is called from the thread of the loop, which calls
+        // `func` right away instead of queuing it with the invocations of the senders.
+        unsafe { invoke_raw(self.as_ptr(), func) }
+    }
+
+    fn invoke_nonblocking<F>(&self, func: F) -> Result<(), Error>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        // Called from the thread of the loop like `invoke`.
+        unsafe { invoke_nonblocking_raw(self.as_ptr(), func) }
+    }
+}
+
+impl LocalLoop for MainLoop {}
+
+impl AsRawFd for MainLoop {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd()
     }
 }
 
@@ -52,7 +90,11 @@ impl WeakMainLoop {
 }
 
 #[derive(Debug)]
-pub struct MainLoopInner(*mut pw_sys::pw_main_loop);
+pub struct MainLoopInner {
+    ptr: *mut pw_sys::pw_main_loop,
+    /// Shared with the senders, cleared when the loop is destroyed.
+    sender_loop: Arc<Mutex<Option<SenderLoopPtr>>>,
+}
 
 impl MainLoopInner {
     // TODO: props argument
@@ -62,26 +104,127 @@ impl MainLoopInner {
             if l.is_null() {
                 Err(Error::CreationFailed)
             } else {
-                Ok(MainLoopInner(l))
+                let sender_loop = SenderLoopPtr(pw_sys::pw_main_loop_get_loop(l));
+                Ok(MainLoopInner {
+                    ptr: l,
+                    sender_loop: Arc::new(Mutex::new(Some(sender_loop))),
+                })
             }
         }
     }
 
+    /// Get a sender, to queue closures to be called from the loop from other threads.
+    pub fn sender(&self) -> LoopSender {
+        LoopSender {
+            loop_: self.sender_loop.clone(),
+        }
+    }
+
     pub fn run(&self) {
         unsafe {
-            pw_sys::pw_main_loop_run(self.0);
+            pw_sys::pw_main_loop_run(self.ptr);
         }
     }
 
     pub fn quit(&self) {
         unsafe {
-            pw_sys::pw_main_loop_quit(self.0);
+            pw_sys::pw_main_loop_quit(self.ptr);
+        }
+    }
+
+    /// Run the loop until `future` completes, returning its output.
+    ///
+    /// The loop is stopped each time the future is woken up so it can be polled again.
+    #[cfg(feature = "async")]
+    pub fn run_until<F: Future>(&self, future: F) -> F::Output {
+        futures::pin_mut!(future);
+
+        let wake = Arc::new(LoopWake {
+            main_loop: Mutex::new(Some(MainLoopPtr(self.ptr))),
+            sender_loop: self.sender_loop.clone(),
+            woken: AtomicBool::new(false),
+        });
+        let waker = futures::task::waker(wake.clone());
+        let mut cx = Context::from_waker(&waker);
+
+        let output = loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                break output;
+            }
+            if !wake.woken.swap(false, Ordering::SeqCst) {
+                self.run();
+            }
+        };
+
+        // The waker may be kept around by the future, make sure it won't touch the loop anymore.
+        *wake.main_loop.lock().unwrap() = None;
+        output
+    }
+}
+
+#[cfg(feature = "async")]
+struct MainLoopPtr(*mut pw_sys::pw_main_loop);
+
+// pw_main_loop_quit() can be called from any thread, under the same lock as the senders as it
+// invokes a closure on the loop.
+#[cfg(feature = "async")]
+unsafe impl Send for MainLoopPtr {}
+
+/// Waker of a future run by [`MainLoopInner::run_until`], stopping the loop to poll it again.
+#[cfg(feature = "async")]
+struct LoopWake {
+    main_loop: Mutex<Option<MainLoopPtr>>,
+    /// Locked while stopping the loop, so it is not done at the same time as a sender invokes.
+    sender_loop: Arc<Mutex<Option<SenderLoopPtr>>>,
+    woken: AtomicBool,
+}
+
+#[cfg(feature = "async")]
+impl ArcWake for LoopWake {
+    fn wake_by_ref(arc_self: &Arc<Self>) {
+        arc_self.woken.store(true, Ordering::SeqCst);
+
+        let sender_loop = arc_self.sender_loop.lock().unwrap();
+        if let (Some(_), Some(main_loop)) = (&*sender_loop, &*arc_self.main_loop.lock().unwrap()) {
+            unsafe { pw_sys::pw_main_loop_quit(main_loop.0) };
         }
     }
 }
 
 impl Drop for MainLoopInner {
     fn drop(&mut self) {
-        unsafe { pw_sys::pw_main_loop_destroy(self.0) }
+        *self.sender_loop.lock().unwrap() = None;
+        unsafe { pw_sys::pw_main_loop_destroy(self.ptr) }
+    }
+}
+
+#[derive(Debug)]
+struct SenderLoopPtr(*mut pw_sys::pw_loop);
+
+// Invoking a closure on a loop can be done from any thread, as long as a single thread does it
+// at a time, as older versions of PipeWire queue the invocations in a single producer ring buffer.
+unsafe impl Send for SenderLoopPtr {}
+unsafe impl Sync for SenderLoopPtr {}
+
+/// A handle to queue closures to be called from a [`MainLoop`], which can be sent to other
+/// threads.
+#[derive(Debug, Clone)]
+pub struct LoopSender {
+    /// Locked while invoking, so the senders of other threads don't do it at the same time.
+    loop_: Arc<Mutex<Option<SenderLoopPtr>>>,
+}
+
+impl LoopSender {
+    /// Queue `func` to be called from the thread of the loop.
+    ///
+    /// Returns an `EPIPE` error if the loop was destroyed.
+    pub fn send<F>(&self, func: F) -> Result<(), Error>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        match &*self.loop_.lock().unwrap() {
+            Some(loop_) => unsafe { invoke_nonblocking_raw(loop_.0, func) },
+            None => Err(io::Error::from_raw_os_error(libc::EPIPE).into()),
+        }
     }
 }
@@ -0,0 +1,131 @@
+// Copyright 2021, Collabora Ltd.
+// SPDX-License-Identifier: MIT
+
+use std::{ffi::CStr, fmt, marker::PhantomData, os::raw::c_char, ptr::NonNull};
+
+use crate::properties::Properties;
+use spa::dict::{ForeignDict, ReadableDict};
+
+/// A module loaded in the process by [`Context::load_module`](crate::Context::load_module).
+///
+/// The module is unloaded when this is dropped.
+pub struct ImplModule<'c> {
+    ptr: NonNull<pw_sys::pw_impl_module>,
+    context: PhantomData<&'c ()>,
+}
+
+impl<'c> ImplModule<'c> {
+    /// # Safety
+    /// `ptr` must be a valid module owned by the caller, which is destroyed on drop.
+    pub(crate) unsafe fn from_ptr(ptr: NonNull<pw_sys::pw_impl_module>) -> Self {
+        Self {
+            ptr,
+            context: PhantomData,
+        }
+    }
+
+    pub fn as_ptr(&self) -> *mut pw_sys::pw_impl_module {
+        self.ptr.as_ptr()
+    }
+
+    /// The information about the module.
+    pub fn info(&self) -> ModuleInfo<'_> {
+        unsafe { ModuleInfo::new(pw_sys::pw_impl_module_get_info(self.as_ptr())) }
+    }
+
+    /// A copy of the properties of the module.
+    pub fn properties(&self) -> Properties {
+        unsafe {
+            let props = pw_sys::pw_impl_module_get_properties(self.as_ptr());
+            Properties::from_ptr(pw_sys::pw_properties_copy(props))
+        }
+    }
+
+    /// Update the properties of the module with the key-value pairs of `dict`.
+    ///
+    /// Returns the number of properties that changed.
+    pub fn update_properties<D: ReadableDict>(&self, dict: &D) -> usize {
+        let changed =
+            unsafe { pw_sys::pw_impl_module_update_properties(self.as_ptr(), dict.get_dict_ptr()) };
+
+        changed.max(0) as usize
+    }
+}
+
+impl fmt::Debug for ImplModule<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ImplModule")
+            .field("info", &self.info())
+            .finish()
+    }
+}
+
+impl Drop for ImplModule<'_> {
+    fn drop(&mut self) {
+        unsafe { pw_sys::pw_impl_module_destroy(self.as_ptr()) }
+    }
+}
+
+/// The information about a module, as returned by [`ImplModule::info`].
+pub struct ModuleInfo<'m> {
+    ptr: *const pw_sys::pw_module_info,
+    props: Option<ForeignDict>,
+    module: PhantomData<&'m ()>,
+}
+
+impl ModuleInfo<'_> {
+    fn new(ptr: *const pw_sys::pw_module_info) -> Self {
+        let props_ptr = unsafe { (*ptr).props };
+        Self {
+            ptr,
+            props: if props_ptr.is_null() {
+                None
+            } else {
+                Some(unsafe { ForeignDict::from_ptr(props_ptr) })
+            },
+            module: PhantomData,
+        }
+    }
+
+    pub fn id(&self) -> u32 {
+        unsafe { (*self.ptr).id }
+    }
+
+    pub fn name(&self) -> &str {
+        unsafe { str_from_ptr((*self.ptr).name) }.unwrap_or_default()
+    }
+
+    pub fn filename(&self) -> &str {
+        unsafe { str_from_ptr((*self.ptr).filename) }.unwrap_or_default()
+    }
+
+    pub fn args(&self) -> Option<&str> {
+        unsafe { str_from_ptr((*self.ptr).args) }
+    }
+
+    pub fn props(&self) -> Option<&ForeignDict> {
+        self.props.as_ref()
+    }
+}
+
+impl fmt::Debug for ModuleInfo<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ModuleInfo")
+            .field("id", &self.id())
+            .field("name", &self.name())
+            .field("filename", &self.filename())
+            .field("args", &self.args())
+            .field("props", &self.props())
+            .finish()
+    }
+}
+
+/// # Safety
+/// `ptr` must be null or point to a null-terminated string valid for `'a`.
+unsafe fn str_from_ptr<'a>(ptr: *const c_char) -> Option<&'a str> {
+    if ptr.is_null() {
+        None
+    } else {
+        CStr::from_ptr(ptr).to_str().ok()
+    }
+}
@@ -5,13 +5,16 @@ use bitflags::bitflags;
 use libc::c_void;
 use std::ffi::CStr;
 use std::pin::Pin;
-use std::{fmt, mem};
+use std::rc::Rc;
+use std::{any::Any, fmt, mem, ptr, slice};
 
 use crate::{
     proxy::{Listener, Proxy, ProxyT},
     types::ObjectType,
+    utils::message_seq,
+    Error,
 };
-use spa::dict::ForeignDict;
+use spa::{dict::ForeignDict, param::ParamType, pod::Pod, spa_interface_call_method};
 
 #[derive(Debug)]
 pub struct Node {
@@ -40,7 +43,6 @@ impl ProxyT for Node {
 }
 
 impl Node {
-    // TODO: add non-local version when we'll bind pw_thread_loop_start()
     #[must_use]
     pub fn add_listener_local(&self) -> NodeListenerLocalBuilder {
         NodeListenerLocalBuilder {
@@ -48,13 +50,41 @@ impl Node {
             cbs: ListenerLocalCallbacks::default(),
         }
     }
+
+    /// Like [`add_listener_local`](Self::add_listener_local), but the callbacks can be called
+    /// from another thread, such as the one of a [`ThreadLoop`](crate::ThreadLoop).
+    #[must_use]
+    pub fn add_listener(&self) -> NodeListenerBuilder {
+        NodeListenerBuilder(self.add_listener_local())
+    }
+
+    /// Subscribe to the params of the node with the ids `ids`, which are then emitted to the
+    /// `param` callback of the listeners, and again each time they change.
+    ///
+    /// Returns the sequence number of the request, which is also the
+    /// [`seq`](crate::ServerError::seq) of the errors reported about it.
+    pub fn subscribe_params(&self, ids: &[ParamType]) -> Result<i32, Error> {
+        let mut ids: Vec<u32> = ids.iter().map(|id| id.as_raw()).collect();
+
+        let res = unsafe {
+            spa_interface_call_method!(
+                self.proxy.as_ptr(),
+                pw_sys::pw_node_methods,
+                subscribe_params,
+                ids.as_mut_ptr(),
+                ids.len() as u32
+            )
+        };
+
+        message_seq(res)
+    }
 }
 
 #[derive(Default)]
 struct ListenerLocalCallbacks {
     info: Option<Box<dyn Fn(&NodeInfo)>>,
     #[allow(clippy::type_complexity)]
-    param: Option<Box<dyn Fn(i32, u32, u32, u32)>>, // TODO: add params
+    param: Option<Box<dyn Fn(i32, u32, u32, u32, Option<&Pod>)>>,
 }
 
 pub struct NodeListenerLocalBuilder<'a> {
@@ -62,24 +92,68 @@ pub struct NodeListenerLocalBuilder<'a> {
     cbs: ListenerLocalCallbacks,
 }
 
+/// Builder of a listener whose callbacks can be called from another thread.
+pub struct NodeListenerBuilder<'a>(NodeListenerLocalBuilder<'a>);
+
+impl<'a> NodeListenerBuilder<'a> {
+    #[must_use]
+    pub fn info<F>(self, info: F) -> Self
+    where
+        F: Fn(&NodeInfo) + Send + 'static,
+    {
+        Self(self.0.info(info))
+    }
+
+    #[must_use]
+    pub fn param<F>(self, param: F) -> Self
+    where
+        F: Fn(i32, u32, u32, u32, Option<&Pod>) + Send + 'static,
+    {
+        Self(self.0.param(param))
+    }
+
+    #[must_use]
+    pub fn register(self) -> NodeListener {
+        self.0.register()
+    }
+}
+
+/// The info of a node, which is either borrowed from the `info` callback, or a copy owning its
+/// data made with `clone`.
 pub struct NodeInfo {
     ptr: *const pw_sys::pw_node_info,
     props: Option<ForeignDict>,
+    owned: bool,
 }
 
 impl NodeInfo {
     fn new(ptr: *const pw_sys::pw_node_info) -> Self {
-        let props_ptr = unsafe { (*ptr).props };
         Self {
             ptr,
-            props: if props_ptr.is_null() {
-                None
-            } else {
-                Some(unsafe { ForeignDict::from_ptr(props_ptr) })
-            },
+            props: Self::props_of(ptr),
+            owned: false,
         }
     }
 
+    fn props_of(ptr: *const pw_sys::pw_node_info) -> Option<ForeignDict> {
+        let props_ptr = unsafe { (*ptr).props };
+        if props_ptr.is_null() {
+            None
+        } else {
+            Some(unsafe { ForeignDict::from_ptr(props_ptr) })
+        }
+    }
+
+    /// Update this copy with the fields of `update` that changed, as received by the `info`
+    /// callback, to keep the latest info of the node.
+    pub(crate) fn update(&mut self, update: &Self) {
+        assert!(self.owned, "only a copy of the info can be updated");
+
+        unsafe { pw_sys::pw_node_info_update(self.ptr as *mut _, update.ptr) };
+        // The properties are copied again when they changed.
+        self.props = Self::props_of(self.ptr);
+    }
+
     pub fn id(&self) -> u32 {
         unsafe { (*self.ptr).id }
     }
@@ -127,6 +201,45 @@ impl NodeInfo {
         self.props.as_ref()
     }
     // TODO: params
+
+    /// The ids of the params of the node with their flags, whose `SPA_PARAM_INFO_SERIAL` bit
+    /// toggles each time the params with that id change.
+    pub(crate) fn raw_params(&self) -> &[spa_sys::spa_param_info] {
+        unsafe {
+            let info = &*self.ptr;
+            if info.params.is_null() {
+                &[]
+            } else {
+                slice::from_raw_parts(info.params, info.n_params as usize)
+            }
+        }
+    }
+}
+
+impl Clone for NodeInfo {
+    fn clone(&self) -> Self {
+        unsafe {
+            // Copy all the fields, not only the ones that changed.
+            let mut info = *self.ptr;
+            info.change_mask = NodeChangeMask::all().bits();
+            let ptr = pw_sys::pw_node_info_update(ptr::null_mut(), &info);
+            (*ptr).change_mask = (*self.ptr).change_mask;
+
+            Self {
+                ptr,
+                props: Self::props_of(ptr),
+                owned: true,
+            }
+        }
+    }
+}
+
+impl Drop for NodeInfo {
+    fn drop(&mut self) {
+        if self.owned {
+            unsafe { pw_sys::pw_node_info_free(self.ptr as *mut _) }
+        }
+    }
 }
 
 bitflags! {
@@ -170,6 +283,9 @@ pub struct NodeListener {
     listener: Pin<Box<spa_sys::spa_hook>>,
     #[allow(dead_code)]
     data: Box<ListenerLocalCallbacks>,
+    // Keep the proxy alive as long as the listener is registered on it
+    #[allow(dead_code)]
+    proxy: Rc<dyn Any>,
 }
 
 impl<'a> Listener for NodeListener {}
@@ -193,7 +309,7 @@ impl<'a> NodeListenerLocalBuilder<'a> {
     #[must_use]
     pub fn param<F>(mut self, param: F) -> Self
     where
-        F: Fn(i32, u32, u32, u32) + 'static,
+        F: Fn(i32, u32, u32, u32, Option<&Pod>) + 'static,
     {
         self.cbs.param = Some(Box::new(param));
         self
@@ -216,10 +332,15 @@ impl<'a> NodeListenerLocalBuilder<'a> {
             id: u32,
             index: u32,
             next: u32,
-            _param: *const spa_sys::spa_pod,
+            param: *const spa_sys::spa_pod,
         ) {
             let callbacks = (data as *mut ListenerLocalCallbacks).as_ref().unwrap();
-            callbacks.param.as_ref().unwrap()(seq, id, index, next);
+            let param = if param.is_null() {
+                None
+            } else {
+                Some(Pod::from_raw(param))
+            };
+            callbacks.param.as_ref().unwrap()(seq, id, index, next, param.as_ref());
         }
 
         let e = unsafe {
@@ -258,6 +379,7 @@ impl<'a> NodeListenerLocalBuilder<'a> {
             events: e,
             listener,
             data,
+            proxy: self.node.proxy.keep_alive(),
         }
     }
 }
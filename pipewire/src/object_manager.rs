@@ -0,0 +1,847 @@
+// Copyright 2021, Collabora Ltd.
+// SPDX-License-Identifier: MIT
+
+//! A cache of the global objects of a [`Registry`], kept up to date.
+//!
+//! An [`ObjectManager`] binds the globals matching its [`Interest`]s, keeps their latest info,
+//! properties and params, and tells its listeners when objects are added, changed or removed.
+//!
+//! # Examples
+//! ```no_run
+//! use pipewire::{
+//!     object_manager::{Interest, ObjectManager},
+//!     types::ObjectType,
+//!     Context, MainLoop,
+//! };
+//! use spa::dict::ReadableDict;
+//!
+//! pipewire::init();
+//! let main_loop = MainLoop::new().unwrap();
+//! let context = Context::new(&main_loop).unwrap();
+//! let core = context.connect(None).unwrap();
+//!
+//! let object_manager = ObjectManager::new(
+//!     &core,
+//!     vec![Interest::new(ObjectType::Node).with_property("media.class", "Audio/Sink")],
+//! );
+//! let _listener = object_manager
+//!     .add_listener_local()
+//!     .added(|object| {
+//!         let name = object.props().and_then(|props| props.get("node.name"));
+//!         println!("sink {}: {:?}", object.id(), name);
+//!     })
+//!     .removed(|object| println!("sink {} removed", object.id()))
+//!     .register();
+//!
+//! main_loop.run();
+//! ```
+
+use std::{
+    cell::{Cell, RefCell},
+    collections::{HashMap, HashSet},
+    fmt,
+    rc::{Rc, Weak},
+};
+
+use crate::{
+    core_::{self, Core, PW_ID_CORE},
+    graph::Graph,
+    link::{Link, LinkInfo, LinkListener},
+    node::{Node, NodeChangeMask, NodeInfo, NodeListener},
+    port::{Port, PortChangeMask, PortInfo, PortListener},
+    proxy::{Listener as ProxyListener, Proxy, ProxyT},
+    registry::{self, GlobalObject, Registry},
+    types::ObjectType,
+    Error, Properties,
+};
+use spa::{
+    dict::{ForeignDict, ReadableDict},
+    param::ParamType,
+    pod::Pod,
+};
+
+/// A kind of objects an [`ObjectManager`] is interested in.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Interest {
+    type_: ObjectType,
+    props: Vec<(String, String)>,
+    params: Vec<ParamType>,
+}
+
+impl Interest {
+    /// An interest in all the objects of type `type_`.
+    pub fn new(type_: ObjectType) -> Self {
+        Self {
+            type_,
+            props: Vec::new(),
+            params: Vec::new(),
+        }
+    }
+
+    /// Only match the objects whose global property `key` is `value`.
+    #[must_use]
+    pub fn with_property(mut self, key: &str, value: &str) -> Self {
+        self.props.push((key.to_owned(), value.to_owned()));
+        self
+    }
+
+    /// Keep the params with the id `id` of the matching nodes and ports.
+    #[must_use]
+    pub fn with_param(mut self, id: ParamType) -> Self {
+        self.params.push(id);
+        self
+    }
+
+    /// Whether `global` matches the interest.
+    pub fn matches<D: ReadableDict>(&self, global: &GlobalObject<D>) -> bool {
+        global.type_ == self.type_
+            && self.props.iter().all(|(key, value)| {
+                global.props.as_ref().and_then(|props| props.get(key)) == Some(value.as_str())
+            })
+    }
+}
+
+/// The params with one id of an object, as last enumerated by the server.
+#[derive(Clone)]
+struct Params {
+    seq: i32,
+    pods: Vec<Vec<u8>>,
+}
+
+/// The latest info of an [`Object`], as reported by the proxy bound to it.
+#[derive(Debug, Clone)]
+pub enum ObjectInfo {
+    Node(NodeInfo),
+    Port(PortInfo),
+    Link(LinkInfo),
+}
+
+impl ObjectInfo {
+    pub fn props(&self) -> Option<&ForeignDict> {
+        match self {
+            Self::Node(info) => info.props(),
+            Self::Port(info) => info.props(),
+            Self::Link(info) => info.props(),
+        }
+    }
+
+    fn raw_params(&self) -> &[spa_sys::spa_param_info] {
+        match self {
+            Self::Node(info) => info.raw_params(),
+            Self::Port(info) => info.raw_params(),
+            Self::Link(_) => &[],
+        }
+    }
+}
+
+/// An object tracked by an [`ObjectManager`].
+///
+/// The objects are handed out as snapshots, which don't change when the tracked object changes.
+#[derive(Clone)]
+pub struct Object {
+    global: GlobalObject<Properties>,
+    info: Option<ObjectInfo>,
+    props: Option<Properties>,
+    params: HashMap<u32, Params>,
+    proxy: Option<Rc<dyn ProxyT>>,
+}
+
+impl Object {
+    /// An object that is not bound yet.
+    pub(crate) fn from_global(global: GlobalObject<Properties>) -> Self {
+        Self {
+            global,
+            info: None,
+            props: None,
+            params: HashMap::new(),
+            proxy: None,
+        }
+    }
+
+    pub fn id(&self) -> u32 {
+        self.global.id
+    }
+
+    pub fn type_(&self) -> &ObjectType {
+        &self.global.type_
+    }
+
+    /// The global object, as announced by the registry.
+    pub fn global(&self) -> &GlobalObject<Properties> {
+        &self.global
+    }
+
+    /// The latest info of the object, once the bound proxy reported it.
+    pub fn info(&self) -> Option<&ObjectInfo> {
+        self.info.as_ref()
+    }
+
+    /// The latest properties of the object, which are the ones of the global until the bound
+    /// object reports its own.
+    pub fn props(&self) -> Option<&Properties> {
+        self.props.as_ref().or_else(|| self.global.props.as_ref())
+    }
+
+    /// The latest params of the object with the id `id`, if they are kept by its interests.
+    pub fn params(&self, id: ParamType) -> impl Iterator<Item = Pod<'_>> {
+        self.params
+            .get(&id.as_raw())
+            .into_iter()
+            .flat_map(|params| params.pods.iter())
+            .filter_map(|pod| Pod::from_bytes(pod).ok())
+    }
+
+    /// The proxy bound to the object, for the nodes, ports and links.
+    pub fn proxy(&self) -> Option<&Proxy> {
+        self.proxy.as_ref().map(|proxy| proxy.upcast_ref())
+    }
+
+    /// Update the object with `info`, dropping the params of the ids in `kept` that changed.
+    ///
+    /// Returns whether such params changed, in which case the server sends them again.
+    fn set_info<P: BoundProxy>(&mut self, info: &P::Info, kept: &[ParamType]) -> bool {
+        let changed: Vec<u32> = P::changed_params(info)
+            .unwrap_or(&[])
+            .iter()
+            .filter(|param| kept.iter().any(|id| id.as_raw() == param.id))
+            .filter(|param| {
+                // The serial flag toggles each time the params change.
+                let latest = self.info.as_ref().map_or(&[][..], ObjectInfo::raw_params);
+                !latest.iter().any(|latest| {
+                    latest.id == param.id
+                        && (latest.flags ^ param.flags) & spa_sys::SPA_PARAM_INFO_SERIAL == 0
+                })
+            })
+            .map(|param| param.id)
+            .collect();
+        for id in &changed {
+            self.params.remove(id);
+        }
+
+        P::set_info(&mut self.info, info);
+        self.props = self
+            .info
+            .as_ref()
+            .and_then(ObjectInfo::props)
+            .map(Properties::from_dict);
+
+        !changed.is_empty()
+    }
+
+    fn set_param(&mut self, seq: i32, id: u32, param: &Pod) {
+        let params = self.params.entry(id).or_insert_with(|| Params {
+            seq,
+            pods: Vec::new(),
+        });
+        // The params that changed were dropped with the info announcing it, but a new
+        // enumeration of them may also be sent with another seq.
+        if params.seq != seq {
+            params.seq = seq;
+            params.pods.clear();
+        }
+        params.pods.push(param.as_bytes().to_vec());
+    }
+}
+
+impl fmt::Debug for Object {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Object")
+            .field("global", &self.global)
+            .field("info", &self.info)
+            .field("props", &self.props())
+            .field("proxy", &self.proxy())
+            .finish()
+    }
+}
+
+/// The proxies bound by an [`ObjectManager`] to keep the latest info and params of their objects.
+trait BoundProxy: ProxyT + Sized + 'static {
+    type Info;
+    type Listener: ProxyListener + 'static;
+
+    /// Listen to the info and params of the proxy.
+    fn listen<I, P>(&self, info: I, param: P) -> Self::Listener
+    where
+        I: Fn(&Self::Info) + 'static,
+        P: Fn(i32, u32, u32, u32, Option<&Pod>) + 'static;
+
+    /// Subscribe to the params of the proxy with the ids `ids`.
+    fn subscribe(&self, ids: &[ParamType]) -> Result<i32, Error>;
+
+    /// The params listed by `info`, if they changed.
+    fn changed_params(info: &Self::Info) -> Option<&[spa_sys::spa_param_info]>;
+
+    /// Update `latest` with `info`, as received by the listener.
+    fn set_info(latest: &mut Option<ObjectInfo>, info: &Self::Info);
+}
+
+impl BoundProxy for Node {
+    type Info = NodeInfo;
+    type Listener = NodeListener;
+
+    fn listen<I, P>(&self, info: I, param: P) -> NodeListener
+    where
+        I: Fn(&NodeInfo) + 'static,
+        P: Fn(i32, u32, u32, u32, Option<&Pod>) + 'static,
+    {
+        self.add_listener_local().info(info).param(param).register()
+    }
+
+    fn subscribe(&self, ids: &[ParamType]) -> Result<i32, Error> {
+        self.subscribe_params(ids)
+    }
+
+    fn changed_params(info: &NodeInfo) -> Option<&[spa_sys::spa_param_info]> {
+        if info.change_mask().contains(NodeChangeMask::PARAMS) {
+            Some(info.raw_params())
+        } else {
+            None
+        }
+    }
+
+    fn set_info(latest: &mut Option<ObjectInfo>, info: &NodeInfo) {
+        match latest {
+            Some(ObjectInfo::Node(latest)) => latest.update(info),
+            _ => *latest = Some(ObjectInfo::Node(info.clone())),
+        }
+    }
+}
+
+impl BoundProxy for Port {
+    type Info = PortInfo;
+    type Listener = PortListener;
+
+    fn listen<I, P>(&self, info: I, param: P) -> PortListener
+    where
+        I: Fn(&PortInfo) + 'static,
+        P: Fn(i32, u32, u32, u32, Option<&Pod>) + 'static,
+    {
+        self.add_listener_local().info(info).param(param).register()
+    }
+
+    fn subscribe(&self, ids: &[ParamType]) -> Result<i32, Error> {
+        self.subscribe_params(ids)
+    }
+
+    fn changed_params(info: &PortInfo) -> Option<&[spa_sys::spa_param_info]> {
+        if info.change_mask().contains(PortChangeMask::PARAMS) {
+            Some(info.raw_params())
+        } else {
+            None
+        }
+    }
+
+    fn set_info(latest: &mut Option<ObjectInfo>, info: &PortInfo) {
+        match latest {
+            Some(ObjectInfo::Port(latest)) => latest.update(info),
+            _ => *latest = Some(ObjectInfo::Port(info.clone())),
+        }
+    }
+}
+
+impl BoundProxy for Link {
+    type Info = LinkInfo;
+    type Listener = LinkListener;
+
+    fn listen<I, P>(&self, info: I, _param: P) -> LinkListener
+    where
+        I: Fn(&LinkInfo) + 'static,
+        P: Fn(i32, u32, u32, u32, Option<&Pod>) + 'static,
+    {
+        self.add_listener_local().info(info).register()
+    }
+
+    fn subscribe(&self, _ids: &[ParamType]) -> Result<i32, Error> {
+        // Links have no params.
+        Ok(0)
+    }
+
+    fn changed_params(_info: &LinkInfo) -> Option<&[spa_sys::spa_param_info]> {
+        None
+    }
+
+    fn set_info(latest: &mut Option<ObjectInfo>, info: &LinkInfo) {
+        match latest {
+            Some(ObjectInfo::Link(latest)) => latest.update(info),
+            _ => *latest = Some(ObjectInfo::Link(info.clone())),
+        }
+    }
+}
+
+struct Inner {
+    registry: Registry,
+    interests: Vec<Interest>,
+    // Only borrowed while no callback is called, so the callbacks can use the object manager.
+    objects: RefCell<HashMap<u32, Rc<Object>>>,
+    /// The listeners of the proxies bound to the objects, by id.
+    bindings: RefCell<HashMap<u32, Box<dyn ProxyListener>>>,
+    listeners: RefCell<Vec<Weak<ListenerLocalCallbacks>>>,
+    /// The objects whose params are being sent again, whose listeners are told about the change
+    /// once the server has sent all of them.
+    pending: RefCell<HashSet<u32>>,
+    /// The seq of the sync answered after the pending params.
+    pending_seq: Cell<Option<i32>>,
+}
+
+impl Inner {
+    fn global(this: &Rc<Self>, global: &GlobalObject<&ForeignDict>) {
+        let interests: Vec<&Interest> = this
+            .interests
+            .iter()
+            .filter(|interest| interest.matches(global))
+            .collect();
+        if interests.is_empty() {
+            return;
+        }
+        let params: Vec<ParamType> = interests
+            .iter()
+            .flat_map(|interest| interest.params.iter().copied())
+            .collect();
+
+        let mut object = Object::from_global(global.to_owned());
+        match object.global.type_ {
+            ObjectType::Node => Self::bind::<Node>(this, &mut object, &params),
+            ObjectType::Port => Self::bind::<Port>(this, &mut object, &params),
+            ObjectType::Link => Self::bind::<Link>(this, &mut object, &params),
+            _ => {}
+        }
+        let object = Rc::new(object);
+        this.objects.borrow_mut().insert(global.id, object.clone());
+
+        for cbs in this.listeners() {
+            if let Some(added) = &cbs.added {
+                added(&object);
+            }
+        }
+    }
+
+    fn global_remove(&self, id: u32) {
+        self.bindings.borrow_mut().remove(&id);
+        self.pending.borrow_mut().remove(&id);
+        let object = self.objects.borrow_mut().remove(&id);
+
+        if let Some(object) = object {
+            for cbs in self.listeners() {
+                if let Some(removed) = &cbs.removed {
+                    removed(&object);
+                }
+            }
+        }
+    }
+
+    /// Bind the proxy of `object` and listen to its info and params.
+    fn bind<P: BoundProxy>(this: &Rc<Self>, object: &mut Object, params: &[ParamType]) {
+        let proxy: P = match this.registry.bind(&object.global) {
+            Ok(proxy) => proxy,
+            Err(_) => return,
+        };
+        let id = object.global.id;
+        let inner = Rc::downgrade(this);
+
+        let listener = proxy.listen(
+            {
+                let inner = inner.clone();
+                let params = params.to_vec();
+                move |info| Self::update(&inner, id, |object| !object.set_info::<P>(info, &params))
+            },
+            move |seq, param_id, _index, _next, param| {
+                if let Some(param) = param {
+                    Self::update(&inner, id, |object| {
+                        object.set_param(seq, param_id, param);
+                        false
+                    })
+                }
+            },
+        );
+        if !params.is_empty() {
+            // The object is still tracked without its params.
+            let _ = proxy.subscribe(params);
+        }
+
+        this.bindings.borrow_mut().insert(id, Box::new(listener));
+        object.proxy = Some(Rc::new(proxy));
+    }
+
+    /// Update the object `id` with `update`, then tell the listeners it changed.
+    ///
+    /// If `update` returns `false`, more params of the object are on their way, so the listeners
+    /// are only told once the server has sent all of them.
+    fn update<F: FnOnce(&mut Object) -> bool>(inner: &Weak<Self>, id: u32, update: F) {
+        let inner = match inner.upgrade() {
+            Some(inner) => inner,
+            None => return,
+        };
+        let (object, complete) = match inner.objects.borrow_mut().get_mut(&id) {
+            Some(object) => {
+                // The object is copied if a snapshot still refers to it.
+                let complete = update(Rc::make_mut(object));
+                (object.clone(), complete)
+            }
+            None => return,
+        };
+
+        if complete && !inner.pending.borrow().contains(&id) {
+            inner.changed(&object);
+        } else {
+            inner.pending.borrow_mut().insert(id);
+            if inner.pending_seq.get().is_none() {
+                // The server answers the sync once it has sent the params.
+                let seq = inner.registry.core().sync(0);
+                inner.pending_seq.set(Some(seq));
+            }
+        }
+    }
+
+    /// Tell the listeners about the pending objects, once the server answered the sync sent after
+    /// their params.
+    fn done(&self, id: u32, seq: i32) {
+        if id != PW_ID_CORE || self.pending_seq.get() != Some(seq) {
+            return;
+        }
+        self.pending_seq.set(None);
+
+        let ids: Vec<u32> = self.pending.borrow_mut().drain().collect();
+        for id in ids {
+            let object = self.objects.borrow().get(&id).cloned();
+            if let Some(object) = object {
+                self.changed(&object);
+            }
+        }
+    }
+
+    fn changed(&self, object: &Object) {
+        for cbs in self.listeners() {
+            if let Some(changed) = &cbs.changed {
+                changed(object);
+            }
+        }
+    }
+
+    /// The registered listeners, forgetting about the dropped ones.
+    fn listeners(&self) -> Vec<Rc<ListenerLocalCallbacks>> {
+        let mut listeners = self.listeners.borrow_mut();
+        listeners.retain(|cbs| cbs.strong_count() > 0);
+        listeners.iter().filter_map(Weak::upgrade).collect()
+    }
+}
+
+/// A cache of the global objects matching some [`Interest`]s.
+///
+/// The objects are only tracked while the loop of the core runs.
+pub struct ObjectManager {
+    // Declared first so they are removed before the registry is destroyed.
+    _listener: registry::Listener,
+    _core_listener: core_::Listener,
+    inner: Rc<Inner>,
+}
+
+impl ObjectManager {
+    /// Create an object manager tracking the global objects of `core` matching any of
+    /// `interests`.
+    pub fn new(core: &Core, interests: Vec<Interest>) -> Self {
+        let inner = Rc::new(Inner {
+            registry: core.get_registry(),
+            interests,
+            objects: RefCell::new(HashMap::new()),
+            bindings: RefCell::new(HashMap::new()),
+            listeners: RefCell::new(Vec::new()),
+            pending: RefCell::new(HashSet::new()),
+            pending_seq: Cell::new(None),
+        });
+
+        let listener = inner
+            .registry
+            .add_listener_local()
+            .global({
+                let inner = Rc::downgrade(&inner);
+                move |global| {
+                    if let Some(inner) = inner.upgrade() {
+                        Inner::global(&inner, global);
+                    }
+                }
+            })
+            .global_remove({
+                let inner = Rc::downgrade(&inner);
+                move |id| {
+                    if let Some(inner) = inner.upgrade() {
+                        inner.global_remove(id);
+                    }
+                }
+            })
+            .register();
+
+        let core_listener = core
+            .add_listener_local()
+            .done({
+                let inner = Rc::downgrade(&inner);
+                move |id, seq| {
+                    if let Some(inner) = inner.upgrade() {
+                        inner.done(id, seq);
+                    }
+                }
+            })
+            .register();
+
+        Self {
+            _listener: listener,
+            _core_listener: core_listener,
+            inner,
+        }
+    }
+
+    /// Listen to the changes of the tracked objects.
+    ///
+    /// The listener is not told about the objects that are already tracked, which can be
+    /// found with [`objects`](Self::objects).
+    #[must_use]
+    pub fn add_listener_local(&self) -> ListenerLocalBuilder {
+        ListenerLocalBuilder {
+            object_manager: self,
+            cbs: ListenerLocalCallbacks::default(),
+        }
+    }
+
+    /// A snapshot of the tracked objects, by id, which doesn't change when the objects change.
+    pub fn objects(&self) -> HashMap<u32, Rc<Object>> {
+        self.inner.objects.borrow().clone()
+    }
+
+    /// A view of a snapshot of the tracked objects, to query them.
+    pub fn graph(&self) -> Graph {
+        Graph::new(self.objects())
+    }
+
+    /// A snapshot of the tracked object with the id `id`.
+    pub fn get(&self, id: u32) -> Option<Rc<Object>> {
+        self.inner.objects.borrow().get(&id).cloned()
+    }
+}
+
+impl fmt::Debug for ObjectManager {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ObjectManager")
+            .field("interests", &self.inner.interests)
+            .field("objects", &*self.inner.objects.borrow())
+            .finish()
+    }
+}
+
+#[derive(Default)]
+struct ListenerLocalCallbacks {
+    added: Option<Box<dyn Fn(&Object)>>,
+    changed: Option<Box<dyn Fn(&Object)>>,
+    removed: Option<Box<dyn Fn(&Object)>>,
+}
+
+pub struct ListenerLocalBuilder<'a> {
+    object_manager: &'a ObjectManager,
+    cbs: ListenerLocalCallbacks,
+}
+
+impl<'a> ListenerLocalBuilder<'a> {
+    /// Called when an object matching the interests is added.
+    #[must_use]
+    pub fn added<F>(mut self, added: F) -> Self
+    where
+        F: Fn(&Object) + 'static,
+    {
+        self.cbs.added = Some(Box::new(added));
+        self
+    }
+
+    /// Called once per update of the info, properties or params of an object.
+    #[must_use]
+    pub fn changed<F>(mut self, changed: F) -> Self
+    where
+        F: Fn(&Object) + 'static,
+    {
+        self.cbs.changed = Some(Box::new(changed));
+        self
+    }
+
+    /// Called with an object that was removed, right before it is dropped.
+    #[must_use]
+    pub fn removed<F>(mut self, removed: F) -> Self
+    where
+        F: Fn(&Object) + 'static,
+    {
+        self.cbs.removed = Some(Box::new(removed));
+        self
+    }
+
+    #[must_use]
+    pub fn register(self) -> Listener {
+        let data = Rc::new(self.cbs);
+        self.object_manager
+            .inner
+            .listeners
+            .borrow_mut()
+            .push(Rc::downgrade(&data));
+
+        Listener { data }
+    }
+}
+
+/// A listener of an [`ObjectManager`], unregistered when dropped.
+pub struct Listener {
+    // Only weakly referenced by the object manager
+    #[allow(dead_code)]
+    data: Rc<ListenerLocalCallbacks>,
+}
+
+impl Listener {
+    pub fn unregister(self) {
+        // Consuming the listener will call drop()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        properties,
+        stream::{Stream, StreamFlags},
+        utils::{connect_to_private_server, iterate_until},
+        MainLoop,
+    };
+    use spa::{
+        param::audio::{AudioFormat, AudioInfoRaw},
+        pod::Builder,
+        Direction,
+    };
+
+    fn global(type_: ObjectType, props: Properties) -> GlobalObject<Properties> {
+        GlobalObject::for_tests(42, type_, props)
+    }
+
+    #[test]
+    fn interest_type() {
+        let interest = Interest::new(ObjectType::Node);
+
+        assert!(interest.matches(&global(ObjectType::Node, crate::properties! {"K0" => "V0"})));
+        assert!(!interest.matches(&global(ObjectType::Port, crate::properties! {"K0" => "V0"})));
+    }
+
+    #[test]
+    fn interest_properties() {
+        let interest = Interest::new(ObjectType::Node)
+            .with_property("media.class", "Audio/Sink")
+            .with_property("node.name", "sink");
+
+        assert!(interest.matches(&global(
+            ObjectType::Node,
+            crate::properties! {
+                "media.class" => "Audio/Sink",
+                "node.name" => "sink",
+                "node.nick" => "Sink"
+            }
+        )));
+        assert!(!interest.matches(&global(
+            ObjectType::Node,
+            crate::properties! {
+                "media.class" => "Audio/Source",
+                "node.name" => "sink"
+            }
+        )));
+        assert!(!interest.matches(&global(
+            ObjectType::Node,
+            crate::properties! {"media.class" => "Audio/Sink"}
+        )));
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    enum Event {
+        Added(u32),
+        Changed(u32),
+        Removed(u32),
+    }
+
+    #[test]
+    fn track_stream_node() {
+        crate::init();
+        let main_loop = MainLoop::new().unwrap();
+        let (_context, core) = connect_to_private_server(&main_loop);
+
+        let object_manager = ObjectManager::new(
+            &core,
+            vec![Interest::new(ObjectType::Node)
+                .with_property("node.name", "test-object-manager")
+                .with_param(ParamType::EnumFormat)],
+        );
+        let events = Rc::new(RefCell::new(Vec::new()));
+        let _listener = object_manager
+            .add_listener_local()
+            .added({
+                let events = events.clone();
+                move |object| events.borrow_mut().push(Event::Added(object.id()))
+            })
+            .changed({
+                let events = events.clone();
+                move |object| events.borrow_mut().push(Event::Changed(object.id()))
+            })
+            .removed({
+                let events = events.clone();
+                move |object| events.borrow_mut().push(Event::Removed(object.id()))
+            })
+            .register();
+
+        let stream = Stream::new(
+            &core,
+            "test-object-manager",
+            properties! {
+                "node.name" => "test-object-manager",
+                "media.type" => "Audio",
+                "media.category" => "Playback"
+            },
+        )
+        .unwrap();
+        let info = AudioInfoRaw {
+            format: AudioFormat::S16LE,
+            ..AudioInfoRaw::new()
+        };
+        let mut data = Vec::new();
+        info.build(&mut Builder::with_vec(&mut data), ParamType::EnumFormat)
+            .unwrap();
+        stream
+            .connect(
+                Direction::Output,
+                None,
+                StreamFlags::INACTIVE,
+                &[Pod::from_bytes(&data).unwrap()],
+            )
+            .unwrap();
+
+        // The node is added, then changed once its info and params are received.
+        iterate_until(&main_loop, || {
+            events
+                .borrow()
+                .iter()
+                .any(|event| matches!(event, Event::Changed(_)))
+        });
+        let id = match events.borrow()[0] {
+            Event::Added(id) => id,
+            event => panic!("unexpected event {:?}", event),
+        };
+        let object = object_manager.get(id).unwrap();
+        assert!(object.info().is_some());
+        assert!(object.params(ParamType::EnumFormat).next().is_some());
+
+        // The params are not kept twice, and the changes are told once the params are received.
+        let mut pods: Vec<Vec<u8>> = object
+            .params(ParamType::EnumFormat)
+            .map(|pod| pod.as_bytes().to_vec())
+            .collect();
+        pods.sort();
+        pods.dedup();
+        assert_eq!(pods.len(), object.params(ParamType::EnumFormat).count());
+        assert!(events.borrow()[1..]
+            .iter()
+            .all(|event| *event == Event::Changed(id)));
+
+        drop(stream);
+        iterate_until(&main_loop, || {
+            events.borrow().last() == Some(&Event::Removed(id))
+        });
+        assert!(object_manager.objects().is_empty());
+    }
+}
@@ -4,13 +4,16 @@
 use bitflags::bitflags;
 use libc::c_void;
 use std::pin::Pin;
-use std::{fmt, mem};
+use std::rc::Rc;
+use std::{any::Any, fmt, mem, ptr, slice};
 
 use crate::{
     proxy::{Listener, Proxy, ProxyT},
     types::ObjectType,
+    utils::message_seq,
+    Error,
 };
-use spa::dict::ForeignDict;
+use spa::{dict::ForeignDict, param::ParamType, pod::Pod, spa_interface_call_method};
 
 #[derive(Debug)]
 pub struct Port {
@@ -39,7 +42,6 @@ impl ProxyT for Port {
 }
 
 impl Port {
-    // TODO: add non-local version when we'll bind pw_thread_loop_start()
     #[must_use]
     pub fn add_listener_local(&self) -> PortListenerLocalBuilder {
         PortListenerLocalBuilder {
@@ -47,13 +49,41 @@ impl Port {
             cbs: ListenerLocalCallbacks::default(),
         }
     }
+
+    /// Like [`add_listener_local`](Self::add_listener_local), but the callbacks can be called
+    /// from another thread, such as the one of a [`ThreadLoop`](crate::ThreadLoop).
+    #[must_use]
+    pub fn add_listener(&self) -> PortListenerBuilder {
+        PortListenerBuilder(self.add_listener_local())
+    }
+
+    /// Subscribe to the params of the port with the ids `ids`, which are then emitted to the
+    /// `param` callback of the listeners, and again each time they change.
+    ///
+    /// Returns the sequence number of the request, which is also the
+    /// [`seq`](crate::ServerError::seq) of the errors reported about it.
+    pub fn subscribe_params(&self, ids: &[ParamType]) -> Result<i32, Error> {
+        let mut ids: Vec<u32> = ids.iter().map(|id| id.as_raw()).collect();
+
+        let res = unsafe {
+            spa_interface_call_method!(
+                self.proxy.as_ptr(),
+                pw_sys::pw_port_methods,
+                subscribe_params,
+                ids.as_mut_ptr(),
+                ids.len() as u32
+            )
+        };
+
+        message_seq(res)
+    }
 }
 
 #[derive(Default)]
 struct ListenerLocalCallbacks {
     info: Option<Box<dyn Fn(&PortInfo)>>,
     #[allow(clippy::type_complexity)]
-    param: Option<Box<dyn Fn(i32, u32, u32, u32)>>, // TODO: add params
+    param: Option<Box<dyn Fn(i32, u32, u32, u32, Option<&Pod>)>>,
 }
 
 pub struct PortListenerLocalBuilder<'a> {
@@ -61,30 +91,74 @@ pub struct PortListenerLocalBuilder<'a> {
     cbs: ListenerLocalCallbacks,
 }
 
+/// Builder of a listener whose callbacks can be called from another thread.
+pub struct PortListenerBuilder<'a>(PortListenerLocalBuilder<'a>);
+
+impl<'a> PortListenerBuilder<'a> {
+    #[must_use]
+    pub fn info<F>(self, info: F) -> Self
+    where
+        F: Fn(&PortInfo) + Send + 'static,
+    {
+        Self(self.0.info(info))
+    }
+
+    #[must_use]
+    pub fn param<F>(self, param: F) -> Self
+    where
+        F: Fn(i32, u32, u32, u32, Option<&Pod>) + Send + 'static,
+    {
+        Self(self.0.param(param))
+    }
+
+    #[must_use]
+    pub fn register(self) -> PortListener {
+        self.0.register()
+    }
+}
+
 #[derive(Debug)]
 pub enum Direction {
     Input,
     Output,
 }
 
+/// The info of a port, which is either borrowed from the `info` callback, or a copy owning its
+/// data made with `clone`.
 pub struct PortInfo {
     ptr: *const pw_sys::pw_port_info,
     props: Option<ForeignDict>,
+    owned: bool,
 }
 
 impl PortInfo {
     fn new(ptr: *const pw_sys::pw_port_info) -> Self {
-        let props_ptr = unsafe { (*ptr).props };
         Self {
             ptr,
-            props: if props_ptr.is_null() {
-                None
-            } else {
-                Some(unsafe { ForeignDict::from_ptr(props_ptr) })
-            },
+            props: Self::props_of(ptr),
+            owned: false,
         }
     }
 
+    fn props_of(ptr: *const pw_sys::pw_port_info) -> Option<ForeignDict> {
+        let props_ptr = unsafe { (*ptr).props };
+        if props_ptr.is_null() {
+            None
+        } else {
+            Some(unsafe { ForeignDict::from_ptr(props_ptr) })
+        }
+    }
+
+    /// Update this copy with the fields of `update` that changed, as received by the `info`
+    /// callback, to keep the latest info of the port.
+    pub(crate) fn update(&mut self, update: &Self) {
+        assert!(self.owned, "only a copy of the info can be updated");
+
+        unsafe { pw_sys::pw_port_info_update(self.ptr as *mut _, update.ptr) };
+        // The properties are copied again when they changed.
+        self.props = Self::props_of(self.ptr);
+    }
+
     pub fn id(&self) -> u32 {
         unsafe { (*self.ptr).id }
     }
@@ -108,6 +182,45 @@ impl PortInfo {
         self.props.as_ref()
     }
     // TODO: params
+
+    /// The ids of the params of the port with their flags, whose `SPA_PARAM_INFO_SERIAL` bit
+    /// toggles each time the params with that id change.
+    pub(crate) fn raw_params(&self) -> &[spa_sys::spa_param_info] {
+        unsafe {
+            let info = &*self.ptr;
+            if info.params.is_null() {
+                &[]
+            } else {
+                slice::from_raw_parts(info.params, info.n_params as usize)
+            }
+        }
+    }
+}
+
+impl Clone for PortInfo {
+    fn clone(&self) -> Self {
+        unsafe {
+            // Copy all the fields, not only the ones that changed.
+            let mut info = *self.ptr;
+            info.change_mask = PortChangeMask::all().bits();
+            let ptr = pw_sys::pw_port_info_update(ptr::null_mut(), &info);
+            (*ptr).change_mask = (*self.ptr).change_mask;
+
+            Self {
+                ptr,
+                props: Self::props_of(ptr),
+                owned: true,
+            }
+        }
+    }
+}
+
+impl Drop for PortInfo {
+    fn drop(&mut self) {
+        if self.owned {
+            unsafe { pw_sys::pw_port_info_free(self.ptr as *mut _) }
+        }
+    }
 }
 
 bitflags! {
@@ -135,6 +248,9 @@ pub struct PortListener {
     listener: Pin<Box<spa_sys::spa_hook>>,
     #[allow(dead_code)]
     data: Box<ListenerLocalCallbacks>,
+    // Keep the proxy alive as long as the listener is registered on it
+    #[allow(dead_code)]
+    proxy: Rc<dyn Any>,
 }
 
 impl<'a> Listener for PortListener {}
@@ -158,7 +274,7 @@ impl<'a> PortListenerLocalBuilder<'a> {
     #[must_use]
     pub fn param<F>(mut self, param: F) -> Self
     where
-        F: Fn(i32, u32, u32, u32) + 'static,
+        F: Fn(i32, u32, u32, u32, Option<&Pod>) + 'static,
     {
         self.cbs.param = Some(Box::new(param));
         self
@@ -181,10 +297,15 @@ impl<'a> PortListenerLocalBuilder<'a> {
             id: u32,
             index: u32,
             next: u32,
-            _param: *const spa_sys::spa_pod,
+            param: *const spa_sys::spa_pod,
         ) {
             let callbacks = (data as *mut ListenerLocalCallbacks).as_ref().unwrap();
-            callbacks.param.as_ref().unwrap()(seq, id, index, next);
+            let param = if param.is_null() {
+                None
+            } else {
+                Some(Pod::from_raw(param))
+            };
+            callbacks.param.as_ref().unwrap()(seq, id, index, next, param.as_ref());
         }
 
         let e = unsafe {
@@ -223,6 +344,7 @@ impl<'a> PortListenerLocalBuilder<'a> {
             events: e,
             listener,
             data,
+            proxy: self.port.proxy.keep_alive(),
         }
     }
 }
@@ -1,5 +1,5 @@
 use spa::dict::{ReadableDict, WritableDict};
-use std::{ffi::CString, mem::ManuallyDrop};
+use std::{ffi::CString, fmt, mem::ManuallyDrop};
 
 /// A collection of key/value pairs.
 ///
@@ -99,6 +99,15 @@ impl Properties {
         this.ptr
     }
 
+    /// Create a `Properties` struct holding a copy of the key-value pairs of `dict`.
+    pub fn from_dict<D: ReadableDict>(dict: &D) -> Self {
+        unsafe {
+            let ptr = pw_sys::pw_properties_new_dict(dict.get_dict_ptr());
+
+            Self { ptr }
+        }
+    }
+
     // TODO: `fn from_string` that calls `pw_sys::pw_properties_new_string`
     // TODO: bindings for pw_properties_update_keys, pw_properties_update, pw_properties_add, pw_properties_add_keys
 }
@@ -136,6 +145,12 @@ impl Clone for Properties {
     }
 }
 
+impl fmt::Debug for Properties {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter_cstr()).finish()
+    }
+}
+
 impl Drop for Properties {
     fn drop(&mut self) {
         unsafe { pw_sys::pw_properties_free(self.ptr) }
@@ -3,22 +3,45 @@
 
 use libc::{c_char, c_void};
 use std::ffi::CStr;
-use std::fmt;
-use std::mem;
 use std::pin::Pin;
+use std::rc::Rc;
+use std::{any::Any, borrow::Cow, fmt, mem};
 
-use crate::{types::ObjectType, Error};
+use crate::{core_::Core, types::ObjectType, Error, ServerError};
 
-pub struct Proxy(*mut pw_sys::pw_proxy);
+/// A proxy to an object of the server.
+///
+/// The proxy is shared with its listeners, and destroyed once they are all dropped with it.
+pub struct Proxy {
+    inner: Rc<ProxyInner>,
+}
+
+struct ProxyInner {
+    ptr: *mut pw_sys::pw_proxy,
+    // The proxy is destroyed with the connection, so keep it alive as long as the proxy
+    core: Core,
+}
 
 // Wrapper around a proxy pointer
 impl Proxy {
-    pub(crate) fn new(proxy: *mut pw_sys::pw_proxy) -> Self {
-        Proxy(proxy)
+    pub(crate) fn new(proxy: *mut pw_sys::pw_proxy, core: Core) -> Self {
+        Proxy {
+            inner: Rc::new(ProxyInner { ptr: proxy, core }),
+        }
     }
 
     pub(crate) fn as_ptr(&self) -> *mut pw_sys::pw_proxy {
-        self.0
+        self.inner.ptr
+    }
+
+    /// A reference keeping the proxy alive, for the listeners registered on it.
+    pub(crate) fn keep_alive(&self) -> Rc<dyn Any> {
+        self.inner.clone()
+    }
+
+    /// The core the proxy was created from.
+    pub fn core(&self) -> &Core {
+        &self.inner.core
     }
 
     pub fn add_listener_local(&self) -> ProxyListenerLocalBuilder {
@@ -29,14 +52,14 @@ impl Proxy {
     }
 
     pub fn id(&self) -> u32 {
-        unsafe { pw_sys::pw_proxy_get_id(self.0) }
+        unsafe { pw_sys::pw_proxy_get_id(self.as_ptr()) }
     }
 
     /// Get the type of the proxy as well as it's version.
     pub fn get_type(&self) -> (ObjectType, u32) {
         unsafe {
             let mut version = 0;
-            let proxy_type = pw_sys::pw_proxy_get_type(self.0, &mut version);
+            let proxy_type = pw_sys::pw_proxy_get_type(self.as_ptr(), &mut version);
             let proxy_type = CStr::from_ptr(proxy_type);
 
             (
@@ -60,10 +83,10 @@ impl Proxy {
     }
 }
 
-impl Drop for Proxy {
+impl Drop for ProxyInner {
     fn drop(&mut self) {
         unsafe {
-            pw_sys::pw_proxy_destroy(self.0);
+            pw_sys::pw_proxy_destroy(self.ptr);
         }
     }
 }
@@ -114,6 +137,9 @@ pub struct ProxyListener {
     listener: Pin<Box<spa_sys::spa_hook>>,
     #[allow(dead_code)]
     data: Box<ListenerLocalCallbacks>,
+    // Keep the proxy alive as long as the listener is registered on it
+    #[allow(dead_code)]
+    proxy: Rc<dyn Any>,
 }
 
 impl<'a> Listener for ProxyListener {}
@@ -129,8 +155,9 @@ struct ListenerLocalCallbacks {
     bound: Option<Box<dyn Fn(u32)>>,
     removed: Option<Box<dyn Fn()>>,
     done: Option<Box<dyn Fn(i32)>>,
-    #[allow(clippy::type_complexity)]
-    error: Option<Box<dyn Fn(i32, i32, &str)>>, // TODO: return a proper Error enum?
+    error: Option<Box<dyn Fn(&ServerError)>>,
+    /// The id of the proxy, reported in its errors.
+    proxy_id: u32,
 }
 
 pub struct ProxyListenerLocalBuilder<'a> {
@@ -175,17 +202,18 @@ impl<'a> ProxyListenerLocalBuilder<'a> {
         self
     }
 
+    /// Called with the errors the server reports about the object of the proxy.
     #[must_use]
     pub fn error<F>(mut self, error: F) -> Self
     where
-        F: Fn(i32, i32, &str) + 'static,
+        F: Fn(&ServerError) + 'static,
     {
         self.cbs.error = Some(Box::new(error));
         self
     }
 
     #[must_use]
-    pub fn register(self) -> ProxyListener {
+    pub fn register(mut self) -> ProxyListener {
         unsafe extern "C" fn proxy_destroy(data: *mut c_void) {
             let callbacks = (data as *mut ListenerLocalCallbacks).as_ref().unwrap();
             callbacks.destroy.as_ref().unwrap()();
@@ -213,8 +241,14 @@ impl<'a> ProxyListenerLocalBuilder<'a> {
             message: *const c_char,
         ) {
             let callbacks = (data as *mut ListenerLocalCallbacks).as_ref().unwrap();
-            let message = CStr::from_ptr(message).to_str().unwrap();
-            callbacks.error.as_ref().unwrap()(seq, res, message);
+            // The message comes from the server, which may not send valid UTF-8.
+            let message = if message.is_null() {
+                Cow::Borrowed("")
+            } else {
+                CStr::from_ptr(message).to_string_lossy()
+            };
+            let error = ServerError::new(callbacks.proxy_id, seq, res, &message);
+            callbacks.error.as_ref().unwrap()(&error);
         }
 
         let e = unsafe {
@@ -247,6 +281,7 @@ impl<'a> ProxyListenerLocalBuilder<'a> {
         let (listener, data) = unsafe {
             let proxy = &self.proxy.as_ptr();
 
+            self.cbs.proxy_id = self.proxy.id();
             let data = Box::into_raw(Box::new(self.cbs));
             let mut listener: Pin<Box<spa_sys::spa_hook>> = Box::pin(mem::zeroed());
             let listener_ptr: *mut spa_sys::spa_hook = listener.as_mut().get_unchecked_mut();
@@ -266,6 +301,7 @@ impl<'a> ProxyListenerLocalBuilder<'a> {
             events: e,
             listener,
             data,
+            proxy: self.proxy.keep_alive(),
         }
     }
 }
@@ -4,25 +4,41 @@
 use bitflags::bitflags;
 use libc::{c_char, c_void};
 use std::ffi::{CStr, CString};
-use std::mem;
 use std::pin::Pin;
+use std::rc::Rc;
+use std::{any::Any, mem};
 
 use crate::{
+    core_::Core,
     proxy::{Proxy, ProxyT},
     types::ObjectType,
-    Error,
+    utils::message_seq,
+    Error, Properties,
 };
-use spa::dict::ForeignDict;
+use spa::dict::{ForeignDict, ReadableDict};
 
 #[derive(Debug)]
-pub struct Registry(*mut pw_sys::pw_registry);
+pub struct Registry {
+    // The registry is a proxy, shared with its listeners
+    proxy: Proxy,
+}
 
 impl Registry {
-    pub(crate) fn new(registry: *mut pw_sys::pw_registry) -> Self {
-        Registry(registry)
+    pub(crate) fn new(registry: *mut pw_sys::pw_registry, core: Core) -> Self {
+        Registry {
+            proxy: Proxy::new(registry.cast(), core),
+        }
+    }
+
+    fn as_ptr(&self) -> *mut pw_sys::pw_registry {
+        self.proxy.as_ptr().cast()
+    }
+
+    /// The core the registry was created from.
+    pub fn core(&self) -> &Core {
+        self.proxy.core()
     }
 
-    // TODO: add non-local version when we'll bind pw_thread_loop_start()
     #[must_use]
     pub fn add_listener_local(&self) -> ListenerLocalBuilder {
         ListenerLocalBuilder {
@@ -31,13 +47,20 @@ impl Registry {
         }
     }
 
-    pub fn bind<T: ProxyT>(&self, object: &GlobalObject) -> Result<T, Error> {
+    /// Like [`add_listener_local`](Self::add_listener_local), but the callbacks can be called
+    /// from another thread, such as the one of a [`ThreadLoop`](crate::ThreadLoop).
+    #[must_use]
+    pub fn add_listener(&self) -> ListenerBuilder {
+        ListenerBuilder(self.add_listener_local())
+    }
+
+    pub fn bind<T: ProxyT, D>(&self, object: &GlobalObject<D>) -> Result<T, Error> {
         let proxy = unsafe {
             let type_ = CString::new(object.type_.to_str()).unwrap();
             let version = object.type_.client_version();
 
             let proxy = spa::spa_interface_call_method!(
-                self.0,
+                self.as_ptr(),
                 pw_sys::pw_registry_methods,
                 bind,
                 object.id,
@@ -53,21 +76,35 @@ impl Registry {
             return Err(Error::NoMemory);
         }
 
-        Proxy::new(proxy.cast()).downcast().map_err(|(_, e)| e)
+        Proxy::new(proxy.cast(), self.core().clone())
+            .downcast()
+            .map_err(|(_, e)| e)
     }
-}
 
-impl Drop for Registry {
-    fn drop(&mut self) {
-        unsafe {
-            pw_sys::pw_proxy_destroy(self.0.cast());
-        }
+    /// Ask the server to destroy the global object with the id `global_id`.
+    ///
+    /// This needs the [`X`](Permission::X) permission on the object, otherwise the server reports
+    /// an `EPERM` error about the registry to the error listener of the core.
+    ///
+    /// Returns the sequence number of the request, which is also the
+    /// [`seq`](crate::ServerError::seq) of the errors reported about it.
+    pub fn destroy(&self, global_id: u32) -> Result<i32, Error> {
+        let res = unsafe {
+            spa::spa_interface_call_method!(
+                self.as_ptr(),
+                pw_sys::pw_registry_methods,
+                destroy,
+                global_id
+            )
+        };
+
+        message_seq(res)
     }
 }
 
 #[derive(Default)]
 struct ListenerLocalCallbacks {
-    global: Option<Box<dyn Fn(GlobalObject)>>,
+    global: Option<Box<dyn Fn(&GlobalObject<&ForeignDict>)>>,
     global_remove: Option<Box<dyn Fn(u32)>>,
 }
 
@@ -76,6 +113,32 @@ pub struct ListenerLocalBuilder<'a> {
     cbs: ListenerLocalCallbacks,
 }
 
+/// Builder of a listener whose callbacks can be called from another thread.
+pub struct ListenerBuilder<'a>(ListenerLocalBuilder<'a>);
+
+impl<'a> ListenerBuilder<'a> {
+    #[must_use]
+    pub fn global<F>(self, global: F) -> Self
+    where
+        F: Fn(&GlobalObject<&ForeignDict>) + Send + 'static,
+    {
+        Self(self.0.global(global))
+    }
+
+    #[must_use]
+    pub fn global_remove<F>(self, global_remove: F) -> Self
+    where
+        F: Fn(u32) + Send + 'static,
+    {
+        Self(self.0.global_remove(global_remove))
+    }
+
+    #[must_use]
+    pub fn register(self) -> Listener {
+        self.0.register()
+    }
+}
+
 pub struct Listener {
     // Need to stay allocated while the listener is registered
     #[allow(dead_code)]
@@ -83,6 +146,9 @@ pub struct Listener {
     listener: Pin<Box<spa_sys::spa_hook>>,
     #[allow(dead_code)]
     data: Box<ListenerLocalCallbacks>,
+    // Keep the registry alive as long as the listener is registered on it
+    #[allow(dead_code)]
+    registry: Rc<dyn Any>,
 }
 
 impl<'a> Drop for Listener {
@@ -92,10 +158,14 @@ impl<'a> Drop for Listener {
 }
 
 impl<'a> ListenerLocalBuilder<'a> {
+    /// Called for each global object, the existing ones first.
+    ///
+    /// The properties of the global are borrowed for the duration of the call, use
+    /// [`GlobalObject::to_owned`] to keep a copy of it.
     #[must_use]
     pub fn global<F>(mut self, global: F) -> Self
     where
-        F: Fn(GlobalObject) + 'static,
+        F: Fn(&GlobalObject<&ForeignDict>) + 'static,
     {
         self.cbs.global = Some(Box::new(global));
         self
@@ -121,9 +191,14 @@ impl<'a> ListenerLocalBuilder<'a> {
             props: *const spa_sys::spa_dict,
         ) {
             let type_ = CStr::from_ptr(type_).to_str().unwrap();
-            let obj = GlobalObject::new(id, permissions, type_, version, props);
+            let props = if props.is_null() {
+                None
+            } else {
+                Some(ForeignDict::from_ptr(props))
+            };
+            let obj = GlobalObject::new(id, permissions, type_, version, props.as_ref());
             let callbacks = (data as *mut ListenerLocalCallbacks).as_ref().unwrap();
-            callbacks.global.as_ref().unwrap()(obj);
+            callbacks.global.as_ref().unwrap()(&obj);
         }
 
         unsafe extern "C" fn registry_events_global_remove(data: *mut c_void, id: u32) {
@@ -146,7 +221,7 @@ impl<'a> ListenerLocalBuilder<'a> {
         };
 
         let (listener, data) = unsafe {
-            let ptr = self.registry.0;
+            let ptr = self.registry.as_ptr();
             let data = Box::into_raw(Box::new(self.cbs));
             let mut listener: Pin<Box<spa_sys::spa_hook>> = Box::pin(mem::zeroed());
             let listener_ptr: *mut spa_sys::spa_hook = listener.as_mut().get_unchecked_mut();
@@ -167,6 +242,7 @@ impl<'a> ListenerLocalBuilder<'a> {
             events: e,
             listener,
             data,
+            registry: self.registry.proxy.keep_alive(),
         }
     }
 }
@@ -177,33 +253,90 @@ bitflags! {
         const W = pw_sys::PW_PERM_W;
         const X = pw_sys::PW_PERM_X;
         const M = pw_sys::PW_PERM_M;
+        const L = pw_sys::PW_PERM_L;
     }
 }
 
-#[derive(Debug)]
-pub struct GlobalObject {
+/// A global object announced by a [`Registry`].
+///
+/// The registry hands out a `GlobalObject<&ForeignDict>` whose properties are borrowed for the
+/// duration of the callback, which can be turned into an owned `GlobalObject<Properties>` with
+/// [`to_owned`](Self::to_owned) to be kept around.
+#[derive(Debug, Clone)]
+pub struct GlobalObject<D> {
     pub id: u32,
     pub permissions: Permission,
     pub type_: ObjectType,
     pub version: u32,
-    pub props: Option<ForeignDict>,
+    pub props: Option<D>,
 }
 
-impl GlobalObject {
+impl<D> GlobalObject<D> {
+    /// Whether the object can be seen, and its methods returning information called.
+    pub fn can_read(&self) -> bool {
+        self.permissions.contains(Permission::R)
+    }
+
+    /// Whether the methods of the object modifying it can be called.
+    pub fn can_write(&self) -> bool {
+        self.permissions.contains(Permission::W)
+    }
+
+    /// Whether the object can be destroyed with [`Registry::destroy`], and its other methods
+    /// called.
+    pub fn can_execute(&self) -> bool {
+        self.permissions.contains(Permission::X)
+    }
+
+    /// Whether the metadata of the object can be changed.
+    pub fn can_modify_metadata(&self) -> bool {
+        self.permissions.contains(Permission::M)
+    }
+
+    /// Whether the object can be linked to other objects, even without being able to see them.
+    pub fn can_link(&self) -> bool {
+        self.permissions.contains(Permission::L)
+    }
+}
+
+impl<D: ReadableDict> GlobalObject<D> {
+    /// A copy of the global object, owning its properties.
+    pub fn to_owned(&self) -> GlobalObject<Properties> {
+        GlobalObject {
+            id: self.id,
+            permissions: self.permissions,
+            type_: self.type_.clone(),
+            version: self.version,
+            props: self.props.as_ref().map(Properties::from_dict),
+        }
+    }
+}
+
+#[cfg(test)]
+impl<D> GlobalObject<D> {
+    /// A readable global object of the current version, for the tests.
+    pub(crate) fn for_tests(id: u32, type_: ObjectType, props: D) -> Self {
+        Self {
+            id,
+            permissions: Permission::R,
+            type_,
+            version: 3,
+            props: Some(props),
+        }
+    }
+}
+
+impl<'a> GlobalObject<&'a ForeignDict> {
     fn new(
         id: u32,
         permissions: u32,
         type_: &str,
         version: u32,
-        props: *const spa_sys::spa_dict,
+        props: Option<&'a ForeignDict>,
     ) -> Self {
         let type_ = ObjectType::from_str(type_);
-        let permissions = Permission::from_bits(permissions).expect("invalid permissions");
-        let props = if props.is_null() {
-            None
-        } else {
-            Some(unsafe { ForeignDict::from_ptr(props) })
-        };
+        // Newer versions of PipeWire may add permissions unknown to the bindings.
+        let permissions = Permission::from_bits_truncate(permissions);
 
         Self {
             id,
@@ -238,4 +371,56 @@ mod tests {
         let o = ObjectType::Other("PipeWire:Interface:Badger".to_string());
         assert_eq!(o.client_version(), 0);
     }
+
+    #[test]
+    fn global_to_owned() {
+        let key = std::ffi::CString::new("node.name").unwrap();
+        let value = std::ffi::CString::new("sink").unwrap();
+        let item = spa_sys::spa_dict_item {
+            key: key.as_ptr(),
+            value: value.as_ptr(),
+        };
+        let dict = spa_sys::spa_dict {
+            flags: 0,
+            n_items: 1,
+            items: &item,
+        };
+
+        let dict = unsafe { ForeignDict::from_ptr(&dict) };
+
+        let global = GlobalObject::new(
+            42,
+            pw_sys::PW_PERM_R,
+            "PipeWire:Interface:Node",
+            3,
+            Some(&dict),
+        );
+        let owned = global.to_owned();
+        drop(global);
+
+        assert_eq!(owned.id, 42);
+        assert_eq!(owned.permissions, Permission::R);
+        assert!(owned.can_read());
+        assert!(!owned.can_write());
+        assert!(!owned.can_execute());
+        assert!(!owned.can_modify_metadata());
+        assert!(!owned.can_link());
+        assert_eq!(owned.type_, ObjectType::Node);
+        assert_eq!(owned.version, 3);
+        assert_eq!(owned.props.unwrap().get("node.name"), Some("sink"));
+    }
+
+    #[test]
+    fn unknown_permissions() {
+        let global = GlobalObject::new(
+            42,
+            pw_sys::PW_PERM_R | pw_sys::PW_PERM_L | 0x8000,
+            "PipeWire:Interface:Node",
+            3,
+            None,
+        );
+
+        assert_eq!(global.permissions, Permission::R | Permission::L);
+        assert!(global.can_link());
+    }
 }
@@ -0,0 +1,636 @@
+// Copyright 2021, Collabora Ltd.
+// SPDX-License-Identifier: MIT
+
+//! Streams, to send media to or receive media from other nodes of the graph.
+//!
+//! A [`Stream`] is the simplest way to play back or capture audio and video: PipeWire creates a
+//! node for it, negotiates a format with the node it is linked to and calls the `process`
+//! callback each time buffers need to be filled or consumed.
+
+use bitflags::bitflags;
+use libc::{c_char, c_void};
+use std::{
+    borrow::Cow,
+    ffi::{CStr, CString},
+    fmt,
+    marker::PhantomData,
+    mem::{self, ManuallyDrop},
+    pin::Pin,
+    ptr::{self, NonNull},
+};
+
+use crate::{core_::Core, error::Error, properties::Properties};
+use spa::{buffer::Data, pod::Pod, Direction, SpaResult};
+
+/// `PW_ID_ANY` is defined with a cast, which bindgen cannot evaluate.
+const PW_ID_ANY: u32 = 0xffff_ffff;
+
+/// A stream, created from a [`Core`] it cannot outlive.
+pub struct Stream<'c> {
+    ptr: NonNull<pw_sys::pw_stream>,
+    core: PhantomData<&'c Core>,
+}
+
+impl<'c> Stream<'c> {
+    /// Create a new, unconnected, stream.
+    ///
+    /// # Panics
+    /// If `name` contains a null byte.
+    pub fn new(core: &'c Core, name: &str, properties: Properties) -> Result<Self, Error> {
+        let name = CString::new(name).expect("Null byte in name parameter");
+
+        let stream =
+            unsafe { pw_sys::pw_stream_new(core.as_ptr(), name.as_ptr(), properties.into_raw()) };
+
+        NonNull::new(stream)
+            .map(|ptr| Stream {
+                ptr,
+                core: PhantomData,
+            })
+            .ok_or(Error::CreationFailed)
+    }
+
+    pub fn as_ptr(&self) -> *mut pw_sys::pw_stream {
+        self.ptr.as_ptr()
+    }
+
+    #[must_use]
+    pub fn add_listener_local(&self) -> ListenerLocalBuilder<'_, 'c> {
+        ListenerLocalBuilder {
+            stream: self,
+            cbs: ListenerLocalCallbacks::default(),
+        }
+    }
+
+    /// Connect the stream to the graph.
+    ///
+    /// # Parameters
+    /// - `direction`: whether the stream produces ([`Direction::Output`]) or consumes
+    ///   ([`Direction::Input`]) data
+    /// - `target_id`: the id of the node to link to, or `None` to let the session manager decide
+    /// - `flags`: how the stream should behave
+    /// - `params`: the params the stream supports, usually `EnumFormat` objects
+    pub fn connect(
+        &self,
+        direction: Direction,
+        target_id: Option<u32>,
+        flags: StreamFlags,
+        params: &[Pod],
+    ) -> Result<(), Error> {
+        self.connect_raw(direction, target_id, flags.bits(), params)
+    }
+
+    /// Connect the stream to the graph, like [`connect`](Self::connect), calling the `process`
+    /// callbacks from the realtime data thread instead of the thread of the loop.
+    ///
+    /// # Safety
+    /// The `process` callbacks of the listeners of the stream must be safe to call from another
+    /// thread, concurrently with the other callbacks: they must not access thread-local state nor
+    /// data shared with the rest of the program without synchronization.
+    /// They should also not block, allocate or take locks.
+    pub unsafe fn connect_rt(
+        &self,
+        direction: Direction,
+        target_id: Option<u32>,
+        flags: StreamFlags,
+        params: &[Pod],
+    ) -> Result<(), Error> {
+        self.connect_raw(
+            direction,
+            target_id,
+            flags.bits() | pw_sys::pw_stream_flags_PW_STREAM_FLAG_RT_PROCESS,
+            params,
+        )
+    }
+
+    fn connect_raw(
+        &self,
+        direction: Direction,
+        target_id: Option<u32>,
+        flags: u32,
+        params: &[Pod],
+    ) -> Result<(), Error> {
+        let mut params: Vec<*const spa_sys::spa_pod> = params.iter().map(Pod::as_ptr).collect();
+
+        let res = unsafe {
+            pw_sys::pw_stream_connect(
+                self.as_ptr(),
+                direction.as_raw(),
+                target_id.unwrap_or(PW_ID_ANY),
+                flags,
+                params.as_mut_ptr(),
+                params.len() as u32,
+            )
+        };
+
+        SpaResult::from_raw(res).into_sync_result()?;
+        Ok(())
+    }
+
+    /// Disconnect the stream from the graph, it can be connected again afterwards.
+    pub fn disconnect(&self) -> Result<(), Error> {
+        let res = unsafe { pw_sys::pw_stream_disconnect(self.as_ptr()) };
+
+        SpaResult::from_raw(res).into_sync_result()?;
+        Ok(())
+    }
+
+    /// Update the params of the stream, usually from the `param_changed` callback once
+    /// the format is known.
+    pub fn update_params(&self, params: &[Pod]) -> Result<(), Error> {
+        let mut params: Vec<*const spa_sys::spa_pod> = params.iter().map(Pod::as_ptr).collect();
+
+        let res = unsafe {
+            pw_sys::pw_stream_update_params(self.as_ptr(), params.as_mut_ptr(), params.len() as u32)
+        };
+
+        SpaResult::from_raw(res).into_sync_result()?;
+        Ok(())
+    }
+
+    /// Activate or deactivate the stream.
+    pub fn set_active(&self, active: bool) -> Result<(), Error> {
+        let res = unsafe { pw_sys::pw_stream_set_active(self.as_ptr(), active) };
+
+        SpaResult::from_raw(res).into_sync_result()?;
+        Ok(())
+    }
+
+    /// Flush the queued buffers.
+    ///
+    /// If `drain` is `true`, the `drained` callback is called once all the buffers are played.
+    pub fn flush(&self, drain: bool) -> Result<(), Error> {
+        let res = unsafe { pw_sys::pw_stream_flush(self.as_ptr(), drain) };
+
+        SpaResult::from_raw(res).into_sync_result()?;
+        Ok(())
+    }
+
+    pub fn name(&self) -> &str {
+        unsafe {
+            CStr::from_ptr(pw_sys::pw_stream_get_name(self.as_ptr()))
+                .to_str()
+                .unwrap()
+        }
+    }
+
+    pub fn state(&self) -> StreamState<'_> {
+        let mut error: *const c_char = ptr::null();
+        let state = unsafe { pw_sys::pw_stream_get_state(self.as_ptr(), &mut error) };
+
+        unsafe { StreamState::from_raw(state, error) }
+    }
+
+    /// The id of the node of the stream, only valid once the stream is connected.
+    pub fn node_id(&self) -> u32 {
+        unsafe { pw_sys::pw_stream_get_node_id(self.as_ptr()) }
+    }
+
+    /// Get a buffer to fill or read, if one is available.
+    ///
+    /// The buffer is handed back to the stream when it is dropped.
+    pub fn dequeue_buffer(&self) -> Option<Buffer<'_>> {
+        let buffer = unsafe { self.dequeue_raw_buffer() };
+
+        NonNull::new(buffer).map(|buffer| Buffer {
+            buffer,
+            stream: self,
+        })
+    }
+
+    /// Get a buffer to fill or read, if one is available, without wrapping it.
+    ///
+    /// # Safety
+    /// The buffer must be handed back with [`queue_raw_buffer`](Self::queue_raw_buffer) and must not
+    /// be used afterwards.
+    pub unsafe fn dequeue_raw_buffer(&self) -> *mut pw_sys::pw_buffer {
+        pw_sys::pw_stream_dequeue_buffer(self.as_ptr())
+    }
+
+    /// Hand a buffer obtained with [`dequeue_raw_buffer`](Self::dequeue_raw_buffer) back to
+    /// the stream.
+    ///
+    /// # Safety
+    /// `buffer` must have been dequeued from this stream and not queued since.
+    pub unsafe fn queue_raw_buffer(&self, buffer: *mut pw_sys::pw_buffer) -> Result<(), Error> {
+        let res = pw_sys::pw_stream_queue_buffer(self.as_ptr(), buffer);
+
+        SpaResult::from_raw(res).into_sync_result()?;
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Stream<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Stream")
+            .field("name", &self.name())
+            .field("state", &self.state())
+            .field("node-id", &self.node_id())
+            .finish()
+    }
+}
+
+impl Drop for Stream<'_> {
+    fn drop(&mut self) {
+        unsafe { pw_sys::pw_stream_destroy(self.as_ptr()) }
+    }
+}
+
+/// A buffer dequeued from a [`Stream`], queued back when dropped.
+pub struct Buffer<'s> {
+    buffer: NonNull<pw_sys::pw_buffer>,
+    stream: &'s Stream<'s>,
+}
+
+impl Buffer<'_> {
+    pub fn as_ptr(&self) -> *mut pw_sys::pw_buffer {
+        self.buffer.as_ptr()
+    }
+
+    /// The data planes of the buffer.
+    pub fn datas_mut(&mut self) -> &mut [Data] {
+        unsafe {
+            let buffer = (*self.as_ptr()).buffer;
+            if buffer.is_null() {
+                return &mut [];
+            }
+
+            Data::from_raw_mut((*buffer).datas, (*buffer).n_datas)
+        }
+    }
+}
+
+impl fmt::Debug for Buffer<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Buffer").field("ptr", &self.buffer).finish()
+    }
+}
+
+impl Drop for Buffer<'_> {
+    fn drop(&mut self) {
+        // Errors can only come from a buffer that does not belong to the stream, which the
+        // constructor rules out.
+        let _ = unsafe { self.stream.queue_raw_buffer(self.as_ptr()) };
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StreamState<'a> {
+    /// The stream is in error, with the error message, which is empty if there is none.
+    Error(Cow<'a, str>),
+    Unconnected,
+    Connecting,
+    Paused,
+    Streaming,
+    /// A state unknown to this version of the bindings.
+    Unknown(u32),
+}
+
+impl<'a> StreamState<'a> {
+    /// # Safety
+    /// `error` must be null or point to a string valid for `'a`.
+    unsafe fn from_raw(state: pw_sys::pw_stream_state, error: *const c_char) -> Self {
+        match state {
+            pw_sys::pw_stream_state_PW_STREAM_STATE_ERROR => {
+                let error = if error.is_null() {
+                    Cow::Borrowed("")
+                } else {
+                    CStr::from_ptr(error).to_string_lossy()
+                };
+                StreamState::Error(error)
+            }
+            pw_sys::pw_stream_state_PW_STREAM_STATE_UNCONNECTED => StreamState::Unconnected,
+            pw_sys::pw_stream_state_PW_STREAM_STATE_CONNECTING => StreamState::Connecting,
+            pw_sys::pw_stream_state_PW_STREAM_STATE_PAUSED => StreamState::Paused,
+            pw_sys::pw_stream_state_PW_STREAM_STATE_STREAMING => StreamState::Streaming,
+            _ => StreamState::Unknown(state),
+        }
+    }
+}
+
+bitflags! {
+    pub struct StreamFlags: u32 {
+        /// Try to automatically connect the stream.
+        const AUTOCONNECT = pw_sys::pw_stream_flags_PW_STREAM_FLAG_AUTOCONNECT;
+        /// Start the stream inactive, [`Stream::set_active`] needs to be called to start it.
+        const INACTIVE = pw_sys::pw_stream_flags_PW_STREAM_FLAG_INACTIVE;
+        /// Map the buffers in memory, so their data can be accessed directly.
+        const MAP_BUFFERS = pw_sys::pw_stream_flags_PW_STREAM_FLAG_MAP_BUFFERS;
+        /// Be a driver of the graph.
+        const DRIVER = pw_sys::pw_stream_flags_PW_STREAM_FLAG_DRIVER;
+        /// Do not convert the format of the stream.
+        const NO_CONVERT = pw_sys::pw_stream_flags_PW_STREAM_FLAG_NO_CONVERT;
+        /// Require exclusive access to the device.
+        const EXCLUSIVE = pw_sys::pw_stream_flags_PW_STREAM_FLAG_EXCLUSIVE;
+        /// Do not try to reconnect the stream when the target goes away.
+        const DONT_RECONNECT = pw_sys::pw_stream_flags_PW_STREAM_FLAG_DONT_RECONNECT;
+        /// Let the application allocate the buffer memory.
+        const ALLOC_BUFFERS = pw_sys::pw_stream_flags_PW_STREAM_FLAG_ALLOC_BUFFERS;
+    }
+}
+
+#[derive(Default)]
+struct ListenerLocalCallbacks {
+    stream: Option<NonNull<pw_sys::pw_stream>>,
+    #[allow(clippy::type_complexity)]
+    state_changed: Option<Box<dyn Fn(&Stream, StreamState, StreamState)>>,
+    #[allow(clippy::type_complexity)]
+    param_changed: Option<Box<dyn Fn(&Stream, u32, Option<&Pod>)>>,
+    add_buffer: Option<Box<dyn Fn(&Stream, *mut pw_sys::pw_buffer)>>,
+    remove_buffer: Option<Box<dyn Fn(&Stream, *mut pw_sys::pw_buffer)>>,
+    process: Option<Box<dyn Fn(&Stream)>>,
+    drained: Option<Box<dyn Fn(&Stream)>>,
+}
+
+impl ListenerLocalCallbacks {
+    /// Borrow the stream the callbacks are registered on, without taking ownership of it.
+    ///
+    /// # Safety
+    /// Must only be called from a stream event, while the stream is alive.
+    unsafe fn stream(&self) -> ManuallyDrop<Stream<'_>> {
+        ManuallyDrop::new(Stream {
+            ptr: self.stream.unwrap(),
+            core: PhantomData,
+        })
+    }
+}
+
+pub struct ListenerLocalBuilder<'s, 'c> {
+    stream: &'s Stream<'c>,
+    cbs: ListenerLocalCallbacks,
+}
+
+/// A listener on the events of a [`Stream`], which it cannot outlive.
+pub struct StreamListener<'s> {
+    // Need to stay allocated while the listener is registered
+    #[allow(dead_code)]
+    events: Pin<Box<pw_sys::pw_stream_events>>,
+    listener: Pin<Box<spa_sys::spa_hook>>,
+    #[allow(dead_code)]
+    data: Box<ListenerLocalCallbacks>,
+    stream: PhantomData<&'s ()>,
+}
+
+impl StreamListener<'_> {
+    pub fn unregister(self) {
+        // Consuming the listener will call drop()
+    }
+}
+
+impl Drop for StreamListener<'_> {
+    fn drop(&mut self) {
+        spa::hook::remove(*self.listener);
+    }
+}
+
+impl<'s, 'c> ListenerLocalBuilder<'s, 'c> {
+    /// Called with the old and new state when the state of the stream changes.
+    #[must_use]
+    pub fn state_changed<F>(mut self, state_changed: F) -> Self
+    where
+        F: Fn(&Stream, StreamState, StreamState) + 'static,
+    {
+        self.cbs.state_changed = Some(Box::new(state_changed));
+        self
+    }
+
+    /// Called with the param id and the new value when a param of the stream changes,
+    /// such as the negotiated `Format`.
+    #[must_use]
+    pub fn param_changed<F>(mut self, param_changed: F) -> Self
+    where
+        F: Fn(&Stream, u32, Option<&Pod>) + 'static,
+    {
+        self.cbs.param_changed = Some(Box::new(param_changed));
+        self
+    }
+
+    /// Called when a buffer is added to the stream.
+    #[must_use]
+    pub fn add_buffer<F>(mut self, add_buffer: F) -> Self
+    where
+        F: Fn(&Stream, *mut pw_sys::pw_buffer) + 'static,
+    {
+        self.cbs.add_buffer = Some(Box::new(add_buffer));
+        self
+    }
+
+    /// Called when a buffer is removed from the stream.
+    #[must_use]
+    pub fn remove_buffer<F>(mut self, remove_buffer: F) -> Self
+    where
+        F: Fn(&Stream, *mut pw_sys::pw_buffer) + 'static,
+    {
+        self.cbs.remove_buffer = Some(Box::new(remove_buffer));
+        self
+    }
+
+    /// Called when buffers can be dequeued and filled or read.
+    ///
+    /// This is called from the thread of the loop, unless the stream is connected with
+    /// [`Stream::connect_rt`].
+    #[must_use]
+    pub fn process<F>(mut self, process: F) -> Self
+    where
+        F: Fn(&Stream) + 'static,
+    {
+        self.cbs.process = Some(Box::new(process));
+        self
+    }
+
+    /// Called once all the buffers are played after [`Stream::flush`] was called with
+    /// `drain` set.
+    #[must_use]
+    pub fn drained<F>(mut self, drained: F) -> Self
+    where
+        F: Fn(&Stream) + 'static,
+    {
+        self.cbs.drained = Some(Box::new(drained));
+        self
+    }
+
+    #[must_use]
+    pub fn register(mut self) -> StreamListener<'s> {
+        unsafe extern "C" fn stream_events_state_changed(
+            data: *mut c_void,
+            old: pw_sys::pw_stream_state,
+            state: pw_sys::pw_stream_state,
+            error: *const c_char,
+        ) {
+            let callbacks = (data as *mut ListenerLocalCallbacks).as_ref().unwrap();
+            let stream = callbacks.stream();
+            let old = StreamState::from_raw(old, ptr::null());
+            let state = StreamState::from_raw(state, error);
+            callbacks.state_changed.as_ref().unwrap()(&stream, old, state);
+        }
+
+        unsafe extern "C" fn stream_events_param_changed(
+            data: *mut c_void,
+            id: u32,
+            param: *const spa_sys::spa_pod,
+        ) {
+            let callbacks = (data as *mut ListenerLocalCallbacks).as_ref().unwrap();
+            let stream = callbacks.stream();
+            let param = if param.is_null() {
+                None
+            } else {
+                Some(Pod::from_raw(param))
+            };
+            callbacks.param_changed.as_ref().unwrap()(&stream, id, param.as_ref());
+        }
+
+        unsafe extern "C" fn stream_events_add_buffer(
+            data: *mut c_void,
+            buffer: *mut pw_sys::pw_buffer,
+        ) {
+            let callbacks = (data as *mut ListenerLocalCallbacks).as_ref().unwrap();
+            let stream = callbacks.stream();
+            callbacks.add_buffer.as_ref().unwrap()(&stream, buffer);
+        }
+
+        unsafe extern "C" fn stream_events_remove_buffer(
+            data: *mut c_void,
+            buffer: *mut pw_sys::pw_buffer,
+        ) {
+            let callbacks = (data as *mut ListenerLocalCallbacks).as_ref().unwrap();
+            let stream = callbacks.stream();
+            callbacks.remove_buffer.as_ref().unwrap()(&stream, buffer);
+        }
+
+        unsafe extern "C" fn stream_events_process(data: *mut c_void) {
+            let callbacks = (data as *mut ListenerLocalCallbacks).as_ref().unwrap();
+            let stream = callbacks.stream();
+            callbacks.process.as_ref().unwrap()(&stream);
+        }
+
+        unsafe extern "C" fn stream_events_drained(data: *mut c_void) {
+            let callbacks = (data as *mut ListenerLocalCallbacks).as_ref().unwrap();
+            let stream = callbacks.stream();
+            callbacks.drained.as_ref().unwrap()(&stream);
+        }
+
+        let e = unsafe {
+            let mut e: Pin<Box<pw_sys::pw_stream_events>> = Box::pin(mem::zeroed());
+            e.version = pw_sys::PW_VERSION_STREAM_EVENTS;
+
+            if self.cbs.state_changed.is_some() {
+                e.state_changed = Some(stream_events_state_changed);
+            }
+            if self.cbs.param_changed.is_some() {
+                e.param_changed = Some(stream_events_param_changed);
+            }
+            if self.cbs.add_buffer.is_some() {
+                e.add_buffer = Some(stream_events_add_buffer);
+            }
+            if self.cbs.remove_buffer.is_some() {
+                e.remove_buffer = Some(stream_events_remove_buffer);
+            }
+            if self.cbs.process.is_some() {
+                e.process = Some(stream_events_process);
+            }
+            if self.cbs.drained.is_some() {
+                e.drained = Some(stream_events_drained);
+            }
+
+            e
+        };
+
+        self.cbs.stream = Some(self.stream.ptr);
+
+        let (listener, data) = unsafe {
+            let data = Box::into_raw(Box::new(self.cbs));
+            let mut listener: Pin<Box<spa_sys::spa_hook>> = Box::pin(mem::zeroed());
+            let listener_ptr: *mut spa_sys::spa_hook = listener.as_mut().get_unchecked_mut();
+
+            pw_sys::pw_stream_add_listener(
+                self.stream.as_ptr(),
+                listener_ptr,
+                e.as_ref().get_ref(),
+                data as *mut _,
+            );
+
+            (listener, Box::from_raw(data))
+        };
+
+        StreamListener {
+            events: e,
+            listener,
+            data,
+            stream: PhantomData,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use super::*;
+    use crate::{
+        properties,
+        utils::{connect_to_private_server, iterate_until},
+        MainLoop,
+    };
+    use spa::{
+        param::{
+            audio::{AudioChannel, AudioFormat, AudioInfoRaw},
+            ParamType,
+        },
+        pod::Builder,
+    };
+
+    #[test]
+    fn connect() {
+        crate::init();
+        let main_loop = MainLoop::new().unwrap();
+        let (_context, core) = connect_to_private_server(&main_loop);
+
+        let stream = Stream::new(
+            &core,
+            "test-stream",
+            properties! {
+                "media.type" => "Audio",
+                "media.category" => "Playback"
+            },
+        )
+        .unwrap();
+        assert_eq!(stream.name(), "test-stream");
+        assert_eq!(stream.state(), StreamState::Unconnected);
+
+        let states = Rc::new(RefCell::new(Vec::new()));
+        let _listener = stream
+            .add_listener_local()
+            .state_changed({
+                let states = states.clone();
+                move |_, _, state| states.borrow_mut().push(format!("{:?}", state))
+            })
+            .register();
+
+        let info = AudioInfoRaw {
+            format: AudioFormat::F32LE,
+            rate: 48000,
+            channels: 2,
+            position: vec![AudioChannel::FL, AudioChannel::FR],
+            ..AudioInfoRaw::new()
+        };
+        let mut data = Vec::new();
+        info.build(&mut Builder::with_vec(&mut data), ParamType::EnumFormat)
+            .unwrap();
+        let format = Pod::from_bytes(&data).unwrap();
+
+        stream
+            .connect(Direction::Output, None, StreamFlags::INACTIVE, &[format])
+            .unwrap();
+        iterate_until(&main_loop, || match stream.state() {
+            StreamState::Error(error) => panic!("stream error: {}", error),
+            StreamState::Connecting | StreamState::Unconnected => false,
+            _ => true,
+        });
+        assert_eq!(stream.state(), StreamState::Paused);
+        assert_ne!(stream.node_id(), PW_ID_ANY);
+        assert!(states.borrow().contains(&"Paused".to_string()));
+
+        stream.disconnect().unwrap();
+        assert_eq!(stream.state(), StreamState::Unconnected);
+    }
+}
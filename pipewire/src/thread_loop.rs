@@ -0,0 +1,207 @@
+// Copyright 2021, Collabora Ltd.
+// SPDX-License-Identifier: MIT
+
+use std::ffi::CString;
+use std::io;
+use std::ops::Deref;
+use std::ptr;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use crate::error::Error;
+use crate::loop_::{invoke_nonblocking_raw, invoke_raw, Loop};
+use spa::SpaResult;
+
+/// A loop running in its own thread.
+///
+/// The loop is shared with its thread, so it must be locked with [`lock`](ThreadLoopInner::lock)
+/// before doing anything with the objects it runs, except from the callbacks of these objects,
+/// which are already called with the lock held.
+///
+/// Unlike a [`MainLoop`](crate::MainLoop), it is not a [`LocalLoop`](crate::LocalLoop), so only
+/// sources with [`Send`] callbacks can be added to it, with the lock held.
+#[derive(Debug, Clone)]
+pub struct ThreadLoop {
+    inner: Arc<ThreadLoopInner>,
+}
+
+impl ThreadLoop {
+    /// Create a new loop, whose thread will be named `name`.
+    ///
+    /// # Panics
+    /// If `name` contains a null byte.
+    pub fn new(name: Option<&str>) -> Result<Self, Error> {
+        let inner = ThreadLoopInner::new(name)?;
+        Ok(Self {
+            inner: Arc::new(inner),
+        })
+    }
+}
+
+impl Deref for ThreadLoop {
+    type Target = ThreadLoopInner;
+
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+
+impl Loop for ThreadLoop {
+    fn as_ptr(&self) -> *mut pw_sys::pw_loop {
+        unsafe { pw_sys::pw_thread_loop_get_loop(self.inner.ptr) }
+    }
+
+    /// See [`Loop::invoke`].
+    ///
+    /// This must not be called from another thread with the lock held, as the thread of the loop
+    /// needs it to call `func`.
+    fn invoke<F, R>(&self, func: F) -> Result<R, Error>
+    where
+        F: FnOnce() -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        let _guard = self.lock_invoke();
+        unsafe { invoke_raw(self.as_ptr(), func) }
+    }
+
+    fn invoke_nonblocking<F>(&self, func: F) -> Result<(), Error>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let _guard = self.lock_invoke();
+        unsafe { invoke_nonblocking_raw(self.as_ptr(), func) }
+    }
+}
+
+#[derive(Debug)]
+pub struct ThreadLoopInner {
+    ptr: *mut pw_sys::pw_thread_loop,
+    /// Locked while invoking from other threads, so they don't do it at the same time.
+    invoke_lock: Mutex<()>,
+}
+
+// The pw_thread_loop functions can be called from any thread, they do their own locking.
+unsafe impl Send for ThreadLoopInner {}
+unsafe impl Sync for ThreadLoopInner {}
+
+impl ThreadLoopInner {
+    // TODO: props argument
+    fn new(name: Option<&str>) -> Result<Self, Error> {
+        let name = name.map(|name| CString::new(name).expect("Null byte in name parameter"));
+
+        unsafe {
+            let l = pw_sys::pw_thread_loop_new(
+                name.as_ref().map_or(ptr::null(), |name| name.as_ptr()),
+                ptr::null(),
+            );
+            if l.is_null() {
+                Err(Error::CreationFailed)
+            } else {
+                Ok(ThreadLoopInner {
+                    ptr: l,
+                    invoke_lock: Mutex::new(()),
+                })
+            }
+        }
+    }
+
+    /// Start the thread running the loop.
+    pub fn start(&self) -> Result<(), Error> {
+        let res = unsafe { pw_sys::pw_thread_loop_start(self.ptr) };
+
+        SpaResult::from_raw(res).into_sync_result()?;
+        Ok(())
+    }
+
+    /// Stop the loop and wait for its thread to finish.
+    ///
+    /// Must not be called with the lock held or from the thread of the loop.
+    pub fn stop(&self) {
+        unsafe { pw_sys::pw_thread_loop_stop(self.ptr) }
+    }
+
+    /// Lock the loop, the lock is released when the returned guard is dropped.
+    ///
+    /// The lock is recursive, so it can also be taken from the callbacks of the loop.
+    #[must_use]
+    pub fn lock(&self) -> ThreadLoopLockGuard<'_> {
+        unsafe { pw_sys::pw_thread_loop_lock(self.ptr) };
+        ThreadLoopLockGuard { thread_loop: self }
+    }
+
+    /// Wake up the threads waiting with [`ThreadLoopLockGuard::wait`].
+    ///
+    /// If `wait_for_accept` is `true`, this waits until one of them calls [`accept`](Self::accept).
+    /// Must be called with the lock held, usually from a callback of the loop.
+    pub fn signal(&self, wait_for_accept: bool) {
+        unsafe { pw_sys::pw_thread_loop_signal(self.ptr, wait_for_accept) }
+    }
+
+    /// Let the thread of the loop that called [`signal`](Self::signal) go on.
+    ///
+    /// Must be called with the lock held.
+    pub fn accept(&self) {
+        unsafe { pw_sys::pw_thread_loop_accept(self.ptr) }
+    }
+
+    /// Whether this is called from the thread of the loop.
+    pub fn in_thread(&self) -> bool {
+        unsafe { pw_sys::pw_thread_loop_in_thread(self.ptr) }
+    }
+
+    /// Take the lock of the invocations, unless called from the thread of the loop, which calls
+    /// them right away.
+    fn lock_invoke(&self) -> Option<MutexGuard<'_, ()>> {
+        if self.in_thread() {
+            None
+        } else {
+            Some(self.invoke_lock.lock().unwrap())
+        }
+    }
+}
+
+impl Drop for ThreadLoopInner {
+    fn drop(&mut self) {
+        unsafe { pw_sys::pw_thread_loop_destroy(self.ptr) }
+    }
+}
+
+/// The lock of a [`ThreadLoop`], released when dropped.
+#[derive(Debug)]
+pub struct ThreadLoopLockGuard<'a> {
+    thread_loop: &'a ThreadLoopInner,
+}
+
+impl ThreadLoopLockGuard<'_> {
+    /// Release the lock and wait until the loop calls [`signal`](ThreadLoopInner::signal),
+    /// then take the lock again.
+    pub fn wait(&self) {
+        unsafe { pw_sys::pw_thread_loop_wait(self.thread_loop.ptr) }
+    }
+
+    /// Like [`wait`](Self::wait), but give up after `timeout`, which is rounded up to
+    /// the second.
+    ///
+    /// Returns an error with the `ETIMEDOUT` code if the loop did not signal in time.
+    pub fn timed_wait(&self, timeout: Duration) -> Result<(), Error> {
+        let secs = timeout.as_secs() + u64::from(timeout.subsec_nanos() > 0);
+        let res = unsafe {
+            pw_sys::pw_thread_loop_timed_wait(
+                self.thread_loop.ptr,
+                secs.min(i32::MAX as u64) as i32,
+            )
+        };
+
+        // Older versions of PipeWire return a positive ETIMEDOUT.
+        match res {
+            0 => Ok(()),
+            res => Err(io::Error::from_raw_os_error(res.abs()).into()),
+        }
+    }
+}
+
+impl Drop for ThreadLoopLockGuard<'_> {
+    fn drop(&mut self) {
+        unsafe { pw_sys::pw_thread_loop_unlock(self.thread_loop.ptr) }
+    }
+}
@@ -0,0 +1,55 @@
+// Copyright 2021, Collabora Ltd.
+// SPDX-License-Identifier: MIT
+
+//! Drive a PipeWire loop from a tokio runtime, available with the `tokio` feature.
+
+use ::tokio::io::{unix::AsyncFd, Interest};
+use std::{
+    io,
+    os::unix::io::{AsRawFd, RawFd},
+    time::Duration,
+};
+
+use crate::{error::Error, loop_::Loop};
+
+struct LoopFd(RawFd);
+
+impl AsRawFd for LoopFd {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0
+    }
+}
+
+/// Dispatch the events of `loop_` as they arrive, instead of running it.
+///
+/// The returned future only completes if the loop fails. As the PipeWire objects are not `Send`,
+/// it has to be run on the thread that created them, such as with a `LocalSet` or a current
+/// thread runtime.
+///
+/// # Examples
+/// ```ignore
+/// use pipewire as pw;
+///
+/// let mainloop = pw::MainLoop::new()?;
+/// let context = pw::Context::new(&mainloop)?;
+/// let core = context.connect(None)?;
+///
+/// tokio::task::spawn_local(async move { pw::tokio::run(&mainloop).await });
+/// ```
+pub async fn run<L: Loop>(loop_: &L) -> Result<(), Error> {
+    let fd = AsyncFd::with_interest(LoopFd(loop_.fd()), Interest::READABLE)?;
+
+    loop {
+        let mut guard = fd.readable().await?;
+
+        // Only clear the readiness once all the events have been dispatched.
+        match guard.try_io(|_| match loop_.iterate(Duration::from_secs(0)) {
+            Ok(0) => Err(io::ErrorKind::WouldBlock.into()),
+            Ok(_) => Ok(Ok(())),
+            Err(err) => Ok(Err(err)),
+        }) {
+            Ok(res) => res??,
+            Err(_would_block) => continue,
+        }
+    }
+}
@@ -3,6 +3,86 @@
 
 use std::thread;
 
+use crate::error::Error;
+use spa::SpaResult;
+#[cfg(test)]
+use {
+    crate::{properties, Context, Core, Loop, MainLoop},
+    std::{env, process, time::Duration},
+};
+
 pub fn assert_main_thread() {
     assert_eq!(thread::current().name(), Some("main"));
 }
+
+/// Convert the result of a method sending a message to the server, which is the sequence number
+/// of the message returned as an async result, to this sequence number.
+///
+/// The errors the server reports about the message have the same sequence number.
+pub(crate) fn message_seq(res: i32) -> Result<i32, Error> {
+    let res = SpaResult::from_raw(res);
+    if res.is_async() {
+        Ok(res.unwrap_async())
+    } else {
+        // The message was not sent, such as for a proxy whose object was removed.
+        Ok(res.into_sync_result()?)
+    }
+}
+
+/// A context serving the PipeWire protocol on its own socket, and a core connected to it
+/// from the same loop.
+#[cfg(test)]
+pub(crate) fn connect_to_private_server(main_loop: &MainLoop) -> (Context<MainLoop>, Core) {
+    if env::var_os("XDG_RUNTIME_DIR").is_none() {
+        env::set_var("XDG_RUNTIME_DIR", env::temp_dir());
+    }
+    let name = format!(
+        "pipewire-rs-test-{}-{:?}",
+        process::id(),
+        thread::current().id()
+    );
+
+    let context = Context::with_properties(
+        main_loop,
+        properties! {
+            "core.daemon" => "true",
+            "core.name" => name.as_str()
+        },
+    )
+    .unwrap();
+    // The modules may already be loaded by the configuration of the context.
+    let _ = context.load_module("libpipewire-module-protocol-native", None, None);
+    // Streams and filters wrap their node in an adapter and export it through a client node.
+    let _ = context.load_module("libpipewire-module-client-node", None, None);
+    let _ = context.load_module("libpipewire-module-adapter", None, None);
+
+    let core = context
+        .connect(Some(properties! { "remote.name" => name.as_str() }))
+        .unwrap();
+
+    (context, core)
+}
+
+/// Iterate `loop_` until `done` returns `true`, panicking if it takes more than a few seconds.
+#[cfg(test)]
+pub(crate) fn iterate_until<L: Loop>(loop_: &L, mut done: impl FnMut() -> bool) {
+    for _ in 0..50 {
+        if done() {
+            return;
+        }
+        loop_.iterate(Duration::from_millis(100)).unwrap();
+    }
+    assert!(done(), "timed out iterating the loop");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn message_seq_results() {
+        assert_eq!(message_seq(SpaResult::new_async(3).into_raw()).unwrap(), 3);
+        assert_eq!(message_seq(0).unwrap(), 0);
+        assert!(message_seq(-libc::EINVAL).is_err());
+    }
+}